@@ -1,12 +1,12 @@
-///
-/// This example uses a boltzman machine to solve a sudoku in a stochastic way.
-///
-/// Most of this file is actually sued to convert the sudoku into a problem solvable by
-/// a Boltzmann Machine.
-///
-/// The sudoku is encoded as a 81*9 neuron network: 81 grousp of 9 neurons, each of these
-/// groups representing one emplacement in the sudoku. The 9 neurons represent the 9 possible
-/// values of the emplacement, so in a solution only one of them should be activated at a given time.
+//!
+//! This example uses a boltzman machine to solve a sudoku in a stochastic way.
+//!
+//! Most of this file is actually sued to convert the sudoku into a problem solvable by
+//! a Boltzmann Machine.
+//!
+//! The sudoku is encoded as a 81*9 neuron network: 81 grousp of 9 neurons, each of these
+//! groups representing one emplacement in the sudoku. The 9 neurons represent the 9 possible
+//! values of the emplacement, so in a solution only one of them should be activated at a given time.
 
 extern crate silinapse;
 
@@ -41,7 +41,7 @@ static INPUT_SUDOKU: [u8; 81] = [
     2, 0, 0, 9, 0, 1, 0, 0, 7,
 ];
 
-static NUM_CHARS: [&'static str; 10] = [
+static NUM_CHARS: [&str; 10] = [
     "0", "1", "2", "3", "4",
     "5", "6", "7", "8", "9",
 ];
@@ -63,7 +63,7 @@ fn display_sudoku(s: &[u8]) {
             else { "X" }
         );
         if i % 3 == 2 { print!("| "); }
-        if i % 9 == 8 { println!(""); }
+        if i % 9 == 8 { println!(); }
         if i % 27 == 26 {
             println!("+-------+-------+-------+");
         }
@@ -183,7 +183,7 @@ fn main() {
 
     // create the machine with small positive biases, to ensure some units get activated
     let links = generate_links();
-    let mut machine = BoltzmannMachine::with_biases(links, ::std::iter::repeat(10.0).take(81*9).collect());
+    let mut machine = BoltzmannMachine::with_biases(links, vec![10.0; 81*9]);
 
     // intialize the structure of the machine
     let fixed = list_fixed(&INPUT_SUDOKU);
@@ -199,9 +199,8 @@ fn main() {
         let _ = ::std::io::stdout().flush();
         let mut buf = String::new();
         let _ = ::std::io::stdin().read_line(&mut buf);
-        match buf.trim().parse() {
-            Ok(t) => { temperature = t; }
-            Err(_) => {}
+        if let Ok(t) = buf.trim().parse() {
+            temperature = t;
         }
         // let the user choose how many ticks should be done
         print!("Tick count ? [{}] :", ticks);
@@ -218,7 +217,7 @@ fn main() {
         for _ in 0..ticks {
             machine.tick_one_random(temperature, &fixed);
             display_machine(&machine);
-            std::thread::sleep_ms(10);
+            std::thread::sleep(std::time::Duration::from_millis(10));
         }
     }
 }
@@ -1,6 +1,10 @@
 //! A set of classic activation functions.
 
+use alloc::boxed::Box;
+
 use num::{Float, one, zero};
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
 
 /// Wraps two functions or closures as an activation function that can be
 /// used by a network.
@@ -9,7 +13,7 @@ pub struct ActivationFunction<F, V, D>
           V: Fn(F) -> F,
           D: Fn(F) -> F
 {
-    _marker: ::std::marker::PhantomData<F>,
+    _marker: ::core::marker::PhantomData<F>,
     /// Mathematical definition of the activation function, to be evaluated
     /// at any point.
     pub value: V,
@@ -18,6 +22,10 @@ pub struct ActivationFunction<F, V, D>
     pub derivative: D
 }
 
+/// An activation function made of two plain function pointers, as returned by
+/// the constructors of this module.
+pub type FnActivation<F> = ActivationFunction<F, fn(F) -> F, fn(F) -> F>;
+
 impl<F, V, D> ActivationFunction<F, V, D>
     where F: Float,
           V: Fn(F) -> F,
@@ -26,18 +34,28 @@ impl<F, V, D> ActivationFunction<F, V, D>
     /// Create an `ActivationFunction` out of two functions or closures.
     pub fn new(value: V, derivative: D) -> ActivationFunction<F, V, D> {
         ActivationFunction {
-            _marker: ::std::marker::PhantomData,
-            value: value,
-            derivative: derivative
+            _marker: ::core::marker::PhantomData,
+            value,
+            derivative
         }
     }
 }
 
+impl<F, V, D> Clone for ActivationFunction<F, V, D>
+    where F: Float,
+          V: Fn(F) -> F + Clone,
+          D: Fn(F) -> F + Clone
+{
+    fn clone(&self) -> ActivationFunction<F, V, D> {
+        ActivationFunction::new(self.value.clone(), self.derivative.clone())
+    }
+}
+
 /// Identity function, do not change its input.
 ///
 /// Very bad for training but can be useful for debugging, or in some
 /// special cases.
-pub fn identity<F: Float>() -> ActivationFunction<F, fn(F) -> F, fn(F) -> F> {
+pub fn identity<F: Float>() -> FnActivation<F> {
     ActivationFunction::new(identity_val, identity_der)
 }
 
@@ -47,30 +65,477 @@ fn identity_der<F: Float>(_x: F) -> F { one() }
 /// Sigmoid function. A classic smooth learning function.
 ///
 /// Its values are `0.0` at `-inf`, `0.5` at `0` and `1.0` at `+inf`
-pub fn sigmoid<F: Float>() -> ActivationFunction<F, fn(F) -> F, fn(F) -> F> {
+///
+/// The derivative is computed as `s*(1-s)` where `s` is the sigmoid of the
+/// pre-activation value (which is what the layers feed to it).
+pub fn sigmoid<F: Float>() -> FnActivation<F> {
     ActivationFunction::new(sigmoid_val, sigmoid_der)
 }
 
 fn sigmoid_val<F: Float>(x: F) -> F { one::<F>() / ( one::<F>() + (-x).exp() ) }
-fn sigmoid_der<F: Float>(x: F) -> F { x.exp() / ( one::<F>() + x.exp() ).powi(2) }
+fn sigmoid_der<F: Float>(x: F) -> F { let s = sigmoid_val(x); s * ( one::<F>() - s ) }
+
+/// Hard sigmoid function, a piecewise-linear approximation of the sigmoid
+/// which is cheaper to compute: `clamp(0.2*x + 0.5, 0, 1)`.
+///
+/// Its derivative is `0.2` strictly between the breakpoints `-2.5` and `2.5`,
+/// and `0.0` elsewhere, including on them.
+pub fn hard_sigmoid<F: Float>() -> FnActivation<F> {
+    ActivationFunction::new(hard_sigmoid_val, hard_sigmoid_der)
+}
+
+fn hard_sigmoid_val<F: Float>(x: F) -> F {
+    (constant::<F>(0.2) * x + constant(0.5)).max(zero()).min(one())
+}
+fn hard_sigmoid_der<F: Float>(x: F) -> F {
+    if x.abs() < constant(2.5) { constant(0.2) } else { zero() }
+}
+
+/// Hyperbolic tangent function. A smooth learning function centered on `0`.
+///
+/// Its values are `-1.0` at `-inf`, `0.0` at `0` and `1.0` at `+inf`
+pub fn tanh<F: Float>() -> FnActivation<F> {
+    ActivationFunction::new(tanh_val, tanh_der)
+}
+
+fn tanh_val<F: Float>(x: F) -> F { x.tanh() }
+fn tanh_der<F: Float>(x: F) -> F { one::<F>() - x.tanh().powi(2) }
+
+/// Rectified linear unit. Outputs its input if positive and `0.0` otherwise.
+///
+/// Its derivative is taken to be `0.0` at exactly `0.0`, so that training is
+/// reproducible when a pre-activation sum falls on zero.
+pub fn relu<F: Float>() -> FnActivation<F> {
+    ActivationFunction::new(relu_val, relu_der)
+}
+
+fn relu_val<F: Float>(x: F) -> F { x.max(zero()) }
+fn relu_der<F: Float>(x: F) -> F { if x > zero() { one() } else { zero() } }
+
+/// Rectified linear unit capped at `6.0`: `min(max(x, 0), 6)`.
+///
+/// The bounded range of its outputs makes it well suited for networks meant
+/// to be quantized. Like for `relu()`, its derivative is `1.0` strictly
+/// between the two breakpoints and `0.0` elsewhere, including on them.
+pub fn relu6<F: Float>() -> FnActivation<F> {
+    ActivationFunction::new(relu6_val, relu6_der)
+}
+
+fn relu6_val<F: Float>(x: F) -> F { x.max(zero()).min(F::from(6).unwrap()) }
+fn relu6_der<F: Float>(x: F) -> F { if x > zero() && x < F::from(6).unwrap() { one() } else { zero() } }
+
+/// Leaky rectified linear unit. Outputs its input if positive and `alpha`
+/// times its input otherwise.
+///
+/// The small negative-side slope keeps units from dying during training.
+pub fn leaky_relu<F: Float>(alpha: F) -> ActivationFunction<F, impl Fn(F) -> F, impl Fn(F) -> F> {
+    ActivationFunction::new(
+        move |x: F| if x >= zero() { x } else { alpha * x },
+        move |x: F| if x >= zero() { one() } else { alpha }
+    )
+}
+
+/// Exponential linear unit. Outputs its input if positive and
+/// `alpha*(exp(x)-1)` otherwise.
+///
+/// With `alpha = 1.0` both the function and its derivative are continuous
+/// at `0.0`.
+pub fn elu<F: Float>(alpha: F) -> ActivationFunction<F, impl Fn(F) -> F, impl Fn(F) -> F> {
+    ActivationFunction::new(
+        move |x: F| if x > zero() { x } else { alpha * x.exp_m1() },
+        move |x: F| if x > zero() { one() } else { alpha * x.exp() }
+    )
+}
+
+/// Softplus function, a smooth approximation of the ReLU: `ln(1 + exp(x))`.
+///
+/// It is computed as `max(x,0) + ln(1 + exp(-|x|))` so that it does not
+/// overflow for large inputs. Its derivative is the sigmoid.
+pub fn softplus<F: Float>() -> FnActivation<F> {
+    ActivationFunction::new(softplus_val, sigmoid_val)
+}
+
+fn softplus_val<F: Float>(x: F) -> F { x.max(zero()) + (-x.abs()).exp().ln_1p() }
+
+/// Gaussian error linear unit, using the tanh approximation:
+///
+/// ```text
+/// 0.5*x*(1 + tanh(sqrt(2/pi)*(x + 0.044715*x^3)))
+/// ```
+pub fn gelu<F: Float>() -> FnActivation<F> {
+    ActivationFunction::new(gelu_val, gelu_der)
+}
+
+fn gelu_inner<F: Float>(x: F) -> F {
+    constant::<F>(0.7978845608028654) * (x + constant::<F>(0.044715) * x.powi(3))
+}
+fn gelu_val<F: Float>(x: F) -> F {
+    constant::<F>(0.5) * x * (one::<F>() + gelu_inner(x).tanh())
+}
+fn gelu_der<F: Float>(x: F) -> F {
+    let half = constant::<F>(0.5);
+    let t = gelu_inner(x).tanh();
+    let du = constant::<F>(0.7978845608028654)
+             * (one::<F>() + constant::<F>(3.0 * 0.044715) * x.powi(2));
+    half * (one::<F>() + t) + half * x * (one::<F>() - t.powi(2)) * du
+}
+
+/// Swish (or SiLU) function: `x * sigmoid(x)`.
+pub fn swish<F: Float>() -> FnActivation<F> {
+    ActivationFunction::new(swish_val, swish_der)
+}
+
+fn swish_val<F: Float>(x: F) -> F { x * sigmoid_val(x) }
+fn swish_der<F: Float>(x: F) -> F { let s = sigmoid_val(x); s + x * s * ( one::<F>() - s ) }
+
+/// Mish function: `x * tanh(softplus(x))`.
+///
+/// The softplus is computed the same way as in `softplus()`, so that it does
+/// not overflow for large inputs.
+pub fn mish<F: Float>() -> FnActivation<F> {
+    ActivationFunction::new(mish_val, mish_der)
+}
+
+fn mish_val<F: Float>(x: F) -> F { x * softplus_val(x).tanh() }
+fn mish_der<F: Float>(x: F) -> F {
+    let t = softplus_val(x).tanh();
+    t + x * (one::<F>() - t.powi(2)) * sigmoid_val(x)
+}
+
+/// Softsign function: `x / (1 + |x|)`.
+///
+/// Like `tanh()`, its output is in `(-1, 1)`, but it is cheaper to compute.
+pub fn softsign<F: Float>() -> FnActivation<F> {
+    ActivationFunction::new(softsign_val, softsign_der)
+}
+
+fn softsign_val<F: Float>(x: F) -> F { x / (one::<F>() + x.abs()) }
+fn softsign_der<F: Float>(x: F) -> F { (one::<F>() + x.abs()).powi(-2) }
 
 /// Step function. Cannot be used for learning, but can be used
 /// to normalize data.
 ///
 /// It outputs `1.0` if input was positive, and `-0.0` if input was negative.
-pub fn step<F: Float>() -> ActivationFunction<F, fn(F) -> F, fn(F) -> F> {
+pub fn step<F: Float>() -> FnActivation<F> {
     ActivationFunction::new(step_val, step_der)
 }
 
 fn step_val<F: Float>(x: F) -> F { if x.is_sign_positive() { one() } else { zero() } }
 fn step_der<F: Float>(_x: F) -> F { zero() }
 
+/// Step (or Heaviside) function with a threshold: outputs `1.0` if its input
+/// is greater than or equal to `threshold`, and `0.0` otherwise.
+///
+/// Like `step()`, its derivative is taken to be `0.0` everywhere, so it cannot
+/// be used for training by backpropagation. It is however the activation of
+/// the classic perceptron, trained with `PerceptronRule`.
+pub fn step_at<F: Float>(threshold: F) -> ActivationFunction<F, impl Fn(F) -> F, impl Fn(F) -> F> {
+    ActivationFunction::new(
+        move |x: F| if x >= threshold { one() } else { zero() },
+        step_der
+    )
+}
+
 /// Gaussian function. Reaches its maximum `1.0` at `0.0`, and smoothly converges
 /// towards `0.0` on both infinities.
-pub fn gaussian<F: Float>() -> ActivationFunction<F, fn(F) -> F, fn(F) -> F> {
+pub fn gaussian<F: Float>() -> FnActivation<F> {
     ActivationFunction::new(gauss_val, gauss_der)
 }
 
 fn gauss_val<F: Float>(x: F) -> F { (-x.powi(2)).exp() }
 // such a terrible way to make a two: v~~~~~~~~~~~~~~~~~~~v
-fn gauss_der<F: Float>(x: F) -> F { -(one::<F>()+one::<F>())*x*(-x.powi(2)).exp() }
\ No newline at end of file
+fn gauss_der<F: Float>(x: F) -> F { -(one::<F>()+one::<F>())*x*(-x.powi(2)).exp() }
+
+fn constant<F: Float>(x: f64) -> F { F::from(x).unwrap() }
+
+/// A boxed closure of an activation function, which can be shared between
+/// threads.
+pub type BoxedFn<F> = Box<dyn Fn(F) -> F + Send + Sync>;
+
+/// An activation function whose closures are boxed, as returned by
+/// `ActivationKind::build()`.
+pub type BoxedActivation<F> = ActivationFunction<F, BoxedFn<F>, BoxedFn<F>>;
+
+/// An identifier of the activation functions of this module, which can be
+/// stored (and serialized with the `serde` feature) to rebuild the activation
+/// function later.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ActivationKind {
+    /// The `identity()` function.
+    Identity,
+    /// The `sigmoid()` function.
+    Sigmoid,
+    /// The `hard_sigmoid()` function.
+    HardSigmoid,
+    /// The `tanh()` function.
+    Tanh,
+    /// The `relu()` function.
+    Relu,
+    /// The `relu6()` function.
+    Relu6,
+    /// The `leaky_relu()` function, with its slope.
+    LeakyRelu(f64),
+    /// The `elu()` function, with its `alpha`.
+    Elu(f64),
+    /// The `softplus()` function.
+    Softplus,
+    /// The `gelu()` function.
+    Gelu,
+    /// The `swish()` function.
+    Swish,
+    /// The `mish()` function.
+    Mish,
+    /// The `softsign()` function.
+    Softsign,
+    /// The `step()` function.
+    Step,
+    /// The `step_at()` function, with its threshold.
+    StepAt(f64),
+    /// The `gaussian()` function.
+    Gaussian
+}
+
+impl ActivationKind {
+    /// Builds the activation function identified by this kind.
+    pub fn build<F: Float + Send + Sync + 'static>(&self) -> BoxedActivation<F> {
+        fn boxed<F, V, D>(f: ActivationFunction<F, V, D>) -> BoxedActivation<F>
+            where F: Float, V: Fn(F) -> F + Send + Sync + 'static, D: Fn(F) -> F + Send + Sync + 'static
+        {
+            ActivationFunction::new(Box::new(f.value), Box::new(f.derivative))
+        }
+        match *self {
+            ActivationKind::Identity => boxed(identity()),
+            ActivationKind::Sigmoid => boxed(sigmoid()),
+            ActivationKind::HardSigmoid => boxed(hard_sigmoid()),
+            ActivationKind::Tanh => boxed(tanh()),
+            ActivationKind::Relu => boxed(relu()),
+            ActivationKind::Relu6 => boxed(relu6()),
+            ActivationKind::LeakyRelu(alpha) => boxed(leaky_relu(constant(alpha))),
+            ActivationKind::Elu(alpha) => boxed(elu(constant(alpha))),
+            ActivationKind::Softplus => boxed(softplus()),
+            ActivationKind::Gelu => boxed(gelu()),
+            ActivationKind::Swish => boxed(swish()),
+            ActivationKind::Mish => boxed(mish()),
+            ActivationKind::Softsign => boxed(softsign()),
+            ActivationKind::Step => boxed(step()),
+            ActivationKind::StepAt(threshold) => boxed(step_at(constant(threshold))),
+            ActivationKind::Gaussian => boxed(gaussian())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sigmoid, hard_sigmoid, tanh, relu, relu6, step, step_at, leaky_relu, elu, softplus, gelu, swish, mish, softsign, gaussian, ActivationKind};
+
+    #[test]
+    fn sigmoid_values() {
+        let f = sigmoid::<f32>();
+        assert_eq!((f.value)(0.0), 0.5);
+        assert_eq!((f.derivative)(0.0), 0.25);
+        // no overflow far from the origin
+        assert!((f.derivative)(1000.0).abs() < 1e-6);
+        assert!((f.derivative)(-1000.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn hard_sigmoid_values() {
+        let f = hard_sigmoid::<f64>();
+        assert_eq!((f.value)(-4.0), 0.0);
+        assert_eq!((f.derivative)(-4.0), 0.0);
+        assert_eq!((f.value)(0.0), 0.5);
+        assert!(((f.value)(1.0) - 0.7).abs() < 1e-12);
+        assert_eq!((f.derivative)(1.0), 0.2);
+        assert_eq!((f.value)(4.0), 1.0);
+        assert_eq!((f.derivative)(4.0), 0.0);
+        // the breakpoints
+        assert!((f.value)(-2.5).abs() < 1e-12);
+        assert_eq!((f.derivative)(-2.5), 0.0);
+        assert!(((f.value)(2.5) - 1.0).abs() < 1e-12);
+        assert_eq!((f.derivative)(2.5), 0.0);
+    }
+
+    #[test]
+    fn tanh_values() {
+        let f = tanh::<f32>();
+        assert_eq!((f.value)(0.0), 0.0);
+        assert_eq!((f.derivative)(0.0), 1.0);
+    }
+
+    #[test]
+    fn relu_values() {
+        let f = relu::<f32>();
+        assert_eq!((f.value)(-2.0), 0.0);
+        assert_eq!((f.derivative)(-2.0), 0.0);
+        assert_eq!((f.value)(0.0), 0.0);
+        assert_eq!((f.derivative)(0.0), 0.0);
+        assert_eq!((f.value)(3.0), 3.0);
+        assert_eq!((f.derivative)(3.0), 1.0);
+    }
+
+    #[test]
+    fn relu6_values() {
+        let f = relu6::<f32>();
+        assert_eq!((f.value)(-1.0), 0.0);
+        assert_eq!((f.derivative)(-1.0), 0.0);
+        assert_eq!((f.value)(3.0), 3.0);
+        assert_eq!((f.derivative)(3.0), 1.0);
+        assert_eq!((f.value)(10.0), 6.0);
+        assert_eq!((f.derivative)(10.0), 0.0);
+        // the boundaries are outside of the linear region
+        assert_eq!((f.value)(0.0), 0.0);
+        assert_eq!((f.derivative)(0.0), 0.0);
+        assert_eq!((f.value)(6.0), 6.0);
+        assert_eq!((f.derivative)(6.0), 0.0);
+    }
+
+    #[test]
+    fn leaky_relu_values() {
+        let f = leaky_relu(0.01f32);
+        assert_eq!((f.value)(-2.0), -0.02);
+        assert_eq!((f.derivative)(-2.0), 0.01);
+        assert_eq!((f.value)(3.0), 3.0);
+        assert_eq!((f.derivative)(3.0), 1.0);
+    }
+
+    #[test]
+    fn elu_values() {
+        let f = elu(1.0f64);
+        assert_eq!((f.value)(2.0), 2.0);
+        assert_eq!((f.derivative)(2.0), 1.0);
+        assert!(((f.value)(-1.0) - (-1.0f64).exp_m1()).abs() < 1e-12);
+        assert!(((f.derivative)(-1.0) - (-1.0f64).exp()).abs() < 1e-12);
+        // continuity at zero
+        assert!(((f.value)(1e-9) - (f.value)(-1e-9)).abs() < 1e-8);
+        assert!(((f.derivative)(1e-9) - (f.derivative)(-1e-9)).abs() < 1e-8);
+    }
+
+    #[test]
+    fn softplus_values() {
+        let f = softplus::<f64>();
+        for &x in &[-2.0f64, -0.5, 0.0, 0.5, 2.0] {
+            assert!(((f.value)(x) - (1.0 + x.exp()).ln()).abs() < 1e-12);
+            assert!(((f.derivative)(x) - 1.0 / (1.0 + (-x).exp())).abs() < 1e-12);
+        }
+        // no overflow for large inputs
+        let big = (softplus::<f32>().value)(100.0);
+        assert!(big.is_finite());
+        assert!((big - 100.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn gelu_values() {
+        let f = gelu::<f64>();
+        assert_eq!((f.value)(0.0), 0.0);
+        assert!(((f.derivative)(0.0) - 0.5).abs() < 1e-12);
+        // compare the derivative against finite differences
+        for &x in &[-1.5f64, -0.3, 0.7, 2.0] {
+            let num = ((f.value)(x + 1e-6) - (f.value)(x - 1e-6)) / 2e-6;
+            assert!(((f.derivative)(x) - num).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn swish_values() {
+        let f = swish::<f64>();
+        assert_eq!((f.value)(0.0), 0.0);
+        assert_eq!((f.derivative)(0.0), 0.5);
+        assert!(((f.value)(2.0) - 2.0 / (1.0 + (-2.0f64).exp())).abs() < 1e-12);
+        assert!(((f.value)(-1.0) + 1.0 / (1.0 + 1.0f64.exp())).abs() < 1e-12);
+        for &x in &[-2.0f64, 1.0] {
+            let num = ((f.value)(x + 1e-6) - (f.value)(x - 1e-6)) / 2e-6;
+            assert!(((f.derivative)(x) - num).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn mish_values() {
+        let f = mish::<f64>();
+        assert_eq!((f.value)(0.0), 0.0);
+        assert!(((f.derivative)(0.0) - 0.6).abs() < 1e-12);
+        assert!(((f.value)(1.0) - 0.8650983882673103).abs() < 1e-12);
+        assert!(((f.value)(-1.0) + 0.3034014613741089).abs() < 1e-12);
+        for &x in &[-2.0f64, -0.5, 0.0, 1.0, 3.0] {
+            let num = ((f.value)(x + 1e-6) - (f.value)(x - 1e-6)) / 2e-6;
+            assert!(((f.derivative)(x) - num).abs() < 1e-6);
+        }
+        // no overflow for large inputs
+        let f = mish::<f32>();
+        assert!(((f.value)(100.0) - 100.0).abs() < 1e-4);
+        assert!(((f.derivative)(100.0) - 1.0).abs() < 1e-4);
+        assert!((f.value)(-100.0).is_finite());
+        assert!((f.derivative)(-100.0).is_finite());
+    }
+
+    #[test]
+    fn softsign_values() {
+        let f = softsign::<f64>();
+        assert_eq!((f.value)(0.0), 0.0);
+        assert_eq!((f.derivative)(0.0), 1.0);
+        assert_eq!((f.value)(1.0), 0.5);
+        assert_eq!((f.derivative)(1.0), 0.25);
+        assert_eq!((f.value)(-1.0), -0.5);
+        assert_eq!((f.derivative)(-1.0), 0.25);
+        for &x in &[-1e6f64, -10.0, 10.0, 1e6] {
+            let y = (f.value)(x);
+            assert!(y > -1.0 && y < 1.0);
+        }
+    }
+
+    #[test]
+    fn gaussian_values() {
+        let f = gaussian::<f64>();
+        assert_eq!((f.value)(0.0), 1.0);
+        assert_eq!((f.derivative)(0.0), 0.0);
+        for &x in &[0.5f64, 1.0, 2.0] {
+            assert_eq!((f.value)(x), (f.value)(-x));
+            assert_eq!((f.derivative)(x), -(f.derivative)(-x));
+            assert!((f.value)(x) < 1.0 && (f.derivative)(x) < 0.0);
+            assert!(((f.value)(x) - (-x * x).exp()).abs() < 1e-12);
+            assert!(((f.derivative)(x) + 2.0 * x * (-x * x).exp()).abs() < 1e-12);
+        }
+        assert!((f.value)(2.0) < (f.value)(1.0) && (f.value)(1.0) < (f.value)(0.5));
+    }
+
+    #[test]
+    fn step_values() {
+        let f = step::<f32>();
+        assert_eq!((f.value)(-1.0), 0.0);
+        assert_eq!((f.value)(0.0), 1.0);
+        assert_eq!((f.derivative)(1.0), 0.0);
+        let f = step_at(0.5f32);
+        assert_eq!((f.value)(0.0), 0.0);
+        assert_eq!((f.value)(0.5), 1.0);
+        assert_eq!((f.value)(2.0), 1.0);
+        assert_eq!((f.derivative)(0.5), 0.0);
+        let built = ActivationKind::StepAt(0.5).build::<f32>();
+        assert_eq!((built.value)(0.25), 0.0);
+        assert_eq!((built.value)(0.75), 1.0);
+    }
+
+    #[test]
+    fn kind_build() {
+        let built = ActivationKind::Sigmoid.build::<f64>();
+        let f = sigmoid::<f64>();
+        for &x in &[-2.0, 0.0, 0.5, 3.0] {
+            assert_eq!((built.value)(x), (f.value)(x));
+            assert_eq!((built.derivative)(x), (f.derivative)(x));
+        }
+        let built = ActivationKind::LeakyRelu(0.1).build::<f32>();
+        assert_eq!((built.value)(-2.0), -0.2);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn kind_round_trip() {
+        use serde_json;
+
+        let json = serde_json::to_string(&ActivationKind::Sigmoid).unwrap();
+        let kind: ActivationKind = serde_json::from_str(&json).unwrap();
+        assert_eq!(kind, ActivationKind::Sigmoid);
+        assert_eq!((kind.build::<f64>().value)(1.0), (sigmoid::<f64>().value)(1.0));
+        let json = serde_json::to_string(&ActivationKind::Elu(0.5)).unwrap();
+        assert_eq!(serde_json::from_str::<ActivationKind>(&json).unwrap(), ActivationKind::Elu(0.5));
+    }
+}
@@ -1,3 +1,5 @@
+use alloc::vec::Vec;
+
 use num::{Float, one, zero};
 
 use rand::{Rand, random, thread_rng};
@@ -42,7 +44,7 @@ impl<F: Float> BoltzmannMachine<F> {
         assert!(biases.len() == n, "The biases count must be equal to the nodes count.");
         BoltzmannMachine {
             values: vec![one(); n],
-            biases: biases,
+            biases,
             coeffs: weigths
         }
     }
@@ -0,0 +1,287 @@
+//! Convolutional layers
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use num::{Float, zero};
+
+use {Compute, BackpropTrain, Trainable, Parametrized, ModeAware, any_non_finite};
+use activations::ActivationFunction;
+use diagnostics::Weights;
+use training::{GradientRule, RuleState};
+
+/// A 1-D convolutional layer
+///
+/// The input is made of `channels` signals of `length` samples each, stored
+/// one channel after the other (sample `t` of channel `c` being at index
+/// `c*length + t`). Each of the `filters` output channels is computed by
+/// sliding a kernel of `kernel` samples over all input channels with step
+/// `stride`, adding a bias, and applying the activation function:
+///
+/// ```text
+/// Y[o][i] = f( B[o] + sum_c sum_k W[o][c][k] * X[c][i*stride + k] )
+/// ```
+///
+/// Only the positions where the kernel fully fits in the input are computed,
+/// so each output channel has `(length - kernel) / stride + 1` samples, and
+/// the output is stored in the same channel-major layout as the input.
+///
+/// It can be trained by backpropagation using any `GradientRule`.
+pub struct Conv1D<F: Float, V: Fn(F) -> F, D: Fn(F) -> F> {
+    channels: usize,
+    length: usize,
+    kernel: usize,
+    stride: usize,
+    coeffs: Vec<F>,
+    biases: Vec<F>,
+    activation: ActivationFunction<F, V, D>,
+    coeffs_state: RuleState<F>,
+    biases_state: RuleState<F>
+}
+
+impl<F, V, D> Conv1D<F, V, D>
+    where F: Float,
+          V: Fn(F) -> F,
+          D: Fn(F) -> F
+{
+    /// Creates a new convolutional layer with all its kernels and biases set
+    /// to 0.
+    ///
+    /// Panics if `stride` is 0.
+    pub fn new(channels: usize,
+               length: usize,
+               filters: usize,
+               kernel: usize,
+               stride: usize,
+               activation: ActivationFunction<F, V, D>)
+        -> Conv1D<F, V, D>
+    {
+        Conv1D::new_from(channels, length, filters, kernel, stride, activation, zero)
+    }
+
+    /// Creates a new convolutional layer with all its kernels and biases
+    /// generated by provided closure (for example a random number generator).
+    ///
+    /// Panics if `stride` is 0.
+    pub fn new_from<G>(channels: usize,
+                       length: usize,
+                       filters: usize,
+                       kernel: usize,
+                       stride: usize,
+                       activation: ActivationFunction<F, V, D>,
+                       mut generator: G)
+        -> Conv1D<F, V, D>
+        where G: FnMut() -> F
+    {
+        assert!(stride > 0, "The stride of a convolution cannot be 0.");
+        Conv1D {
+            channels,
+            length,
+            kernel,
+            stride,
+            coeffs: (0..filters*channels*kernel).map(|_| generator()).collect(),
+            biases: (0..filters).map(|_| generator()).collect(),
+            activation,
+            coeffs_state: RuleState::new(),
+            biases_state: RuleState::new()
+        }
+    }
+
+    /// Get access to the kernels of this layer.
+    ///
+    /// The weight `k` applied to input channel `c` by output channel `o` is
+    /// at index `(o*channels + c)*kernel + k`.
+    pub fn kernels(&self) -> &[F] {
+        &self.coeffs
+    }
+
+    /// Get mutable access to the kernels of this layer.
+    ///
+    /// See `kernels()` for their layout.
+    pub fn kernels_mut(&mut self) -> &mut [F] {
+        &mut self.coeffs
+    }
+
+    /// Get access to the biases of this layer, one per output channel.
+    pub fn biases(&self) -> &[F] {
+        &self.biases
+    }
+
+    /// Get mutable access to the biases of this layer.
+    pub fn biases_mut(&mut self) -> &mut [F] {
+        &mut self.biases
+    }
+
+    /// The number of samples in each output channel.
+    fn out_length(&self) -> usize {
+        if self.length < self.kernel { 0 } else { (self.length - self.kernel) / self.stride + 1 }
+    }
+
+    /// The values of the outputs before the activation function.
+    fn pre_activations(&self, input: &[F]) -> Vec<F> {
+        let out_length = self.out_length();
+        let mut out = Vec::with_capacity(self.biases.len() * out_length);
+        for o in 0..self.biases.len() {
+            for i in 0..out_length {
+                let mut acc = self.biases[o];
+                for c in 0..self.channels {
+                    let w = &self.coeffs[(o*self.channels + c)*self.kernel..];
+                    for (k, &wk) in w[..self.kernel].iter().enumerate() {
+                        let x = input.get(c*self.length + i*self.stride + k).cloned().unwrap_or(zero());
+                        acc = acc + wk * x;
+                    }
+                }
+                out.push(acc);
+            }
+        }
+        out
+    }
+}
+
+impl<F, V, D> Compute<F> for Conv1D<F, V, D>
+    where F: Float,
+          V: Fn(F) -> F,
+          D: Fn(F) -> F
+{
+    fn compute(&self, input: &[F]) -> Vec<F> {
+        self.pre_activations(input).into_iter().map(|x| (self.activation.value)(x)).collect()
+    }
+
+    fn input_size(&self) -> usize {
+        self.channels * self.length
+    }
+
+    fn output_size(&self) -> usize {
+        self.biases.len() * self.out_length()
+    }
+}
+
+impl<F, V, D, R> BackpropTrain<F, R> for Conv1D<F, V, D>
+    where F: Float,
+          V: Fn(F) -> F,
+          D: Fn(F) -> F,
+          R: GradientRule<Float = F>
+{
+    fn backprop_train(&mut self, rule: &R, input: &[F], error: &[F]) -> Vec<F> {
+        let out_length = self.out_length();
+        let deltas = self.pre_activations(input)
+                         .iter()
+                         .enumerate()
+                         .map(|(j, x)| {
+                             (self.activation.derivative)(*x)
+                                 * error.get(j).cloned().unwrap_or(zero())
+                         })
+                         .collect::<Vec<_>>();
+
+        let mut coeffs_grad = vec![zero(); self.coeffs.len()];
+        let mut biases_grad = vec![zero(); self.biases.len()];
+        let mut returned = vec![zero(); self.channels * self.length];
+        for o in 0..self.biases.len() {
+            for i in 0..out_length {
+                let delta = deltas[o*out_length + i];
+                biases_grad[o] = biases_grad[o] + delta;
+                for c in 0..self.channels {
+                    for k in 0..self.kernel {
+                        let w = (o*self.channels + c)*self.kernel + k;
+                        let x = c*self.length + i*self.stride + k;
+                        coeffs_grad[w] = coeffs_grad[w]
+                                         + delta * input.get(x).cloned().unwrap_or(zero());
+                        returned[x] = returned[x] + delta * self.coeffs[w];
+                    }
+                }
+            }
+        }
+
+        rule.apply(&mut self.coeffs_state, &mut self.coeffs, &coeffs_grad, true);
+        rule.apply(&mut self.biases_state, &mut self.biases, &biases_grad, false);
+        returned
+    }
+}
+
+impl<F, V, D> Weights<F> for Conv1D<F, V, D>
+    where F: Float,
+          V: Fn(F) -> F,
+          D: Fn(F) -> F
+{
+    fn weights(&self) -> &[F] {
+        &self.coeffs
+    }
+
+    fn weights_mut(&mut self) -> &mut [F] {
+        &mut self.coeffs
+    }
+}
+
+impl<F, V, D> ModeAware for Conv1D<F, V, D>
+    where F: Float,
+          V: Fn(F) -> F,
+          D: Fn(F) -> F
+{}
+
+impl<F, V, D> Trainable for Conv1D<F, V, D>
+    where F: Float,
+          V: Fn(F) -> F,
+          D: Fn(F) -> F
+{
+    fn num_parameters(&self) -> usize {
+        self.coeffs.len() + self.biases.len()
+    }
+
+    fn has_nan(&self) -> bool {
+        any_non_finite(&self.coeffs) || any_non_finite(&self.biases)
+    }
+}
+
+impl<F, V, D> Parametrized<F> for Conv1D<F, V, D>
+    where F: Float,
+          V: Fn(F) -> F,
+          D: Fn(F) -> F
+{
+    fn parameters_iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a F> + 'a> {
+        Box::new(self.coeffs.iter().chain(self.biases.iter()))
+    }
+
+    fn parameters_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = &'a mut F> + 'a> {
+        Box::new(self.coeffs.iter_mut().chain(self.biases.iter_mut()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use Compute;
+    use activations::{identity, sigmoid};
+    use diagnostics::gradient_check;
+    use loss::MeanSquaredError;
+    use testing::random;
+
+    use super::Conv1D;
+
+    #[test]
+    fn basics() {
+        let layer = Conv1D::new(2, 10, 3, 4, 2, identity::<f32>());
+        assert_eq!(layer.input_size(), 20);
+        assert_eq!(layer.output_size(), 3 * 4);
+        assert_eq!(layer.kernels().len(), 3 * 2 * 4);
+    }
+
+    #[test]
+    fn single_channel() {
+        let mut layer = Conv1D::new(1, 5, 1, 3, 1, identity());
+        layer.kernels_mut().copy_from_slice(&[1.0f32, 2.0, -1.0]);
+        layer.biases_mut()[0] = 0.5;
+        assert_eq!(layer.compute(&[1.0, 2.0, 3.0, 4.0, 5.0]), vec![2.5, 4.5, 6.5]);
+
+        let mut strided = Conv1D::new(1, 5, 1, 3, 2, identity());
+        strided.kernels_mut().copy_from_slice(&[1.0f32, 2.0, -1.0]);
+        assert_eq!(strided.output_size(), 2);
+        assert_eq!(strided.compute(&[1.0, 2.0, 3.0, 4.0, 5.0]), vec![2.0, 6.0]);
+    }
+
+    #[test]
+    fn backprop_train() {
+        let mut layer = Conv1D::new_from(2, 5, 2, 2, 2, sigmoid(), random());
+        let input = [0.3f64, -0.8, 0.5, 0.1, -0.4, 0.9, 0.2, -0.6, 0.7, -0.1];
+        let error = gradient_check(&mut layer, &MeanSquaredError, &input, &[1.0, 0.0, 0.5, 0.0], 1e-6);
+        assert!(error < 1e-4);
+    }
+}
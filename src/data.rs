@@ -0,0 +1,277 @@
+//! Utilities to prepare datasets for training.
+//!
+//! A dataset is represented as a list of `(input, target)` pairs.
+
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::fs::File;
+#[cfg(feature = "std")]
+use std::io::{self, BufRead, BufReader};
+#[cfg(feature = "std")]
+use std::path::Path;
+
+use num::{Float, zero, one};
+#[cfg(feature = "std")]
+use rand::Rng;
+
+use LabelError;
+#[cfg(feature = "std")]
+use ParameterError;
+
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+/// Shuffles `samples` in place using the Fisher-Yates algorithm.
+///
+/// The random number generator is provided by the caller, so that seeding it
+/// gives a reproducible order. Requires the `std` feature.
+#[cfg(feature = "std")]
+pub fn shuffle_dataset<F, R: Rng>(samples: &mut [(Vec<F>, Vec<F>)], rng: &mut R) {
+    for i in (1..samples.len()).rev() {
+        let j = rng.gen_range(0, i + 1);
+        samples.swap(i, j);
+    }
+}
+
+/// Shuffles a copy of `samples` using `rng` and splits it in a training set
+/// and a test set, the latter holding a fraction `test_fraction` of the
+/// samples (rounded to the nearest integer).
+///
+/// Returns `(train, test)`, or an error if `test_fraction` is not in `[0, 1]`.
+/// Requires the `std` feature.
+#[cfg(feature = "std")]
+#[allow(clippy::type_complexity)]
+pub fn train_test_split<F, R>(samples: &[(Vec<F>, Vec<F>)], test_fraction: f64, rng: &mut R)
+    -> Result<(Vec<(Vec<F>, Vec<F>)>, Vec<(Vec<F>, Vec<F>)>), ParameterError>
+    where F: Clone, R: Rng
+{
+    if !(0.0..=1.0).contains(&test_fraction) {
+        return Err(ParameterError { name: "test_fraction", value: test_fraction });
+    }
+    let mut train = samples.to_vec();
+    shuffle_dataset(&mut train, rng);
+    let test_len = (test_fraction * samples.len() as f64).round() as usize;
+    let test = train.split_off(samples.len() - test_len);
+    Ok((train, test))
+}
+
+/// Loads a dataset from the CSV file at `path`.
+///
+/// See `read_csv()` for the expected format. Requires the `std` feature.
+#[cfg(feature = "std")]
+pub fn load_csv<F, P>(path: P, target_columns: usize, header: bool) -> io::Result<Vec<(Vec<F>, Vec<F>)>>
+    where F: FromStr, P: AsRef<Path>
+{
+    read_csv(BufReader::new(File::open(path)?), target_columns, header)
+}
+
+/// Reads a dataset from CSV data, one sample per line.
+///
+/// The last `target_columns` columns of each line are the target of the
+/// sample, and the other ones its input. If `header` is true, the first line
+/// is skipped. Empty lines are ignored.
+///
+/// Fails with an `InvalidData` error if a value cannot be parsed or if a line
+/// has no more than `target_columns` columns. Requires the `std` feature.
+#[cfg(feature = "std")]
+pub fn read_csv<F, R>(reader: R, target_columns: usize, header: bool) -> io::Result<Vec<(Vec<F>, Vec<F>)>>
+    where F: FromStr, R: BufRead
+{
+    let mut samples = Vec::new();
+    for (n, line) in reader.lines().enumerate().skip(if header { 1 } else { 0 }) {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut values = line.split(',').map(|v| {
+            v.trim().parse().map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidData,
+                               format!("Invalid value {:?} on line {}.", v, n + 1))
+            })
+        }).collect::<io::Result<Vec<F>>>()?;
+        if values.len() <= target_columns {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      format!("Not enough columns on line {}.", n + 1)));
+        }
+        let target = values.split_off(values.len() - target_columns);
+        samples.push((values, target));
+    }
+    Ok(samples)
+}
+
+/// The one-hot encoding of `label` among `num_classes` classes: a vector of
+/// `num_classes` values, all 0 except the one at index `label` which is 1.
+///
+/// Fails if `label` is not below `num_classes`.
+pub fn one_hot<F: Float>(label: usize, num_classes: usize) -> Result<Vec<F>, LabelError> {
+    if label >= num_classes {
+        return Err(LabelError { label, num_classes });
+    }
+    let mut encoded = vec![zero(); num_classes];
+    encoded[label] = one();
+    Ok(encoded)
+}
+
+/// The one-hot encodings of all `labels`, as given by `one_hot()`.
+///
+/// Fails on the first label which is not below `num_classes`.
+pub fn one_hot_batch<F: Float>(labels: &[usize], num_classes: usize) -> Result<Vec<Vec<F>>, LabelError> {
+    labels.iter().map(|&label| one_hot(label, num_classes)).collect()
+}
+
+/// A per-feature standardization, centering each feature on zero and scaling
+/// it to unit variance.
+///
+/// The mean and standard deviation of the features are learned on a training
+/// set by `fit()`, and kept so that the same transformation can be applied to
+/// any later input.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Standardizer<F> {
+    mean: Vec<F>,
+    std_dev: Vec<F>
+}
+
+impl<F: Float> Standardizer<F> {
+    /// Computes the mean and standard deviation of each feature of `inputs`.
+    ///
+    /// The number of features is the size of the first input. A feature
+    /// with a zero standard deviation is only centered, not scaled.
+    pub fn fit(inputs: &[Vec<F>]) -> Standardizer<F> {
+        let size = inputs.first().map(|x| x.len()).unwrap_or(0);
+        let n = F::from(inputs.len().max(1)).unwrap();
+        let feature = |x: &Vec<F>, i: usize| x.get(i).cloned().unwrap_or(zero());
+        let mean = (0..size).map(|i| {
+            inputs.iter().fold(zero::<F>(), |acc, x| acc + feature(x, i)) / n
+        }).collect::<Vec<_>>();
+        let std_dev = (0..size).map(|i| {
+            let var = inputs.iter().fold(zero::<F>(), |acc, x| acc + (feature(x, i) - mean[i]).powi(2)) / n;
+            if var > zero() { var.sqrt() } else { one() }
+        }).collect();
+        Standardizer { mean, std_dev }
+    }
+
+    /// The mean of each feature.
+    pub fn mean(&self) -> &[F] {
+        &self.mean
+    }
+
+    /// The standard deviation each feature is divided by.
+    pub fn std_dev(&self) -> &[F] {
+        &self.std_dev
+    }
+
+    /// Standardizes `input`.
+    pub fn transform(&self, input: &[F]) -> Vec<F> {
+        (0..self.mean.len()).map(|i| {
+            (input.get(i).cloned().unwrap_or(zero()) - self.mean[i]) / self.std_dev[i]
+        }).collect()
+    }
+
+    /// Reverts the standardization of `input`.
+    pub fn inverse_transform(&self, input: &[F]) -> Vec<F> {
+        (0..self.mean.len()).map(|i| {
+            input.get(i).cloned().unwrap_or(zero()) * self.std_dev[i] + self.mean[i]
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "std")]
+    use rand::{SeedableRng, XorShiftRng};
+
+    #[cfg(feature = "std")]
+    use std::io::{Cursor, ErrorKind};
+
+    use LabelError;
+    #[cfg(feature = "std")]
+    use ParameterError;
+
+    use super::{one_hot, one_hot_batch, Standardizer};
+    #[cfg(feature = "std")]
+    use super::{shuffle_dataset, train_test_split, read_csv};
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn shuffle() {
+        let mut samples: Vec<(Vec<f64>, Vec<f64>)> = (0..6)
+            .map(|i| (vec![i as f64], vec![-(i as f64)]))
+            .collect();
+        let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+        shuffle_dataset(&mut samples, &mut rng);
+        let order: Vec<f64> = samples.iter().map(|s| s.0[0]).collect();
+        assert_eq!(order, vec![0.0, 5.0, 4.0, 2.0, 3.0, 1.0]);
+        for (input, target) in &samples {
+            assert_eq!(input[0], -target[0]);
+        }
+        let mut sorted = order.clone();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(sorted, vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn csv() {
+        let data = "x1,x2,x3,label\n0.5,1,2,1\n\n-1,0.25,3,0\n";
+        let samples: Vec<(Vec<f32>, Vec<f32>)> = read_csv(Cursor::new(data), 1, true).unwrap();
+        assert_eq!(samples, vec![(vec![0.5, 1.0, 2.0], vec![1.0]), (vec![-1.0, 0.25, 3.0], vec![0.0])]);
+
+        let samples: Vec<(Vec<f32>, Vec<f32>)> = read_csv(Cursor::new("1,2,3\n4,5,6"), 2, false).unwrap();
+        assert_eq!(samples, vec![(vec![1.0], vec![2.0, 3.0]), (vec![4.0], vec![5.0, 6.0])]);
+
+        let error = read_csv::<f32, _>(Cursor::new("a,b\n1,2"), 1, false).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        let error = read_csv::<f32, _>(Cursor::new("1,2\n3"), 1, false).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn one_hot_encoding() {
+        assert_eq!(one_hot::<f32>(2, 4), Ok(vec![0.0, 0.0, 1.0, 0.0]));
+        assert_eq!(one_hot::<f32>(4, 4), Err(LabelError { label: 4, num_classes: 4 }));
+        assert_eq!(one_hot_batch::<f32>(&[0, 1], 2), Ok(vec![vec![1.0, 0.0], vec![0.0, 1.0]]));
+        assert_eq!(one_hot_batch::<f32>(&[0, 3, 5], 3), Err(LabelError { label: 3, num_classes: 3 }));
+    }
+
+    #[test]
+    fn standardizer() {
+        let inputs = vec![vec![1.0f64, 100.0, 3.0], vec![2.0, 300.0, 3.0],
+                          vec![6.0, 200.0, 3.0], vec![-1.0, 400.0, 3.0]];
+        let standardizer = Standardizer::fit(&inputs);
+        let transformed = inputs.iter().map(|x| standardizer.transform(x)).collect::<Vec<_>>();
+        for i in 0..3 {
+            let mean = transformed.iter().map(|y| y[i]).sum::<f64>() / 4.0;
+            let var = transformed.iter().map(|y| (y[i] - mean).powi(2)).sum::<f64>() / 4.0;
+            assert!(mean.abs() < 1e-12);
+            // the constant feature is only centered
+            let expected = if i == 2 { 0.0 } else { 1.0 };
+            assert!((var - expected).abs() < 1e-12);
+        }
+        for (x, y) in inputs.iter().zip(transformed.iter()) {
+            let back = standardizer.inverse_transform(y);
+            for i in 0..3 {
+                assert!((back[i] - x[i]).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn split() {
+        let samples: Vec<(Vec<f64>, Vec<f64>)> = (0..10).map(|i| (vec![i as f64], vec![0.0])).collect();
+        let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+        let (train, test) = train_test_split(&samples, 0.3, &mut rng).unwrap();
+        assert_eq!(train.len(), 7);
+        assert_eq!(test.len(), 3);
+        let mut all = train.iter().chain(test.iter()).map(|s| s.0[0]).collect::<Vec<_>>();
+        all.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(all, (0..10).map(|i| i as f64).collect::<Vec<_>>());
+
+        assert_eq!(train_test_split(&samples, 1.5, &mut rng).unwrap_err(),
+                   ParameterError { name: "test_fraction", value: 1.5 });
+        assert!(train_test_split(&samples, -0.1, &mut rng).is_err());
+    }
+}
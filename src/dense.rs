@@ -0,0 +1,313 @@
+//! Feedforward layers backed by ndarray
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use ndarray::{Array1, Array2};
+use num::{Float, zero};
+
+use {Compute, BackpropTrain, SupervisedTrain, BatchSupervisedTrain, Trainable, Parametrized, ModeAware, ShapeError, any_non_finite, output_error};
+use activations::ActivationFunction;
+use diagnostics::Weights;
+use training::{GradientRule, RuleState};
+
+/// A feedforward layer storing its parameters as ndarray arrays
+///
+/// This layer computes the same thing as a `FeedforwardLayer`:
+///
+/// ```text
+/// Y = f( W*X + B )
+/// ```
+///
+/// but relies on the matrix products of ndarray, which are faster for large
+/// layers, and computes whole batches with a single matrix product. It is
+/// only available with the `ndarray` feature.
+pub struct DenseLayer<F: Float, V: Fn(F) -> F, D: Fn(F) -> F> {
+    coeffs: Array2<F>,
+    biases: Array1<F>,
+    activation: ActivationFunction<F, V, D>,
+    coeffs_state: RuleState<F>,
+    biases_state: RuleState<F>
+}
+
+impl<F, V, D> DenseLayer<F, V, D>
+    where F: Float + 'static,
+          V: Fn(F) -> F,
+          D: Fn(F) -> F
+{
+    /// Creates a new layer with all its weights and biases set to 0.
+    pub fn new(inputs: usize,
+               outputs: usize,
+               activation: ActivationFunction<F, V, D>)
+        -> DenseLayer<F, V, D>
+    {
+        DenseLayer {
+            coeffs: Array2::zeros((outputs, inputs)),
+            biases: Array1::zeros(outputs),
+            activation,
+            coeffs_state: RuleState::new(),
+            biases_state: RuleState::new()
+        }
+    }
+
+    /// Creates a layer from a known weight matrix, of shape
+    /// `(outputs, inputs)`, and bias vector.
+    ///
+    /// Fails if the number of rows of `coeffs` does not match the number of
+    /// biases.
+    pub fn from_arrays(coeffs: Array2<F>,
+                       biases: Array1<F>,
+                       activation: ActivationFunction<F, V, D>)
+        -> Result<DenseLayer<F, V, D>, ShapeError>
+    {
+        if coeffs.nrows() != biases.len() {
+            return Err(ShapeError { expected: biases.len(), actual: coeffs.nrows() });
+        }
+        Ok(DenseLayer {
+            coeffs: coeffs.as_standard_layout().into_owned(),
+            biases,
+            activation,
+            coeffs_state: RuleState::new(),
+            biases_state: RuleState::new()
+        })
+    }
+
+    /// Get access to the weight matrix, of shape `(outputs, inputs)`.
+    pub fn coeffs(&self) -> &Array2<F> {
+        &self.coeffs
+    }
+
+    /// Get access to the biases, one per output.
+    pub fn biases(&self) -> &Array1<F> {
+        &self.biases
+    }
+
+    /// Get mutable access to the biases.
+    pub fn biases_mut(&mut self) -> &mut Array1<F> {
+        &mut self.biases
+    }
+
+    /// The input as an array of exactly `inputs` values.
+    fn input_array(&self, input: &[F]) -> Array1<F> {
+        (0..self.coeffs.ncols()).map(|i| input.get(i).cloned().unwrap_or(zero())).collect()
+    }
+
+    /// Same as `FeedforwardLayer`, the gradients with respect to the weights,
+    /// the biases and the input.
+    fn gradients(&self, input: &[F], error: &[F]) -> (Array2<F>, Array1<F>, Array1<F>) {
+        let x = self.input_array(input);
+        let z = self.coeffs.dot(&x) + &self.biases;
+        let deltas = z.iter().enumerate().map(|(j, &z)| {
+            (self.activation.derivative)(z) * error.get(j).cloned().unwrap_or(zero())
+        }).collect::<Array1<F>>();
+        let coeffs_grad = Array2::from_shape_fn(self.coeffs.dim(), |(j, i)| deltas[j] * x[i]);
+        let returned = self.coeffs.t().dot(&deltas);
+        (coeffs_grad, deltas, returned)
+    }
+
+    /// Applies given gradients using `rule`.
+    fn apply<R: GradientRule<Float = F>>(&mut self, rule: &R, coeffs_grad: &Array2<F>, biases_grad: &Array1<F>) {
+        // both the parameters and the gradients are in standard layout
+        rule.apply(&mut self.coeffs_state,
+                   self.coeffs.as_slice_mut().unwrap(),
+                   coeffs_grad.as_slice().unwrap(),
+                   true);
+        rule.apply(&mut self.biases_state,
+                   self.biases.as_slice_mut().unwrap(),
+                   biases_grad.as_slice().unwrap(),
+                   false);
+    }
+}
+
+impl<F, V, D> Compute<F> for DenseLayer<F, V, D>
+    where F: Float + 'static,
+          V: Fn(F) -> F,
+          D: Fn(F) -> F
+{
+    fn compute(&self, input: &[F]) -> Vec<F> {
+        let z = self.coeffs.dot(&self.input_array(input)) + &self.biases;
+        z.iter().map(|&z| (self.activation.value)(z)).collect()
+    }
+
+    fn compute_batch(&self, inputs: &[Vec<F>]) -> Vec<Vec<F>> {
+        let n = self.coeffs.ncols();
+        let x = Array2::from_shape_fn((inputs.len(), n), |(k, i)| {
+            inputs[k].get(i).cloned().unwrap_or(zero())
+        });
+        let z = x.dot(&self.coeffs.t()) + &self.biases;
+        z.outer_iter().map(|row| row.iter().map(|&z| (self.activation.value)(z)).collect()).collect()
+    }
+
+    fn input_size(&self) -> usize {
+        self.coeffs.ncols()
+    }
+
+    fn output_size(&self) -> usize {
+        self.biases.len()
+    }
+}
+
+impl<F, V, D, R> BackpropTrain<F, R> for DenseLayer<F, V, D>
+    where F: Float + 'static,
+          V: Fn(F) -> F,
+          D: Fn(F) -> F,
+          R: GradientRule<Float = F>
+{
+    fn backprop_train(&mut self, rule: &R, input: &[F], error: &[F]) -> Vec<F> {
+        let (coeffs_grad, biases_grad, returned) = self.gradients(input, error);
+        self.apply(rule, &coeffs_grad, &biases_grad);
+        returned.to_vec()
+    }
+}
+
+/// The supervised training uses the mean squared error: the layer is trained
+/// by backpropagation with the error signal `2*(output - target)/n`.
+impl<F, V, D, R> SupervisedTrain<F, R> for DenseLayer<F, V, D>
+    where F: Float + 'static,
+          V: Fn(F) -> F,
+          D: Fn(F) -> F,
+          R: GradientRule<Float = F>
+{
+    fn supervised_train(&mut self, rule: &R, input: &[F], target: &[F]) {
+        let error = output_error(&self.compute(input), target);
+        self.backprop_train(rule, input, &error);
+    }
+}
+
+/// The batch training uses the mean squared error, like the supervised
+/// training.
+impl<F, V, D, R> BatchSupervisedTrain<F, R> for DenseLayer<F, V, D>
+    where F: Float + 'static,
+          V: Fn(F) -> F,
+          D: Fn(F) -> F,
+          R: GradientRule<Float = F>
+{
+    fn train_batch(&mut self, rule: &R, inputs: &[Vec<F>], targets: &[Vec<F>]) {
+        if inputs.is_empty() { return; }
+        let mut coeffs_grad = Array2::zeros(self.coeffs.dim());
+        let mut biases_grad = Array1::zeros(self.biases.len());
+        let outputs = self.compute_batch(inputs);
+        for (k, (input, output)) in inputs.iter().zip(outputs.iter()).enumerate() {
+            let target = targets.get(k).map(|t| &t[..]).unwrap_or(&[]);
+            let (cg, bg, _) = self.gradients(input, &output_error(output, target));
+            coeffs_grad = coeffs_grad + cg;
+            biases_grad = biases_grad + bg;
+        }
+        let n = F::from(inputs.len()).unwrap();
+        coeffs_grad.mapv_inplace(|g| g / n);
+        biases_grad.mapv_inplace(|g| g / n);
+        self.apply(rule, &coeffs_grad, &biases_grad);
+    }
+}
+
+impl<F, V, D> ModeAware for DenseLayer<F, V, D>
+    where F: Float,
+          V: Fn(F) -> F,
+          D: Fn(F) -> F
+{}
+
+impl<F, V, D> Trainable for DenseLayer<F, V, D>
+    where F: Float,
+          V: Fn(F) -> F,
+          D: Fn(F) -> F
+{
+    fn num_parameters(&self) -> usize {
+        self.coeffs.len() + self.biases.len()
+    }
+
+    fn has_nan(&self) -> bool {
+        any_non_finite(self.coeffs.as_slice().unwrap()) || any_non_finite(self.biases.as_slice().unwrap())
+    }
+}
+
+impl<F, V, D> Parametrized<F> for DenseLayer<F, V, D>
+    where F: Float,
+          V: Fn(F) -> F,
+          D: Fn(F) -> F
+{
+    fn parameters_iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a F> + 'a> {
+        Box::new(self.coeffs.iter().chain(self.biases.iter()))
+    }
+
+    fn parameters_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = &'a mut F> + 'a> {
+        Box::new(self.coeffs.iter_mut().chain(self.biases.iter_mut()))
+    }
+}
+
+/// The weights are given in the same row-major order as for a
+/// `FeedforwardLayer`.
+impl<F, V, D> Weights<F> for DenseLayer<F, V, D>
+    where F: Float,
+          V: Fn(F) -> F,
+          D: Fn(F) -> F
+{
+    fn weights(&self) -> &[F] {
+        self.coeffs.as_slice().unwrap()
+    }
+
+    fn weights_mut(&mut self) -> &mut [F] {
+        self.coeffs.as_slice_mut().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{Array1, Array2};
+
+    use {Compute, BackpropTrain, BatchSupervisedTrain, Trainable};
+    use activations::sigmoid;
+    use diagnostics::gradient_check;
+    use feedforward::FeedforwardLayer;
+    use loss::MeanSquaredError;
+    use testing::random;
+    use training::GradientDescent;
+
+    use super::DenseLayer;
+
+    #[allow(clippy::type_complexity)]
+    fn layers() -> (FeedforwardLayer<f64, fn(f64) -> f64, fn(f64) -> f64>,
+                    DenseLayer<f64, fn(f64) -> f64, fn(f64) -> f64>) {
+        let ff = FeedforwardLayer::new_from(3, 2, sigmoid(), random());
+        let (coeffs, biases) = ff.save_weights();
+        let dense = DenseLayer::from_arrays(Array2::from_shape_vec((2, 3), coeffs).unwrap(),
+                                            Array1::from(biases),
+                                            sigmoid()).unwrap();
+        (ff, dense)
+    }
+
+    #[test]
+    fn matches_feedforward() {
+        let (mut ff, mut dense) = layers();
+        assert_eq!(dense.input_size(), 3);
+        assert_eq!(dense.output_size(), 2);
+        assert_eq!(dense.num_parameters(), 8);
+        let inputs = vec![vec![0.3, -0.8, 0.5], vec![1.0, 0.0, -1.0], vec![0.25]];
+        for (input, output) in inputs.iter().zip(dense.compute_batch(&inputs)) {
+            let expected = ff.compute(input);
+            for j in 0..2 {
+                assert!((dense.compute(input)[j] - expected[j]).abs() < 1e-12);
+                assert!((output[j] - expected[j]).abs() < 1e-12);
+            }
+        }
+
+        let rule = GradientDescent::new(0.5);
+        let a = ff.backprop_train(&rule, &inputs[0], &[1.0, -0.5]);
+        let b = dense.backprop_train(&rule, &inputs[0], &[1.0, -0.5]);
+        for i in 0..3 { assert!((a[i] - b[i]).abs() < 1e-12); }
+        ff.train_batch(&rule, &inputs, &[vec![1.0, 0.0], vec![0.0, 1.0], vec![1.0, 1.0]]);
+        dense.train_batch(&rule, &inputs, &[vec![1.0, 0.0], vec![0.0, 1.0], vec![1.0, 1.0]]);
+        for (a, b) in ff.weights().iter().zip(dense.coeffs().iter()) {
+            assert!((a - b).abs() < 1e-12);
+        }
+        for (a, b) in ff.biases().iter().zip(dense.biases().iter()) {
+            assert!((a - b).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn gradients() {
+        let (_, mut dense) = layers();
+        let error = gradient_check(&mut dense, &MeanSquaredError, &[0.3, -0.8, 0.5], &[1.0, 0.0], 1e-6);
+        assert!(error < 1e-4);
+    }
+}
@@ -0,0 +1,205 @@
+//! Tools to check the correctness of the training of networks.
+
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+use num::{Float, one, zero};
+
+use {Compute, BackpropTrain, Method, Parametrized};
+use loss::Loss;
+use training::{GradientDescent, GradientRule, RuleState};
+
+/// A network whose connection weights can be accessed as a single slice.
+pub trait Weights<F: Float> {
+    /// Get access to the weights of the network.
+    fn weights(&self) -> &[F];
+    /// Get mutable access to the weights of the network.
+    fn weights_mut(&mut self) -> &mut [F];
+}
+
+/// A training rule that does not change the parameters it is applied to, but
+/// records their gradients instead.
+///
+/// The gradients of all parameters, weights and biases alike, are recorded in
+/// the order they are applied, which for a single layer is the order of its
+/// weights followed by its biases.
+pub struct GradientRecorder<F: Float> {
+    grads: RefCell<Vec<F>>
+}
+
+impl<F: Float> GradientRecorder<F> {
+    /// Creates a new recorder, with no gradient recorded.
+    pub fn new() -> GradientRecorder<F> {
+        GradientRecorder { grads: RefCell::new(Vec::new()) }
+    }
+
+    /// Returns the recorded gradients, and clears them.
+    pub fn take(&self) -> Vec<F> {
+        ::core::mem::take(&mut *self.grads.borrow_mut())
+    }
+}
+
+impl<F: Float> Default for GradientRecorder<F> {
+    fn default() -> GradientRecorder<F> {
+        GradientRecorder::new()
+    }
+}
+
+impl<F: Float> Method for GradientRecorder<F> {}
+
+impl<F: Float> GradientRule for GradientRecorder<F> {
+    type Float = F;
+
+    fn apply(&self, _state: &mut RuleState<F>, _params: &mut [F], grads: &[F], _weights: bool) {
+        self.grads.borrow_mut().extend_from_slice(grads);
+    }
+}
+
+/// Compares the gradient of `loss` with respect to the parameters of
+/// `network`, as given by `Parametrized::parameters_mut()`, computed by
+/// backpropagation with its finite-differences estimation.
+///
+/// Each parameter, weights and biases alike, is moved by `epsilon` in both
+/// directions to estimate the derivative of the loss on given input and
+/// target. The backpropagated gradient is read back as the change a gradient
+/// descent step of rate 1 makes to each parameter, so it does not depend on
+/// the order in which the network updates them. The network is left
+/// unchanged.
+///
+/// The gradient returned by `backprop_train` with respect to the input is
+/// checked the same way.
+///
+/// Returns the maximum relative error between both gradients, differences
+/// smaller than `epsilon` in absolute value being considered relative to
+/// `epsilon`.
+pub fn gradient_check<F, N, L>(network: &mut N, loss: &L, input: &[F], target: &[F], epsilon: F) -> F
+    where F: Float,
+          N: Compute<F> + BackpropTrain<F, GradientDescent<F>> + Parametrized<F>,
+          L: Loss<F>
+{
+    let two = F::from(2.0).unwrap();
+    let values: Vec<F> = network.parameters_iter().cloned().collect();
+    let mut numeric = Vec::with_capacity(values.len());
+    for (k, &w) in values.iter().enumerate() {
+        // restore the previous parameter and move this one in a single pass
+        let start = k.saturating_sub(1);
+        for (i, p) in network.parameters_mut().enumerate().skip(start).take(k + 1 - start) {
+            *p = if i == k { w + epsilon } else { values[i] };
+        }
+        let plus = loss.loss(&network.compute(input), target);
+        if let Some(p) = network.parameters_mut().nth(k) { *p = w - epsilon; }
+        let minus = loss.loss(&network.compute(input), target);
+        numeric.push((plus - minus) / (two * epsilon));
+    }
+    if let (Some(p), Some(&w)) = (network.parameters_mut().last(), values.last()) { *p = w; }
+
+    let mut moved = input.to_vec();
+    let input_numeric: Vec<F> = (0..input.len()).map(|i| {
+        moved[i] = input[i] + epsilon;
+        let plus = loss.loss(&network.compute(&moved), target);
+        moved[i] = input[i] - epsilon;
+        let minus = loss.loss(&network.compute(&moved), target);
+        moved[i] = input[i];
+        (plus - minus) / (two * epsilon)
+    }).collect();
+
+    let output = network.compute(input);
+    let input_grad = network.backprop_train(&GradientDescent::new(one()), input, &loss.grad(&output, target));
+
+    let relative = |a: F, n: F| (a - n).abs() / a.abs().max(n.abs()).max(epsilon);
+    let mut max = input_grad.iter().zip(input_numeric).fold(zero(), |max: F, (&a, n)| max.max(relative(a, n)));
+    for ((p, &w), n) in network.parameters_mut().zip(&values).zip(numeric) {
+        max = max.max(relative(w - *p, n));
+        *p = w;
+    }
+    max
+}
+
+#[cfg(test)]
+mod tests {
+    use {Conv1D, Compute, BackpropTrain, Parametrized};
+    use activations::sigmoid;
+    use feedforward::FeedforwardLayer;
+    use loss::MeanSquaredError;
+    use testing::random;
+    use training::GradientRule;
+    use util::Chain;
+
+    use super::{gradient_check, GradientRecorder};
+
+    /// `y = w*x + b`, with a wrong gradient for `b`.
+    struct WrongBias {
+        params: [f64; 2]
+    }
+
+    impl Compute<f64> for WrongBias {
+        fn compute(&self, input: &[f64]) -> Vec<f64> {
+            vec![self.params[0] * input[0] + self.params[1]]
+        }
+
+        fn input_size(&self) -> usize { 1 }
+
+        fn output_size(&self) -> usize { 1 }
+    }
+
+    impl<R: GradientRule<Float = f64>> BackpropTrain<f64, R> for WrongBias {
+        fn backprop_train(&mut self, rule: &R, input: &[f64], error: &[f64]) -> Vec<f64> {
+            let (w, b) = self.params.split_at_mut(1);
+            rule.apply(&mut Default::default(), w, &[error[0] * input[0]], true);
+            rule.apply(&mut Default::default(), b, &[2.0 * error[0]], false);
+            vec![error[0] * w[0]]
+        }
+    }
+
+    impl Parametrized<f64> for WrongBias {
+        fn parameters_iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a f64> + 'a> {
+            Box::new(self.params.iter())
+        }
+
+        fn parameters_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = &'a mut f64> + 'a> {
+            Box::new(self.params.iter_mut())
+        }
+    }
+
+    #[test]
+    fn recorder() {
+        let mut layer = WrongBias { params: [0.5, -0.3] };
+        let recorder = GradientRecorder::new();
+        layer.backprop_train(&recorder, &[0.8], &[0.25]);
+        assert_eq!(recorder.take(), vec![0.2, 0.5]);
+        assert_eq!(layer.params, [0.5, -0.3]);
+        assert!(recorder.take().is_empty());
+    }
+
+    #[test]
+    fn feedforward() {
+        let mut layer = FeedforwardLayer::new_from(3, 2, sigmoid(), random());
+        let before = layer.save_weights();
+        let error = gradient_check(&mut layer, &MeanSquaredError, &[0.3, -0.8, 0.5], &[1.0, 0.0], 1e-6);
+        assert!(error < 1e-4);
+        assert_eq!(layer.save_weights(), before);
+    }
+
+    #[test]
+    fn conv1d() {
+        let mut layer = Conv1D::new_from(2, 4, 2, 2, 1, sigmoid(), random());
+        let input = [0.3, -0.8, 0.5, 0.1, -0.4, 0.9, 0.2, -0.6];
+        let error = gradient_check(&mut layer, &MeanSquaredError, &input, &[1.0, 0.0, 0.5, 0.0, 1.0, 0.5], 1e-6);
+        assert!(error < 1e-4);
+    }
+
+    #[test]
+    fn biases_are_checked() {
+        let mut layer = WrongBias { params: [0.5, -0.3] };
+        assert!(gradient_check(&mut layer, &MeanSquaredError, &[0.8], &[1.0], 1e-6) > 0.1);
+    }
+
+    #[test]
+    fn chain() {
+        let mut random = random();
+        let mut network = Chain::new(FeedforwardLayer::new_from(3, 4, sigmoid(), &mut random),
+                                     FeedforwardLayer::new_from(4, 2, sigmoid(), &mut random));
+        let error = gradient_check(&mut network, &MeanSquaredError, &[0.3, -0.8, 0.5], &[1.0, 0.0], 1e-6);
+        assert!(error < 1e-4);
+    }
+}
@@ -0,0 +1,163 @@
+//! Dropout regularization
+
+use alloc::borrow::ToOwned;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+use num::{Float, zero, one};
+use rand::{Rng, SeedableRng, XorShiftRng, thread_rng};
+
+use {Compute, BackpropTrain, Method, Trainable, Parametrized, ModeAware, ParameterError};
+
+/// A dropout layer.
+///
+/// In training mode, each input is set to zero with probability `p`, and the
+/// remaining ones are scaled by `1/(1-p)` so that the expected value of the
+/// output matches the input (inverted dropout). In inference mode, the layer
+/// is the identity.
+///
+/// The units dropped by the last call to `compute()` are remembered, so that
+/// `backprop_train()` only lets the gradient through the units that were kept,
+/// scaled like their values were. The drop probability is fixed rather than
+/// learned, so any training method can be passed to it and is simply ignored.
+pub struct Dropout<F: Float> {
+    size: usize,
+    p: F,
+    training: bool,
+    rng: RefCell<XorShiftRng>,
+    mask: RefCell<Vec<F>>
+}
+
+impl<F: Float> Dropout<F> {
+    /// Creates a new dropout layer of `size` units, dropping each of them
+    /// with probability `p`.
+    ///
+    /// The layer starts in training mode, and its random number generator is
+    /// seeded from the thread-local generator.
+    ///
+    /// Fails if `p` is not in `[0, 1)`.
+    pub fn new(size: usize, p: F) -> Result<Dropout<F>, ParameterError> {
+        Dropout::with_seed(size, p, thread_rng().gen())
+    }
+
+    /// Creates a new dropout layer like `new()`, but seeding its random
+    /// number generator with `seed` for reproducible masks.
+    ///
+    /// Fails if `p` is not in `[0, 1)`.
+    pub fn with_seed(size: usize, p: F, seed: [u32; 4]) -> Result<Dropout<F>, ParameterError> {
+        if !(p >= zero() && p < one()) {
+            return Err(ParameterError { name: "p", value: p.to_f64().unwrap_or(f64::NAN) });
+        }
+        Ok(Dropout {
+            size,
+            p,
+            training: true,
+            rng: RefCell::new(XorShiftRng::from_seed(seed)),
+            mask: RefCell::new(Vec::new())
+        })
+    }
+
+    /// Switches the layer between training mode (`true`) and inference
+    /// mode (`false`).
+    pub fn train_mode(&mut self, training: bool) {
+        self.training = training;
+    }
+
+    /// Whether the layer is in training mode.
+    pub fn is_training(&self) -> bool {
+        self.training
+    }
+}
+
+impl<F: Float> Compute<F> for Dropout<F> {
+    fn compute(&self, input: &[F]) -> Vec<F> {
+        let mut output = input.to_owned();
+        output.resize(self.size, zero());
+        if !self.training {
+            return output;
+        }
+        let scale = one::<F>() / (one::<F>() - self.p);
+        let mut rng = self.rng.borrow_mut();
+        let mut mask = self.mask.borrow_mut();
+        mask.clear();
+        for x in &mut output {
+            let keep = F::from(rng.gen::<f64>()).unwrap() >= self.p;
+            let m = if keep { scale } else { zero() };
+            mask.push(m);
+            *x = *x * m;
+        }
+        output
+    }
+
+    fn input_size(&self) -> usize {
+        self.size
+    }
+
+    fn output_size(&self) -> usize {
+        self.size
+    }
+}
+
+impl<F: Float, M: Method> BackpropTrain<F, M> for Dropout<F> {
+    fn backprop_train(&mut self, _rule: &M, _input: &[F], error: &[F]) -> Vec<F> {
+        let mut returned = error.to_owned();
+        returned.resize(self.size, zero());
+        let mask = self.mask.borrow();
+        if self.training && mask.len() == self.size {
+            for (g, &m) in returned.iter_mut().zip(mask.iter()) {
+                *g = *g * m;
+            }
+        }
+        returned
+    }
+}
+
+impl<F: Float> ModeAware for Dropout<F> {
+    fn set_mode(&mut self, training: bool) {
+        self.train_mode(training);
+    }
+}
+
+impl<F: Float> Trainable for Dropout<F> {}
+
+impl<F: Float> Parametrized<F> for Dropout<F> {}
+
+#[cfg(test)]
+mod tests {
+    use {Compute, BackpropTrain, ParameterError};
+    use training::GradientDescent;
+
+    use super::Dropout;
+
+    #[test]
+    fn inference_identity() {
+        let mut layer = Dropout::with_seed(4, 0.5f64, [1, 2, 3, 4]).unwrap();
+        layer.train_mode(false);
+        assert_eq!(layer.compute(&[1.0, -2.0, 3.0, 0.5]), vec![1.0, -2.0, 3.0, 0.5]);
+        assert_eq!(layer.backprop_train(&GradientDescent::new(0.1), &[0.0; 4], &[1.0, 2.0, 3.0, 4.0]),
+                   vec![1.0, 2.0, 3.0, 4.0]);
+    }
+
+    #[test]
+    fn expected_magnitude() {
+        let mut layer = Dropout::with_seed(10000, 0.3f64, [1, 2, 3, 4]).unwrap();
+        let output = layer.compute(&vec![1.0; 10000]);
+        let dropped = output.iter().filter(|&&y| y == 0.0).count();
+        assert!((dropped as f64 / 10000.0 - 0.3).abs() < 0.02);
+        let mean = output.iter().sum::<f64>() / 10000.0;
+        assert!((mean - 1.0).abs() < 0.05);
+
+        // the gradient only flows through the kept units
+        let grad = layer.backprop_train(&GradientDescent::new(0.1), &vec![1.0; 10000], &vec![1.0; 10000]);
+        assert_eq!(grad, output);
+    }
+
+    #[test]
+    fn invalid_probability() {
+        for &p in &[-0.1f64, 1.0, 1.5] {
+            assert_eq!(Dropout::new(4, p).err(), Some(ParameterError { name: "p", value: p }));
+        }
+        assert!(Dropout::new(4, f64::NAN).is_err());
+        assert!(Dropout::new(4, 0.0f64).is_ok());
+    }
+}
@@ -0,0 +1,245 @@
+//! Embedding layers, turning categorical inputs into dense vectors
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use num::{Float, zero};
+
+use {Trainable, Parametrized, ModeAware, any_non_finite};
+use diagnostics::Weights;
+use training::{GradientRule, RuleState};
+
+/// A lookup table mapping integer indices (for example the words of a
+/// vocabulary) to dense vectors of dimension `dim`.
+///
+/// Its inputs being indices rather than values, this layer does not implement
+/// `Compute`: its output is obtained with `lookup()` or `lookup_mean()`, and
+/// can then be fed to the regular layers of a network. Indices out of the
+/// table map to a vector of zeros and are never trained.
+///
+/// Training only updates the rows that were looked up, each row keeping its
+/// own memory of the training rule.
+pub struct Embedding<F: Float> {
+    dim: usize,
+    table: Vec<F>,
+    states: Vec<RuleState<F>>
+}
+
+impl<F: Float> Embedding<F> {
+    /// Creates a new table of `size` vectors of dimension `dim`, all set to 0.
+    pub fn new(size: usize, dim: usize) -> Embedding<F> {
+        Embedding::new_from(size, dim, zero)
+    }
+
+    /// Creates a new table of `size` vectors of dimension `dim`, generated by
+    /// provided closure (for example a random number generator).
+    pub fn new_from<G>(size: usize, dim: usize, mut generator: G) -> Embedding<F>
+        where G: FnMut() -> F
+    {
+        Embedding {
+            dim,
+            table: (0..size*dim).map(|_| generator()).collect(),
+            states: (0..size).map(|_| RuleState::new()).collect()
+        }
+    }
+
+    /// The number of vectors in this table.
+    pub fn size(&self) -> usize {
+        self.states.len()
+    }
+
+    /// The dimension of the vectors of this table.
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// The vector associated to `index`, if it is in the table.
+    pub fn row(&self, index: usize) -> Option<&[F]> {
+        if index < self.size() {
+            Some(&self.table[index*self.dim..(index+1)*self.dim])
+        } else {
+            None
+        }
+    }
+
+    /// The concatenation of the vectors associated to `indices`, of length
+    /// `indices.len() * dim`.
+    pub fn lookup(&self, indices: &[usize]) -> Vec<F> {
+        let mut output = Vec::with_capacity(indices.len() * self.dim);
+        for &index in indices {
+            match self.row(index) {
+                Some(row) => output.extend_from_slice(row),
+                None => output.extend((0..self.dim).map(|_| zero::<F>()))
+            }
+        }
+        output
+    }
+
+    /// The average of the vectors associated to `indices`, of length `dim`.
+    ///
+    /// The average of no vector at all is a vector of zeros.
+    pub fn lookup_mean(&self, indices: &[usize]) -> Vec<F> {
+        let mut output = vec![zero(); self.dim];
+        for row in indices.iter().filter_map(|&index| self.row(index)) {
+            for (o, &x) in output.iter_mut().zip(row) {
+                *o = *o + x;
+            }
+        }
+        let n = F::from(indices.len().max(1)).unwrap();
+        for o in &mut output {
+            *o = *o / n;
+        }
+        output
+    }
+
+    /// Performs one step of training of the vectors returned by
+    /// `lookup(indices)`, given the gradient `error` of the error with
+    /// respect to this output.
+    ///
+    /// Only the looked up rows are updated. If an index appears several times,
+    /// the gradients of all its occurrences are summed.
+    pub fn train_lookup<R>(&mut self, rule: &R, indices: &[usize], error: &[F])
+        where R: GradientRule<Float = F>
+    {
+        let grads = indices.iter().enumerate().map(|(k, &index)| {
+            let grad = (0..self.dim).map(|d| error.get(k*self.dim + d).cloned().unwrap_or(zero()))
+                                    .collect();
+            (index, grad)
+        }).collect::<Vec<_>>();
+        self.apply_grads(rule, grads);
+    }
+
+    /// Performs one step of training of the vector returned by
+    /// `lookup_mean(indices)`, given the gradient `error` of the error with
+    /// respect to this output.
+    ///
+    /// Only the looked up rows are updated.
+    pub fn train_lookup_mean<R>(&mut self, rule: &R, indices: &[usize], error: &[F])
+        where R: GradientRule<Float = F>
+    {
+        let n = F::from(indices.len().max(1)).unwrap();
+        let grad = (0..self.dim).map(|d| error.get(d).cloned().unwrap_or(zero()) / n)
+                                .collect::<Vec<F>>();
+        let grads = indices.iter().map(|&index| (index, grad.clone())).collect();
+        self.apply_grads(rule, grads);
+    }
+
+    /// Applies the gradients of given rows, summing those of a same row.
+    fn apply_grads<R>(&mut self, rule: &R, mut grads: Vec<(usize, Vec<F>)>)
+        where R: GradientRule<Float = F>
+    {
+        grads.retain(|g| g.0 < self.states.len());
+        grads.sort_by_key(|g| g.0);
+        let mut i = 0;
+        while i < grads.len() {
+            let index = grads[i].0;
+            let mut grad = vec![zero(); self.dim];
+            while i < grads.len() && grads[i].0 == index {
+                for (acc, &g) in grad.iter_mut().zip(&grads[i].1) {
+                    *acc = *acc + g;
+                }
+                i += 1;
+            }
+            let row = &mut self.table[index*self.dim..(index+1)*self.dim];
+            rule.apply(&mut self.states[index], row, &grad, true);
+        }
+    }
+}
+
+impl<F: Float> Weights<F> for Embedding<F> {
+    fn weights(&self) -> &[F] {
+        &self.table
+    }
+
+    fn weights_mut(&mut self) -> &mut [F] {
+        &mut self.table
+    }
+}
+
+impl<F: Float> ModeAware for Embedding<F> {}
+
+impl<F: Float> Trainable for Embedding<F> {
+    fn num_parameters(&self) -> usize {
+        self.table.len()
+    }
+
+    fn has_nan(&self) -> bool {
+        any_non_finite(&self.table)
+    }
+}
+
+impl<F: Float> Parametrized<F> for Embedding<F> {
+    fn parameters_iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a F> + 'a> {
+        Box::new(self.table.iter())
+    }
+
+    fn parameters_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = &'a mut F> + 'a> {
+        Box::new(self.table.iter_mut())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {Compute, BackpropTrain, FeedforwardLayer, Trainable};
+    use activations::identity;
+    use training::GradientDescent;
+
+    use super::Embedding;
+
+    fn table() -> Embedding<f64> {
+        let mut acc = 0.0;
+        Embedding::new_from(4, 3, move || { acc += 1.0; acc })
+    }
+
+    #[test]
+    fn lookup() {
+        let embedding = table();
+        assert_eq!(embedding.size(), 4);
+        assert_eq!(embedding.dim(), 3);
+        assert_eq!(embedding.num_parameters(), 12);
+        assert_eq!(embedding.lookup(&[2]), vec![7.0, 8.0, 9.0]);
+        assert_eq!(embedding.lookup(&[2, 0]), vec![7.0, 8.0, 9.0, 1.0, 2.0, 3.0]);
+        assert_eq!(embedding.lookup(&[5]), vec![0.0, 0.0, 0.0]);
+        assert_eq!(embedding.lookup_mean(&[2, 0]), vec![4.0, 5.0, 6.0]);
+        assert_eq!(embedding.lookup_mean(&[]), vec![0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn train_only_looked_up_rows() {
+        let mut embedding = table();
+        embedding.train_lookup(&GradientDescent::new(1.0), &[2], &[1.0, -1.0, 0.5]);
+        assert_eq!(embedding.row(2), Some(&[6.0, 9.0, 8.5][..]));
+        for &i in &[0, 1, 3] {
+            assert_eq!(embedding.row(i), table().row(i));
+        }
+
+        // a repeated index sums its gradients
+        let mut embedding = table();
+        embedding.train_lookup(&GradientDescent::new(1.0), &[1, 1], &[1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+        assert_eq!(embedding.row(1), Some(&[2.0, 5.0, 6.0][..]));
+
+        let mut embedding = table();
+        embedding.train_lookup_mean(&GradientDescent::new(1.0), &[0, 3], &[2.0, 0.0, 0.0]);
+        assert_eq!(embedding.row(0), Some(&[0.0, 2.0, 3.0][..]));
+        assert_eq!(embedding.row(3), Some(&[9.0, 11.0, 12.0][..]));
+        assert_eq!(embedding.row(1), table().row(1));
+    }
+
+    #[test]
+    fn feeding_a_layer() {
+        let mut embedding = table();
+        let mut layer = FeedforwardLayer::new_from(6, 1, identity(), || 0.1);
+        let rule = GradientDescent::new(0.001);
+        let indices = [3, 1];
+        let error = |e: &Embedding<f64>, l: &FeedforwardLayer<f64, _, _>| l.compute(&e.lookup(&indices))[0] - 1.0;
+        let before = error(&embedding, &layer).abs();
+        for _ in 0..10 {
+            let input = embedding.lookup(&indices);
+            let output = layer.compute(&input);
+            let grad = layer.backprop_train(&rule, &input, &[output[0] - 1.0]);
+            embedding.train_lookup(&rule, &indices, &grad);
+        }
+        assert!(error(&embedding, &layer).abs() < before);
+        assert_eq!(embedding.row(0), table().row(0));
+    }
+}
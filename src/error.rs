@@ -0,0 +1,88 @@
+//! Error types
+
+use core::error::Error;
+use core::fmt;
+
+/// Error returned when the size of some values does not match what was
+/// expected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ShapeError {
+    /// The expected size.
+    pub expected: usize,
+    /// The size that was actually provided.
+    pub actual: usize
+}
+
+impl fmt::Display for ShapeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "mismatched sizes: expected {}, got {}", self.expected, self.actual)
+    }
+}
+
+impl Error for ShapeError {}
+
+/// Error returned when a class label is not below the number of classes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LabelError {
+    /// The label that was provided.
+    pub label: usize,
+    /// The number of classes.
+    pub num_classes: usize
+}
+
+impl fmt::Display for LabelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "label {} out of range for {} classes", self.label, self.num_classes)
+    }
+}
+
+impl Error for LabelError {}
+
+/// Error returned when a parameter is outside of its valid range.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParameterError {
+    /// The name of the parameter.
+    pub name: &'static str,
+    /// The value that was provided.
+    pub value: f64
+}
+
+impl fmt::Display for ParameterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid value {} for parameter {}", self.value, self.name)
+    }
+}
+
+impl Error for ParameterError {}
+
+/// Error returned when the parameters of a network became NaN or infinite
+/// during training.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NonFiniteError {
+    /// The epoch after which non-finite parameters were found, counting
+    /// from `0`.
+    pub epoch: usize
+}
+
+impl fmt::Display for NonFiniteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "non-finite parameters after epoch {}", self.epoch)
+    }
+}
+
+impl Error for NonFiniteError {}
+
+/// Error returned when building a layer without one of its required settings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BuildError {
+    /// The name of the missing setting.
+    pub missing: &'static str
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "missing setting `{}`", self.missing)
+    }
+}
+
+impl Error for BuildError {}
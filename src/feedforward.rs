@@ -1,12 +1,23 @@
 //! Constructions related to feed-forward networks
 
-use std::cmp::min;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt;
 
 use num::{Float, zero};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Serializer, Deserialize};
+#[cfg(feature = "serde")]
+use serde::ser::SerializeStruct;
 
-use {Compute, BackpropTrain, SupervisedTrain};
-use activations::ActivationFunction;
-use training::{PerceptronRule, GradientDescent};
+use {Compute, BackpropTrain, SupervisedTrain, BatchSupervisedTrain, Trainable, Parametrized, ModeAware, ShapeError, BuildError};
+use {output_error, any_non_finite};
+use activations::{ActivationFunction, ActivationKind, BoxedActivation, BoxedFn, identity};
+use diagnostics::Weights;
+use training::{PerceptronRule, GradientRule, RuleState};
 
 /// A feedforward layer
 ///
@@ -23,11 +34,20 @@ use training::{PerceptronRule, GradientDescent};
 /// ```
 ///
 /// The training of this layer consists on fitting the values of `W` and `B`.
+///
+/// It can be trained by backpropagation using any `GradientRule`, the memory
+/// of stateful rules being kept by the layer.
 pub struct FeedforwardLayer<F: Float, V: Fn(F) -> F, D: Fn(F) -> F> {
     inputs: usize,
     coeffs: Vec<F>,
     biases: Vec<F>,
-    activation: ActivationFunction<F, V, D>
+    activation: ActivationFunction<F, V, D>,
+    kind: Option<ActivationKind>,
+    frozen: bool,
+    bias: bool,
+    accumulated: Option<Accumulated<F>>,
+    coeffs_state: RuleState<F>,
+    biases_state: RuleState<F>
 }
 
 impl<F, V, D> FeedforwardLayer<F, V, D>
@@ -42,12 +62,23 @@ impl<F, V, D> FeedforwardLayer<F, V, D>
                activation: ActivationFunction<F, V, D>)
         -> FeedforwardLayer<F, V, D>
     {
-        FeedforwardLayer {
-            inputs: inputs,
-            coeffs: vec![zero(); inputs*outputs],
-            biases: vec![zero(); outputs],
-            activation: activation
-        }
+        FeedforwardLayer::with_parameters(inputs, vec![zero(); inputs*outputs], vec![zero(); outputs], activation)
+    }
+
+    /// Creates a new linear feedforward layer without biases, with all its
+    /// weights set to 0.
+    ///
+    /// Its output is `f(W*X)`: the biases are not added, and are neither
+    /// trained nor counted in its parameters. This is useful when the layer is
+    /// followed by another one that has its own offset, like a `BatchNorm`.
+    pub fn new_no_bias(inputs: usize,
+                       outputs: usize,
+                       activation: ActivationFunction<F, V, D>)
+        -> FeedforwardLayer<F, V, D>
+    {
+        let mut layer = FeedforwardLayer::new(inputs, outputs, activation);
+        layer.bias = false;
+        layer
     }
 
     /// Creates a new linear feedforward layer with all its weights and biases
@@ -58,34 +89,947 @@ impl<F, V, D> FeedforwardLayer<F, V, D>
                        mut generator: G)
         -> FeedforwardLayer<F, V, D>
         where G: FnMut() -> F
+    {
+        let coeffs = (0..inputs*outputs).map(|_| generator()).collect();
+        let biases = (0..outputs).map(|_| generator()).collect();
+        FeedforwardLayer::with_parameters(inputs, coeffs, biases, activation)
+    }
+
+    /// Creates a new feedforward layer using the Xavier (or Glorot) uniform
+    /// initialization, well suited for sigmoid or tanh activations.
+    ///
+    /// The weights are sampled uniformly in `[-limit, limit]` with
+    /// `limit = sqrt(6 / (inputs + outputs))`, and the biases are set to 0.
+    ///
+    /// `uniform` must generate values uniformly distributed in `[0, 1)`,
+    /// for example `rand::random`.
+    pub fn xavier_uniform<G>(inputs: usize,
+                             outputs: usize,
+                             activation: ActivationFunction<F, V, D>,
+                             mut uniform: G)
+        -> FeedforwardLayer<F, V, D>
+        where G: FnMut() -> F
+    {
+        let limit = (F::from(6.0).unwrap() / F::from(inputs + outputs).unwrap()).sqrt();
+        let two = F::from(2.0).unwrap();
+        let mut layer = FeedforwardLayer::new(inputs, outputs, activation);
+        for w in &mut layer.coeffs {
+            *w = (two * uniform() - F::one()) * limit;
+        }
+        layer
+    }
+
+    /// Creates a new feedforward layer using the He (or Kaiming) normal
+    /// initialization, well suited for ReLU activations.
+    ///
+    /// The weights are sampled from a normal distribution of mean 0 and
+    /// standard deviation `sqrt(2 / inputs)`, and the biases are set to 0.
+    ///
+    /// `normal` must generate values following a standard normal distribution
+    /// (mean 0, standard deviation 1), for example using
+    /// `rand::distributions::normal::StandardNormal`.
+    pub fn he_normal<G>(inputs: usize,
+                        outputs: usize,
+                        activation: ActivationFunction<F, V, D>,
+                        mut normal: G)
+        -> FeedforwardLayer<F, V, D>
+        where G: FnMut() -> F
+    {
+        let std_dev = (F::from(2.0).unwrap() / F::from(inputs).unwrap()).sqrt();
+        let mut layer = FeedforwardLayer::new(inputs, outputs, activation);
+        for w in &mut layer.coeffs {
+            *w = normal() * std_dev;
+        }
+        layer
+    }
+
+    /// Creates a feedforward layer from a known weight matrix and bias vector.
+    ///
+    /// `coeffs` contains the weights in row-major order (the weight from input
+    /// `i` to output `j` being at index `j*inputs + i`), and the number of outputs
+    /// is given by the number of biases.
+    ///
+    /// Fails if `coeffs` does not contain exactly `inputs * biases.len()` values.
+    pub fn from_weights(inputs: usize,
+                        coeffs: Vec<F>,
+                        biases: Vec<F>,
+                        activation: ActivationFunction<F, V, D>)
+        -> Result<FeedforwardLayer<F, V, D>, ShapeError>
+    {
+        if coeffs.len() != inputs * biases.len() {
+            return Err(ShapeError { expected: inputs * biases.len(), actual: coeffs.len() });
+        }
+        Ok(FeedforwardLayer::with_parameters(inputs, coeffs, biases, activation))
+    }
+
+    /// A layer with given parameters, which must have matching sizes, and the
+    /// default settings.
+    fn with_parameters(inputs: usize,
+                       coeffs: Vec<F>,
+                       biases: Vec<F>,
+                       activation: ActivationFunction<F, V, D>)
+        -> FeedforwardLayer<F, V, D>
     {
         FeedforwardLayer {
-            inputs: inputs,
-            coeffs: (0..inputs*outputs).map(|_| generator()).collect(),
-            biases: (0..outputs).map(|_| generator()).collect(),
-            activation: activation
+            inputs,
+            coeffs,
+            biases,
+            activation,
+            kind: None,
+            frozen: false,
+            bias: true,
+            accumulated: None,
+            coeffs_state: RuleState::new(),
+            biases_state: RuleState::new()
+        }
+    }
+
+    /// A copy of the parameters of this layer, which can be saved and later
+    /// turned back into a layer using `from_weights`, or `into_layer` if the
+    /// kind of its activation function is recorded.
+    pub fn parameters(&self) -> FeedforwardParameters<F> {
+        FeedforwardParameters {
+            inputs: self.inputs,
+            coeffs: self.coeffs.clone(),
+            biases: self.biases.clone(),
+            bias: self.bias,
+            activation: self.kind
+        }
+    }
+
+    /// Records which of the built-in activation functions this layer uses,
+    /// so that it is saved along with its parameters.
+    ///
+    /// The layer cannot know it by itself, as it only holds the closures
+    /// of its activation function.
+    pub fn set_activation_kind(&mut self, kind: Option<ActivationKind>) {
+        self.kind = kind;
+    }
+
+    /// The recorded kind of the activation function of this layer, if any.
+    pub fn activation_kind(&self) -> Option<ActivationKind> {
+        self.kind
+    }
+
+    /// Freezes or unfreezes this layer.
+    ///
+    /// A frozen layer is not modified by training: it still backpropagates
+    /// the gradient of the error with respect to its input, so that the
+    /// layers before it in a network can be trained, but its weights and
+    /// biases stay fixed. This is typically used to keep pre-trained layers
+    /// as they are.
+    pub fn set_frozen(&mut self, frozen: bool) {
+        self.frozen = frozen;
+    }
+
+    /// Whether this layer is frozen, see `set_frozen()`.
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// Enables or disables the accumulation of gradients.
+    ///
+    /// In accumulation mode, `train_batch()` does not update the layer, but
+    /// adds the gradients of the batch to an internal buffer. A single update
+    /// using the average gradient of all the accumulated samples is then
+    /// performed by `apply_accumulated()`. This gives the same result as one
+    /// large batch, without having to hold all its samples at once.
+    ///
+    /// Disabling accumulation discards the gradients accumulated so far.
+    pub fn set_accumulate(&mut self, accumulate: bool) {
+        self.accumulated = if accumulate {
+            Some(Accumulated {
+                coeffs: vec![zero(); self.coeffs.len()],
+                biases: vec![zero(); self.biases.len()],
+                samples: 0
+            })
+        } else {
+            None
+        };
+    }
+
+    /// Whether this layer is in accumulation mode, see `set_accumulate()`.
+    pub fn is_accumulating(&self) -> bool {
+        self.accumulated.is_some()
+    }
+
+    /// Updates the layer using the average of the gradients accumulated by
+    /// `train_batch()` since the last call, and clears them.
+    ///
+    /// Does nothing if no gradient was accumulated.
+    pub fn apply_accumulated<R>(&mut self, rule: &R)
+        where R: GradientRule<Float = F>
+    {
+        let mut acc = match self.accumulated.take() {
+            Some(acc) => acc,
+            None => return
+        };
+        if acc.samples > 0 {
+            self.apply_average(rule, &mut acc.coeffs, &mut acc.biases, acc.samples);
+            for g in acc.coeffs.iter_mut().chain(acc.biases.iter_mut()) {
+                *g = zero();
+            }
+            acc.samples = 0;
+        }
+        self.accumulated = Some(acc);
+    }
+
+    /// Process input into output like `compute()`, but fails if `input` does
+    /// not contain exactly as many values as this layer has inputs, instead
+    /// of padding it with zeros or ignoring the extra values.
+    pub fn try_compute(&self, input: &[F]) -> Result<Vec<F>, ShapeError> {
+        if input.len() != self.inputs {
+            return Err(ShapeError { expected: self.inputs, actual: input.len() });
+        }
+        Ok(self.compute_serial(input))
+    }
+
+    /// Computes the pre-activation values `W*X + B` of this layer for given
+    /// input, that is its output before the activation function is applied.
+    ///
+    /// These are for example the logits of a layer followed by a softmax.
+    pub fn compute_linear(&self, input: &[F]) -> Vec<F> {
+        let mut out = vec![zero(); self.biases.len()];
+        self.linear_into(input, &mut out);
+        out
+    }
+
+    /// Computes the output of this layer, accumulating the products of the
+    /// weights with the inputs in the float type `A`.
+    ///
+    /// This is meant for layers storing their parameters in a low precision
+    /// type like `half::f16` (with the `half` feature), whose sums of many
+    /// values lose too much precision: `compute_upcast::<f32>()` converts the
+    /// values to `f32` for the sums, and only converts the result back before
+    /// applying the activation function.
+    pub fn compute_upcast<A: Float>(&self, input: &[F]) -> Vec<F> {
+        let up = |x: F| A::from(x).unwrap_or(zero());
+        (0..self.biases.len()).map(|j| {
+            let mut acc = up(self.bias(j));
+            for (i, &x) in input.iter().take(self.inputs).enumerate() {
+                acc = acc + up(self.coeffs[j*self.inputs + i]) * up(x);
+            }
+            (self.activation.value)(F::from(acc).unwrap_or(zero()))
+        }).collect()
+    }
+
+    /// Writes the pre-activation values of this layer into `out`.
+    fn linear_into(&self, input: &[F], out: &mut [F]) {
+        for (j, o) in out.iter_mut().enumerate().take(self.biases.len()) {
+            let mut acc = self.bias(j);
+            for (i, &x) in input.iter().take(self.inputs).enumerate() {
+                acc = acc + self.coeffs[j*self.inputs + i] * x
+            }
+            *o = acc;
+        }
+    }
+
+    fn compute_serial(&self, input: &[F]) -> Vec<F> {
+        self.compute_linear(input).into_iter().map(|x| (self.activation.value)(x)).collect()
+    }
+
+    fn activate_into(&self, input: &[F], out: &mut [F]) {
+        self.linear_into(input, out);
+        for o in out.iter_mut().take(self.biases.len()) {
+            *o = (self.activation.value)(*o);
+        }
+    }
+
+    /// The batch is processed one output at a time, so that each row of the
+    /// weight matrix is used for the whole batch while it is still in cache.
+    fn compute_batch_serial(&self, inputs: &[Vec<F>]) -> Vec<Vec<F>> {
+        let mut outs = vec![vec![zero(); self.biases.len()]; inputs.len()];
+        for j in 0..self.biases.len() {
+            let row = &self.coeffs[j*self.inputs..(j+1)*self.inputs];
+            for (input, out) in inputs.iter().zip(outs.iter_mut()) {
+                let mut acc = self.bias(j);
+                for (&w, &x) in row.iter().zip(input.iter()) {
+                    acc = acc + w * x;
+                }
+                out[j] = (self.activation.value)(acc);
+            }
+        }
+        outs
+    }
+
+    /// Get access to the weights of this layer.
+    ///
+    /// They are stored in row-major order: the weight from input `i` to
+    /// output `j` is at index `j*inputs + i`.
+    pub fn weights(&self) -> &[F] {
+        &self.coeffs
+    }
+
+    /// Get mutable access to the weights of this layer.
+    ///
+    /// See `weights()` for their layout.
+    pub fn weights_mut(&mut self) -> &mut [F] {
+        &mut self.coeffs
+    }
+
+    /// Whether this layer has biases, see `new_no_bias()`.
+    pub fn has_bias(&self) -> bool {
+        self.bias
+    }
+
+    /// Get access to the biases of this layer, one per output.
+    ///
+    /// They are all 0 and ignored for a layer without biases.
+    pub fn biases(&self) -> &[F] {
+        &self.biases
+    }
+
+    /// Get mutable access to the biases of this layer.
+    pub fn biases_mut(&mut self) -> &mut [F] {
+        &mut self.biases
+    }
+
+    /// Replaces all the weights and biases of this layer by values generated
+    /// by provided closure, in the same order as `new_from`, keeping its
+    /// dimensions.
+    ///
+    /// The biases of a layer without biases stay at 0. The memory of the
+    /// training rule and the accumulated gradients are cleared, as they
+    /// relate to the previous weights.
+    pub fn reinitialize<G>(&mut self, mut generator: G)
+        where G: FnMut() -> F
+    {
+        for w in &mut self.coeffs {
+            *w = generator();
+        }
+        if self.bias {
+            for b in &mut self.biases {
+                *b = generator();
+            }
+        }
+        self.coeffs_state = RuleState::new();
+        self.biases_state = RuleState::new();
+        if self.accumulated.is_some() {
+            self.set_accumulate(true);
+        }
+    }
+
+    /// Multiplies all the weights of this layer by `factor`, leaving its
+    /// biases untouched.
+    pub fn scale_weights(&mut self, factor: F) {
+        for w in &mut self.coeffs {
+            *w = *w * factor;
+        }
+    }
+
+    /// Rescales the weights of this layer so that their L2 norm is
+    /// `target_norm`, leaving its biases untouched.
+    ///
+    /// Weights that are all 0 have no direction to keep, and are left as is.
+    pub fn normalize_weights(&mut self, target_norm: F) {
+        let norm = self.coeffs.iter().fold(zero::<F>(), |acc, &w| acc + w * w).sqrt();
+        if norm > zero() {
+            self.scale_weights(target_norm / norm);
+        }
+    }
+
+    /// Sets to 0 the `fraction` (between `0.0` and `1.0`) of the weights of
+    /// this layer with the smallest magnitude, leaving its biases untouched.
+    ///
+    /// Returns a mask with one boolean per weight, `true` for the pruned ones,
+    /// which can be given to `apply_mask()` after each training step to keep
+    /// them at 0.
+    pub fn prune(&mut self, fraction: f64) -> Vec<bool> {
+        let count = (fraction.clamp(0.0, 1.0) * self.coeffs.len() as f64) as usize;
+        let mut order = (0..self.coeffs.len()).collect::<Vec<_>>();
+        order.sort_by(|&a, &b| {
+            self.coeffs[a].abs().partial_cmp(&self.coeffs[b].abs()).unwrap_or(Ordering::Equal)
+        });
+        let mut mask = vec![false; self.coeffs.len()];
+        for &i in &order[..count] {
+            mask[i] = true;
+        }
+        self.apply_mask(&mask);
+        mask
+    }
+
+    /// Sets to 0 the weights of this layer for which `mask` is `true`, as
+    /// returned by `prune()`.
+    pub fn apply_mask(&mut self, mask: &[bool]) {
+        for (w, &m) in self.coeffs.iter_mut().zip(mask) {
+            if m {
+                *w = zero();
+            }
+        }
+    }
+
+    /// Replaces the weights and biases of this layer by their weighted average
+    /// with those of `other`:
+    ///
+    /// ```text
+    /// W = (1 - weight) * W + weight * W_other
+    /// ```
+    ///
+    /// Fails without changing anything if both layers do not have the same
+    /// shape. The activation functions and the memory of the training rules
+    /// are left untouched.
+    pub fn average_with<V2, D2>(&mut self, other: &FeedforwardLayer<F, V2, D2>, weight: F)
+        -> Result<(), ShapeError>
+        where V2: Fn(F) -> F, D2: Fn(F) -> F
+    {
+        if other.inputs != self.inputs {
+            return Err(ShapeError { expected: self.inputs, actual: other.inputs });
+        }
+        if other.biases.len() != self.biases.len() {
+            return Err(ShapeError { expected: self.biases.len(), actual: other.biases.len() });
+        }
+        let keep = F::one() - weight;
+        for (w, &o) in self.coeffs.iter_mut().zip(&other.coeffs) {
+            *w = keep * *w + weight * o;
+        }
+        for (b, &o) in self.biases.iter_mut().zip(&other.biases) {
+            *b = keep * *b + weight * o;
+        }
+        Ok(())
+    }
+
+    /// A copy of the weights and biases of this layer, as a `(coeffs, biases)`
+    /// pair that can later be restored using `load_weights`.
+    pub fn save_weights(&self) -> (Vec<F>, Vec<F>) {
+        (self.coeffs.clone(), self.biases.clone())
+    }
+
+    /// Replaces the weights and biases of this layer.
+    ///
+    /// Fails without changing anything if `coeffs` does not contain exactly
+    /// `inputs * outputs` values or `biases` exactly `outputs` values.
+    pub fn load_weights(&mut self, coeffs: &[F], biases: &[F]) -> Result<(), ShapeError> {
+        if coeffs.len() != self.coeffs.len() {
+            return Err(ShapeError { expected: self.coeffs.len(), actual: coeffs.len() });
+        }
+        if biases.len() != self.biases.len() {
+            return Err(ShapeError { expected: self.biases.len(), actual: biases.len() });
+        }
+        self.coeffs.copy_from_slice(coeffs);
+        self.biases.copy_from_slice(biases);
+        Ok(())
+    }
+
+    /// The bias of output `j`, which is 0 for a layer without biases.
+    fn bias(&self, j: usize) -> F {
+        if self.bias { self.biases[j] } else { zero() }
+    }
+
+    /// Computes the gradients of the error with respect to the weights, the
+    /// biases and the input of the layer, given the gradient `error` of the
+    /// error with respect to its output.
+    ///
+    /// This does not change the layer: the gradient with respect to the input
+    /// is computed with the current weights, before any update is applied.
+    /// All loops go through the weight matrix one row at a time.
+    fn gradients(&self, input: &[F], error: &[F]) -> (Vec<F>, Vec<F>, Vec<F>) {
+        let n = self.inputs;
+        let input = (0..n).map(|i| input.get(i).cloned().unwrap_or(zero())).collect::<Vec<F>>();
+
+        // gradient of the error with respect to the pre-activation values,
+        // which is also the gradient with respect to the biases
+        let deltas = self.compute_linear(&input).into_iter().enumerate().map(|(j, acc)| {
+            (self.activation.derivative)(acc) * error.get(j).cloned().unwrap_or(zero())
+        }).collect::<Vec<_>>();
+
+        let mut coeffs_grad = vec![zero(); self.coeffs.len()];
+        let mut returned = vec![zero(); n];
+        for (j, &delta) in deltas.iter().enumerate() {
+            let row = &self.coeffs[j*n..(j+1)*n];
+            let grad_row = &mut coeffs_grad[j*n..(j+1)*n];
+            for i in 0..n {
+                grad_row[i] = input[i] * delta;
+                returned[i] = returned[i] + row[i] * delta;
+            }
+        }
+        (coeffs_grad, deltas, returned)
+    }
+
+    /// Applies the average of `coeffs_grad` and `biases_grad`, the sums of the
+    /// gradients of `samples` samples, unless this layer is frozen.
+    fn apply_average<R>(&mut self, rule: &R, coeffs_grad: &mut [F], biases_grad: &mut [F], samples: usize)
+        where R: GradientRule<Float = F>
+    {
+        if self.frozen { return; }
+        let n = F::from(samples).unwrap();
+        for g in coeffs_grad.iter_mut().chain(biases_grad.iter_mut()) {
+            *g = *g / n;
+        }
+        rule.apply(&mut self.coeffs_state, &mut self.coeffs, coeffs_grad, true);
+        if self.bias {
+            rule.apply(&mut self.biases_state, &mut self.biases, biases_grad, false);
         }
     }
 }
 
-impl<F, V, D> Compute<F> for FeedforwardLayer<F, V, D>
+/// A scheme to initialize the weights of a layer.
+pub enum WeightInit<F> {
+    /// All weights and biases set to 0, like `FeedforwardLayer::new`.
+    Zeros,
+    /// All weights and biases generated by the closure, like
+    /// `FeedforwardLayer::new_from`.
+    Generator(Box<dyn FnMut() -> F>),
+    /// The Xavier uniform initialization, from a closure generating values
+    /// uniformly in `[0, 1)`, like `FeedforwardLayer::xavier_uniform`.
+    XavierUniform(Box<dyn FnMut() -> F>),
+    /// The He normal initialization, from a closure generating values from a
+    /// standard normal distribution, like `FeedforwardLayer::he_normal`.
+    HeNormal(Box<dyn FnMut() -> F>)
+}
+
+/// A builder for `FeedforwardLayer`, created by `FeedforwardLayer::builder()`.
+///
+/// The numbers of inputs and outputs must be provided, the other settings
+/// default to those of `FeedforwardLayer::new`: an identity activation, all
+/// weights set to 0, with biases and not frozen.
+///
+/// ```
+/// # use silinapse::{Compute, FeedforwardLayer};
+/// # use silinapse::activations::sigmoid;
+/// let layer = FeedforwardLayer::builder().inputs(3)
+///                                        .outputs(2)
+///                                        .activation(sigmoid())
+///                                        .build()
+///                                        .unwrap();
+/// assert_eq!(layer.compute(&[1.0f32, 2.0, 3.0]), vec![0.5, 0.5]);
+/// ```
+pub struct FeedforwardLayerBuilder<F: Float, V = fn(F) -> F, D = fn(F) -> F>
+    where V: Fn(F) -> F,
+          D: Fn(F) -> F
+{
+    inputs: Option<usize>,
+    outputs: Option<usize>,
+    activation: ActivationFunction<F, V, D>,
+    init: WeightInit<F>,
+    bias: bool,
+    frozen: bool
+}
+
+impl<F: Float> FeedforwardLayerBuilder<F> {
+    /// Creates a new builder with the default settings.
+    pub fn new() -> FeedforwardLayerBuilder<F> {
+        FeedforwardLayerBuilder {
+            inputs: None,
+            outputs: None,
+            activation: identity(),
+            init: WeightInit::Zeros,
+            bias: true,
+            frozen: false
+        }
+    }
+}
+
+impl<F: Float> Default for FeedforwardLayerBuilder<F> {
+    fn default() -> FeedforwardLayerBuilder<F> {
+        FeedforwardLayerBuilder::new()
+    }
+}
+
+impl<F, V, D> FeedforwardLayerBuilder<F, V, D>
     where F: Float,
           V: Fn(F) -> F,
           D: Fn(F) -> F
 {
-    fn compute(&self, input: &[F]) -> Vec<F> {
-        let mut out = self.biases.clone();
-        for j in 0..self.biases.len() {
-            for i in 0..min(self.inputs, input.len()) {
-                out[j] = out[j] + self.coeffs[j*self.inputs + i] * input[i]
+    /// Sets the number of inputs of the layer.
+    pub fn inputs(mut self, inputs: usize) -> FeedforwardLayerBuilder<F, V, D> {
+        self.inputs = Some(inputs);
+        self
+    }
+
+    /// Sets the number of outputs of the layer.
+    pub fn outputs(mut self, outputs: usize) -> FeedforwardLayerBuilder<F, V, D> {
+        self.outputs = Some(outputs);
+        self
+    }
+
+    /// Sets the activation function of the layer.
+    pub fn activation<V2, D2>(self, activation: ActivationFunction<F, V2, D2>) -> FeedforwardLayerBuilder<F, V2, D2>
+        where V2: Fn(F) -> F,
+              D2: Fn(F) -> F
+    {
+        FeedforwardLayerBuilder {
+            inputs: self.inputs,
+            outputs: self.outputs,
+            activation,
+            init: self.init,
+            bias: self.bias,
+            frozen: self.frozen
+        }
+    }
+
+    /// Sets the initialization scheme of the weights of the layer.
+    pub fn init(mut self, init: WeightInit<F>) -> FeedforwardLayerBuilder<F, V, D> {
+        self.init = init;
+        self
+    }
+
+    /// Sets whether the layer has biases, see `FeedforwardLayer::new_no_bias()`.
+    pub fn with_bias(mut self, bias: bool) -> FeedforwardLayerBuilder<F, V, D> {
+        self.bias = bias;
+        self
+    }
+
+    /// Sets whether the layer is frozen, see `FeedforwardLayer::set_frozen()`.
+    pub fn frozen(mut self, frozen: bool) -> FeedforwardLayerBuilder<F, V, D> {
+        self.frozen = frozen;
+        self
+    }
+
+    /// Builds the layer, failing if its number of inputs or outputs was not
+    /// provided.
+    pub fn build(self) -> Result<FeedforwardLayer<F, V, D>, BuildError> {
+        let inputs = self.inputs.ok_or(BuildError { missing: "inputs" })?;
+        let outputs = self.outputs.ok_or(BuildError { missing: "outputs" })?;
+        let mut layer = match self.init {
+            WeightInit::Zeros => FeedforwardLayer::new(inputs, outputs, self.activation),
+            WeightInit::Generator(g) => FeedforwardLayer::new_from(inputs, outputs, self.activation, g),
+            WeightInit::XavierUniform(g) => FeedforwardLayer::xavier_uniform(inputs, outputs, self.activation, g),
+            WeightInit::HeNormal(g) => FeedforwardLayer::he_normal(inputs, outputs, self.activation, g)
+        };
+        if !self.bias {
+            layer.bias = false;
+            for b in &mut layer.biases {
+                *b = zero();
             }
         }
-        
-        for o in &mut out {
-            *o = (self.activation.value)(*o);
+        layer.frozen = self.frozen;
+        Ok(layer)
+    }
+}
+
+/// Gradients summed over several calls to `train_batch()`, in accumulation mode.
+struct Accumulated<F> {
+    coeffs: Vec<F>,
+    biases: Vec<F>,
+    samples: usize
+}
+
+impl<F: Float> FeedforwardLayer<F, fn(F) -> F, fn(F) -> F> {
+    /// A builder to create a layer with more settings than the constructors
+    /// allow, see `FeedforwardLayerBuilder`.
+    pub fn builder() -> FeedforwardLayerBuilder<F> {
+        FeedforwardLayerBuilder::new()
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<F, V, D> FeedforwardLayer<F, V, D>
+    where F: Float + Send + Sync,
+          V: Fn(F) -> F + Sync,
+          D: Fn(F) -> F
+{
+    /// Process input into output like `compute()`, spreading the outputs over
+    /// the threads of the rayon pool.
+    ///
+    /// Layers with fewer outputs than `parallel::MIN_PARALLEL_OUTPUTS` are
+    /// computed serially. This is only available with the `rayon` feature,
+    /// with which `compute()` uses it.
+    pub fn par_compute(&self, input: &[F]) -> Vec<F> {
+        let outputs = self.output_size();
+        if outputs < ::parallel::MIN_PARALLEL_OUTPUTS {
+            return self.compute_serial(input);
         }
+        // only borrow what is needed, as the derivative may not be shareable
+        let (inputs, coeffs, value) = (self.inputs, &self.coeffs[..], &self.activation.value);
+        let biases = if self.bias { &self.biases[..] } else { &[] };
+        (0..outputs).into_par_iter().map(|j| value(neuron(inputs, coeffs, biases, j, input))).collect()
+    }
+}
 
-        out
+/// The weighted sum of `input` computed by the output `j` of a layer, from
+/// its borrowed weights, in the same order as `FeedforwardLayer::compute()`.
+#[cfg(feature = "rayon")]
+fn neuron<F: Float>(inputs: usize, coeffs: &[F], biases: &[F], j: usize, input: &[F]) -> F {
+    let mut acc = biases.get(j).cloned().unwrap_or(zero());
+    for (i, &x) in input.iter().take(inputs).enumerate() {
+        acc = acc + coeffs[j*inputs + i] * x
+    }
+    acc
+}
+
+/// The parameters of a `FeedforwardLayer`, everything but its activation
+/// function.
+///
+/// With the `serde` feature, this struct can be deserialized from a serialized
+/// layer, which can then be rebuilt with `FeedforwardLayer::from_weights`, by
+/// providing its activation function again.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FeedforwardParameters<F> {
+    /// The number of inputs of the layer.
+    pub inputs: usize,
+    /// The weights of the layer, in row-major order.
+    pub coeffs: Vec<F>,
+    /// The biases of the layer, one per output.
+    pub biases: Vec<F>,
+    /// Whether the layer uses its biases, see `FeedforwardLayer::new_no_bias`.
+    ///
+    /// Parameters serialized before it was recorded are read as having
+    /// biases.
+    #[cfg_attr(feature = "serde", serde(default = "default_bias"))]
+    pub bias: bool,
+    /// The kind of the activation function of the layer, if it was recorded.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub activation: Option<ActivationKind>
+}
+
+#[cfg(feature = "serde")]
+fn default_bias() -> bool {
+    true
+}
+
+impl<F: Float + Send + Sync + 'static> FeedforwardParameters<F> {
+    /// Rebuilds the layer these parameters come from, with its recorded
+    /// activation function.
+    ///
+    /// Returns `None` if no activation kind is recorded, or if there is not
+    /// exactly `inputs` weights per bias.
+    pub fn into_layer(self) -> Option<FeedforwardLayer<F, BoxedFn<F>, BoxedFn<F>>> {
+        let kind = self.activation?;
+        let activation: BoxedActivation<F> = kind.build();
+        let mut layer = FeedforwardLayer::from_weights(self.inputs, self.coeffs, self.biases, activation).ok()?;
+        layer.kind = Some(kind);
+        layer.bias = self.bias;
+        Some(layer)
+    }
+}
+
+impl<F, V, D> FeedforwardLayer<F, V, D>
+    where F: Float,
+          V: Fn(F) -> F + Clone,
+          D: Fn(F) -> F + Clone
+{
+    /// A copy of this layer with its weights and biases stored as 8-bit
+    /// integers, sharing a single scale for the whole layer.
+    ///
+    /// The scale is chosen so that the parameter of largest magnitude maps to
+    /// `127`, so each parameter is rounded to the nearest multiple of it.
+    pub fn quantize(&self) -> QuantizedLayer<F, V, D> {
+        let biases = if self.bias { &self.biases[..] } else { &[] };
+        let scale = quantization_scale(self.coeffs.iter().chain(biases));
+        let quantize = |w: F| quantize_value(w, scale);
+        QuantizedLayer {
+            inputs: self.inputs,
+            scale,
+            coeffs: self.coeffs.iter().map(|&w| quantize(w)).collect(),
+            biases: self.biases.iter().map(|&b| if self.bias { quantize(b) } else { 0 }).collect(),
+            bias: self.bias,
+            activation: self.activation.clone(),
+            kind: self.kind
+        }
+    }
+}
+
+/// The scale mapping the value of largest magnitude of `values` to `127`, or
+/// `1` if they are all 0.
+fn quantization_scale<'a, F: Float + 'a, I: Iterator<Item = &'a F>>(values: I) -> F {
+    let max = values.fold(zero::<F>(), |m, &w| m.max(w.abs()));
+    if max > zero() { max / F::from(127).unwrap() } else { F::one() }
+}
+
+/// `x` rounded to the nearest multiple of `scale`, in units of `scale`.
+fn quantize_value<F: Float>(x: F, scale: F) -> i8 {
+    (x / scale).round().max(F::from(-127).unwrap()).min(F::from(127).unwrap()).to_i8().unwrap_or(0)
+}
+
+/// A feedforward layer whose weights and biases are stored as 8-bit integers
+/// along with a scale, as returned by `FeedforwardLayer::quantize()`.
+///
+/// Its output is computed with integer arithmetic: the input is quantized to
+/// 8-bit integers with its own scale, the products with the integer weights
+/// are accumulated as 32-bit integers, and the product of both scales is
+/// applied once before the activation function. It cannot be trained, but can
+/// be turned back into an approximation of the original layer using
+/// `dequantize()`.
+pub struct QuantizedLayer<F: Float, V: Fn(F) -> F, D: Fn(F) -> F> {
+    inputs: usize,
+    scale: F,
+    coeffs: Vec<i8>,
+    biases: Vec<i8>,
+    bias: bool,
+    activation: ActivationFunction<F, V, D>,
+    kind: Option<ActivationKind>
+}
+
+impl<F, V, D> QuantizedLayer<F, V, D>
+    where F: Float,
+          V: Fn(F) -> F + Clone,
+          D: Fn(F) -> F + Clone
+{
+    /// The value of one unit of the quantized parameters.
+    pub fn scale(&self) -> F {
+        self.scale
+    }
+
+    /// The quantized weights of this layer, in the same order as
+    /// `FeedforwardLayer::weights()`.
+    pub fn weights(&self) -> &[i8] {
+        &self.coeffs
+    }
+
+    /// The quantized biases of this layer, all 0 if it has none.
+    pub fn biases(&self) -> &[i8] {
+        &self.biases
+    }
+
+    /// Rebuilds a `FeedforwardLayer` from the quantized parameters.
+    ///
+    /// Each of its parameters differs from the one of the original layer by
+    /// at most half of `scale()`. The memory of the training rules is not
+    /// kept.
+    pub fn dequantize(&self) -> FeedforwardLayer<F, V, D> {
+        let dequantize = |q: i8| F::from(q).unwrap() * self.scale;
+        let coeffs = self.coeffs.iter().map(|&q| dequantize(q)).collect();
+        let biases = self.biases.iter().map(|&q| dequantize(q)).collect();
+        let mut layer = FeedforwardLayer::with_parameters(self.inputs, coeffs, biases, self.activation.clone());
+        layer.kind = self.kind;
+        layer.bias = self.bias;
+        layer
+    }
+}
+
+impl<F, V, D> Compute<F> for QuantizedLayer<F, V, D>
+    where F: Float,
+          V: Fn(F) -> F,
+          D: Fn(F) -> F
+{
+    fn compute(&self, input: &[F]) -> Vec<F> {
+        let input_scale = quantization_scale(input.iter());
+        let input = input.iter().map(|&x| i32::from(quantize_value(x, input_scale))).collect::<Vec<_>>();
+        (0..self.biases.len()).map(|j| {
+            let row = &self.coeffs[j*self.inputs..(j+1)*self.inputs];
+            let acc = row.iter().zip(&input).fold(0i32, |acc, (&w, &x)| acc + i32::from(w) * x);
+            // the bias is in units of the scale of the weights only
+            let acc = F::from(acc).unwrap() * input_scale + F::from(self.biases[j]).unwrap();
+            (self.activation.value)(acc * self.scale)
+        }).collect()
+    }
+
+    fn input_size(&self) -> usize {
+        self.inputs
+    }
+
+    fn output_size(&self) -> usize {
+        self.biases.len()
+    }
+}
+
+/// A layer is serialized as its `FeedforwardParameters`, the activation
+/// function being left out.
+#[cfg(feature = "serde")]
+impl<F, V, D> Serialize for FeedforwardLayer<F, V, D>
+    where F: Float + Serialize,
+          V: Fn(F) -> F,
+          D: Fn(F) -> F
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("FeedforwardParameters", 5)?;
+        state.serialize_field("inputs", &self.inputs)?;
+        state.serialize_field("coeffs", &self.coeffs)?;
+        state.serialize_field("biases", &self.biases)?;
+        state.serialize_field("bias", &self.bias)?;
+        state.serialize_field("activation", &self.kind)?;
+        state.end()
+    }
+}
+
+/// Parameter vectors longer than this are summarized in the debug output of
+/// a layer, rather than printed in full.
+const DEBUG_MAX_PRINTED: usize = 8;
+
+/// Debug output of a set of parameters: the values themselves if they are few,
+/// or some statistics about them otherwise.
+struct ParamsSummary<'a, F: 'a>(&'a [F]);
+
+impl<'a, F: Float + fmt::Debug> fmt::Debug for ParamsSummary<'a, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.len() <= DEBUG_MAX_PRINTED {
+            return f.debug_list().entries(self.0.iter()).finish();
+        }
+        let min = self.0.iter().fold(F::infinity(), |acc, &x| acc.min(x));
+        let max = self.0.iter().fold(F::neg_infinity(), |acc, &x| acc.max(x));
+        let sum = self.0.iter().fold(zero::<F>(), |acc, &x| acc + x);
+        f.debug_struct("Summary")
+         .field("count", &self.0.len())
+         .field("min", &min)
+         .field("max", &max)
+         .field("mean", &(sum / F::from(self.0.len()).unwrap()))
+         .finish()
+    }
+}
+
+/// The activation function is left out, and large weight matrices and bias
+/// vectors are summarized by their count, minimum, maximum and mean.
+impl<F, V, D> fmt::Debug for FeedforwardLayer<F, V, D>
+    where F: Float + fmt::Debug,
+          V: Fn(F) -> F,
+          D: Fn(F) -> F
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FeedforwardLayer")
+         .field("inputs", &self.inputs)
+         .field("outputs", &self.biases.len())
+         .field("coeffs", &ParamsSummary(&self.coeffs))
+         .field("biases", &ParamsSummary(&self.biases))
+         .finish()
+    }
+}
+
+#[cfg(not(feature = "rayon"))]
+impl<F, V, D> Compute<F> for FeedforwardLayer<F, V, D>
+    where F: Float,
+          V: Fn(F) -> F,
+          D: Fn(F) -> F
+{
+    fn compute(&self, input: &[F]) -> Vec<F> {
+        self.compute_serial(input)
+    }
+
+    fn compute_into(&self, input: &[F], out: &mut [F]) {
+        self.activate_into(input, out);
+    }
+
+    fn compute_batch(&self, inputs: &[Vec<F>]) -> Vec<Vec<F>> {
+        self.compute_batch_serial(inputs)
+    }
+
+    fn input_size(&self) -> usize {
+        self.inputs
+    }
+
+    fn output_size(&self) -> usize {
+        self.biases.len()
+    }
+}
+
+/// With the `rayon` feature, large layers and large batches are spread over
+/// the threads of the rayon pool, see the `parallel` module. Each output is
+/// computed by the same operations as without it, so the results do not
+/// change.
+#[cfg(feature = "rayon")]
+impl<F, V, D> Compute<F> for FeedforwardLayer<F, V, D>
+    where F: Float + Send + Sync,
+          V: Fn(F) -> F + Sync,
+          D: Fn(F) -> F
+{
+    fn compute(&self, input: &[F]) -> Vec<F> {
+        self.par_compute(input)
+    }
+
+    fn compute_into(&self, input: &[F], out: &mut [F]) {
+        self.activate_into(input, out);
+    }
+
+    fn compute_batch(&self, inputs: &[Vec<F>]) -> Vec<Vec<F>> {
+        if inputs.len() < ::parallel::MIN_PARALLEL_BATCH {
+            return self.compute_batch_serial(inputs);
+        }
+        let (n, coeffs, value) = (self.inputs, &self.coeffs[..], &self.activation.value);
+        let biases = if self.bias { &self.biases[..] } else { &[] };
+        let outputs = self.biases.len();
+        inputs.par_iter().map(|input| {
+            (0..outputs).map(|j| value(neuron(n, coeffs, biases, j, input))).collect()
+        }).collect()
     }
 
     fn input_size(&self) -> usize {
@@ -107,86 +1051,151 @@ impl<F, V, D> SupervisedTrain<F, PerceptronRule<F>> for FeedforwardLayer<F, V, D
                         input: &[F],
                         target: &[F])
     {
-        let out = self.compute(input);
-        for j in 0..self.biases.len() {
-            let diff = out[j] - target.get(j).map(|v| *v).unwrap_or(zero());
-            for i in 0..min(self.inputs, input.len()) {
+        if self.frozen { return; }
+        let out = self.compute_serial(input);
+        for (j, &o) in out.iter().enumerate() {
+            let diff = o - target.get(j).cloned().unwrap_or(zero());
+            for (i, &x) in input.iter().take(self.inputs).enumerate() {
                 self.coeffs[i + j*self.inputs] =
-                    self.coeffs[i + j*self.inputs] - rule.rate * diff * input[i];
+                    self.coeffs[i + j*self.inputs] - rule.rate * diff * x;
+            }
+            if self.bias {
+                self.biases[j] = self.biases[j] - rule.rate * diff;
             }
-            self.biases[j] = self.biases[j] - rule.rate * diff;
         }
     }
 }
 
-impl<F, V, D> BackpropTrain<F, GradientDescent<F>> for FeedforwardLayer<F, V, D>
+impl<F, V, D, R> BackpropTrain<F, R> for FeedforwardLayer<F, V, D>
     where F: Float,
           V: Fn(F) -> F,
-          D: Fn(F) -> F
+          D: Fn(F) -> F,
+          R: GradientRule<Float = F>
 {
-    fn backprop_train(&mut self,
-                      rule: &GradientDescent<F>,
-                      input: &[F],
-                      target: &[F])
-        -> Vec<F>
-    {
-        // we need to compute the intermediate states
-        let mut out = self.biases.clone();
-        for j in 0..self.biases.len() {
-            for i in 0..min(self.inputs, input.len()) {
-                out[j] = out[j] + self.coeffs[j*self.inputs + i] * input[i]
-            }
+    fn backprop_train(&mut self, rule: &R, input: &[F], error: &[F]) -> Vec<F> {
+        let (coeffs_grad, biases_grad, returned) = self.gradients(input, error);
+        if self.frozen { return returned; }
+        rule.apply(&mut self.coeffs_state, &mut self.coeffs, &coeffs_grad, true);
+        if self.bias {
+            rule.apply(&mut self.biases_state, &mut self.biases, &biases_grad, false);
         }
+        returned
+    }
+}
 
-        let deltas = out.iter()
-                            .map(|x| { (self.activation.derivative)(*x) })
-                            .collect::<Vec<_>>();
-        for o in &mut out {
-            *o = (self.activation.value)(*o);
+/// The supervised training uses the mean squared error: the layer is trained
+/// by backpropagation with the error signal `2*(output - target)/n`.
+impl<F, V, D, R> SupervisedTrain<F, R> for FeedforwardLayer<F, V, D>
+    where F: Float,
+          V: Fn(F) -> F,
+          D: Fn(F) -> F,
+          R: GradientRule<Float = F>
+{
+    fn supervised_train(&mut self, rule: &R, input: &[F], target: &[F]) {
+        let error = output_error(&self.compute_serial(input), target);
+        self.backprop_train(rule, input, &error);
+    }
+}
+
+/// The batch training uses the mean squared error, like the supervised
+/// training.
+impl<F, V, D, R> BatchSupervisedTrain<F, R> for FeedforwardLayer<F, V, D>
+    where F: Float,
+          V: Fn(F) -> F,
+          D: Fn(F) -> F,
+          R: GradientRule<Float = F>
+{
+    fn train_batch(&mut self, rule: &R, inputs: &[Vec<F>], targets: &[Vec<F>]) {
+        if inputs.is_empty() || self.frozen { return; }
+        let mut coeffs_grad = vec![zero(); self.coeffs.len()];
+        let mut biases_grad = vec![zero(); self.biases.len()];
+        for (k, input) in inputs.iter().enumerate() {
+            let target = targets.get(k).map(|t| &t[..]).unwrap_or(&[]);
+            let error = output_error(&self.compute_serial(input), target);
+            let (cg, bg, _) = self.gradients(input, &error);
+            for (acc, g) in coeffs_grad.iter_mut().zip(cg) { *acc = *acc + g; }
+            for (acc, g) in biases_grad.iter_mut().zip(bg) { *acc = *acc + g; }
+        }
+        if let Some(ref mut acc) = self.accumulated {
+            for (a, g) in acc.coeffs.iter_mut().zip(coeffs_grad) { *a = *a + g; }
+            for (a, g) in acc.biases.iter_mut().zip(biases_grad) { *a = *a + g; }
+            acc.samples += inputs.len();
+            return;
         }
+        self.apply_average(rule, &mut coeffs_grad, &mut biases_grad, inputs.len());
+    }
+}
 
-        let mut returned = input.to_owned();
-        for j in 0..self.biases.len() {
-            for i in 0..min(self.inputs, input.len()) {
-                returned[i] = returned[i] - self.coeffs[i + j*self.inputs]*deltas[j];
-                self.coeffs[i + j*self.inputs] =
-                    self.coeffs[i + j*self.inputs]
-                    - rule.rate * input.get(i).map(|x| *x).unwrap_or(zero())
-                                * deltas[j]
-                                * ( out[j] - target.get(j).map(|x| *x).unwrap_or(zero()) )
+impl<F, V, D> Weights<F> for FeedforwardLayer<F, V, D>
+    where F: Float,
+          V: Fn(F) -> F,
+          D: Fn(F) -> F
+{
+    fn weights(&self) -> &[F] {
+        &self.coeffs
+    }
 
-            }
-            self.biases[j] = self.biases[j]
-                    - rule.rate * deltas[j]
-                                * ( out[j] - target.get(j).map(|x| *x).unwrap_or(zero()) );
-        }
-        returned
+    fn weights_mut(&mut self) -> &mut [F] {
+        &mut self.coeffs
     }
 }
 
-impl<F, V, D> SupervisedTrain<F, GradientDescent<F>> for FeedforwardLayer<F, V, D>
+impl<F, V, D> ModeAware for FeedforwardLayer<F, V, D>
+    where F: Float,
+          V: Fn(F) -> F,
+          D: Fn(F) -> F
+{}
+
+impl<F, V, D> Trainable for FeedforwardLayer<F, V, D>
     where F: Float,
           V: Fn(F) -> F,
           D: Fn(F) -> F
 {
-    fn supervised_train(&mut self,
-                        rule: &GradientDescent<F>,
-                        input: &[F],
-                        target: &[F])
-    {
-        self.backprop_train(rule, input, target);
+    fn num_parameters(&self) -> usize {
+        self.coeffs.len() + if self.bias { self.biases.len() } else { 0 }
+    }
+
+    fn has_nan(&self) -> bool {
+        any_non_finite(&self.coeffs) || any_non_finite(&self.biases)
+    }
+}
+
+impl<F, V, D> Parametrized<F> for FeedforwardLayer<F, V, D>
+    where F: Float,
+          V: Fn(F) -> F,
+          D: Fn(F) -> F
+{
+    /// The weights, in the order of `weights()`, then the biases if the layer
+    /// has some.
+    fn parameters_iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a F> + 'a> {
+        let biases = if self.bias { &self.biases[..] } else { &[] };
+        Box::new(self.coeffs.iter().chain(biases.iter()))
+    }
+
+    fn parameters_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = &'a mut F> + 'a> {
+        let biases = if self.bias { &mut self.biases[..] } else { &mut [] };
+        Box::new(self.coeffs.iter_mut().chain(biases.iter_mut()))
     }
 }
 
 #[cfg(test)]
 mod tests {
 
-    use {Compute, SupervisedTrain};
-    use activations::{identity, step, sigmoid};
-    use training::{PerceptronRule, GradientDescent};
+    use {Compute, SupervisedTrain, BackpropTrain, BatchSupervisedTrain, Trainable, Parametrized, ShapeError, BuildError};
+    use activations::{identity, step, sigmoid, relu};
+    use diagnostics::gradient_check;
+    use loss::{Loss, MeanSquaredError};
+    use testing::random;
+    use training::{PerceptronRule, GradientDescent, Adam};
     use util::Chain;
 
-    use super::FeedforwardLayer;
+    #[cfg(feature = "std")]
+    use rand::{Rng, SeedableRng, XorShiftRng};
+    #[cfg(feature = "std")]
+    use rand::distributions::normal::StandardNormal;
+
+    use super::{FeedforwardLayer, FeedforwardParameters, WeightInit};
+    use QuantizedLayer;
 
     #[test]
     fn basics() {
@@ -217,6 +1226,22 @@ mod tests {
         assert_eq!(layer.compute(&[1.0, -1.0, 1.0, -1.0]), [1.0f32, 1.0]);
     }
 
+    #[test]
+    fn perceptron_and() {
+        let mut layer = FeedforwardLayer::new(2, 1, step());
+        let rule = PerceptronRule { rate: 0.1f64 };
+        let samples = [([0.0, 0.0], [0.0]), ([0.0, 1.0], [0.0]),
+                       ([1.0, 0.0], [0.0]), ([1.0, 1.0], [1.0])];
+        for _ in 0..20 {
+            for (input, target) in &samples {
+                layer.supervised_train(&rule, input, target);
+            }
+        }
+        for (input, target) in &samples {
+            assert_eq!(layer.compute(input), target.to_vec());
+        }
+    }
+
     #[test]
     fn supervised_train() {
         // a deterministic pseudo-random initialization.
@@ -226,7 +1251,7 @@ mod tests {
             move || { acc += 1; (1.0f32 + ((13*acc) % 12) as f32) / 13.0f32}
         };
         let mut layer = FeedforwardLayer::new_from(4, 2, sigmoid(), random);
-        let rule = GradientDescent { rate: 0.5f32 };
+        let rule = GradientDescent::new(0.5f32);
         for _ in 0..40 {
             layer.supervised_train(&rule, &[1.0,1.0,1.0,1.0], &[0.0, 0.0]);
             layer.supervised_train(&rule, &[1.0,-1.0,1.0,-1.0], &[1.0, 1.0]);
@@ -244,14 +1269,636 @@ mod tests {
             move || { acc += 1; (1.0f32 + ((13*acc) % 12) as f32) / 13.0f32}
         };
         let mut layer = Chain::new(FeedforwardLayer::new_from(4, 8, sigmoid(), &mut random), FeedforwardLayer::new_from(8, 2, sigmoid(), &mut random));
-        let rule = GradientDescent { rate: 0.5f32 };
+        let rule = GradientDescent::new(0.5f32);
         for _ in 0..200 {
             layer.supervised_train(&rule, &[1.0, 1.0,1.0, 1.0], &[1.0, 0.0]);
             layer.supervised_train(&rule, &[1.0,-1.0,1.0,-1.0], &[0.0, 1.0]);
         }
-        println!("{:?}", layer.compute(&[1.0, 1.0, 1.0, 1.0]));
         assert!({ let out = layer.compute(&[1.0, 1.0, 1.0, 1.0]); out[0] > 0.8 && out[1] < 0.2 });
-        println!("{:?}", layer.compute(&[1.0, -1.0, 1.0, -1.0]));
         assert!({ let out = layer.compute(&[1.0, -1.0, 1.0, -1.0]); out[0] < 0.2 && out[1] > 0.8 });
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn gradients() {
+        let mut layer = FeedforwardLayer::new_from(3, 2, sigmoid(), random());
+        assert!(gradient_check(&mut layer, &MeanSquaredError, &[0.3, -0.8, 0.5], &[1.0, 0.0], 1e-6) < 1e-4);
+        let mut layer = FeedforwardLayer::new_from(4, 3, relu(), random());
+        assert!(gradient_check(&mut layer, &MeanSquaredError, &[0.3, -0.8, 0.5, 1.2], &[1.0, 0.0, 2.0], 1e-6) < 1e-4);
+    }
+
+    #[test]
+    fn supervised_train_minimizes_mse() {
+        let mut a = FeedforwardLayer::new_from(3, 2, sigmoid(), random());
+        let mut b = FeedforwardLayer::new_from(3, 2, sigmoid(), random());
+        let (input, target) = ([0.3f64, -0.8, 0.5], [1.0, 0.0]);
+        let rule = GradientDescent::new(0.5);
+        a.supervised_train(&rule, &input, &target);
+        let error = MeanSquaredError.grad(&b.compute(&input), &target);
+        b.backprop_train(&rule, &input, &error);
+        assert_eq!(a.save_weights(), b.save_weights());
+    }
+
+    #[test]
+    fn bias_only() {
+        // without any input, only the biases can learn the target
+        let mut layer = FeedforwardLayer::new(0, 2, identity());
+        let rule = GradientDescent::new(0.5f32);
+        for _ in 0..50 {
+            layer.supervised_train(&rule, &[], &[0.7, -1.5]);
+        }
+        let out = layer.compute(&[]);
+        assert!((out[0] - 0.7).abs() < 1e-4);
+        assert!((out[1] + 1.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn adam_faster_than_gradient_descent() {
+        // fit y = 10*x - 3 with a linear layer, the small inputs making the
+        // gradients on the weight small as well
+        let samples = [(-0.1f32, -4.0f32), (0.0, -3.0), (0.05, -2.5), (0.1, -2.0)];
+        let loss = |layer: &FeedforwardLayer<f32, _, _>| {
+            samples.iter().fold(0.0, |acc, &(x, y)| acc + (layer.compute(&[x])[0] - y).powi(2))
+        };
+        let mut plain = FeedforwardLayer::new(1, 1, identity());
+        let mut adam = FeedforwardLayer::new(1, 1, identity());
+        let gd_rule = GradientDescent::new(0.1f32);
+        let adam_rule = Adam::new(0.1f32);
+        for _ in 0..100 {
+            for &(x, y) in &samples {
+                plain.supervised_train(&gd_rule, &[x], &[y]);
+                adam.supervised_train(&adam_rule, &[x], &[y]);
+            }
+        }
+        assert!(loss(&adam) < loss(&plain));
+        assert!(loss(&adam) < 0.01);
+    }
+
+    #[test]
+    fn gradient_clipping() {
+        let mut layer = FeedforwardLayer::new_from(3, 2, identity(), || 0.5f32);
+        let rule = GradientDescent { rate: 0.1, clip: Some(1.0) };
+        layer.backprop_train(&rule, &[1.0, -2.0, 3.0], &[1e6, -1e6]);
+        for &w in layer.coeffs.iter().chain(layer.biases.iter()) {
+            assert!((w - 0.5).abs() <= 0.1 * 1.0 + 1e-6);
+        }
+    }
+
+    #[test]
+    fn parameters_round_trip() {
+        let layer = FeedforwardLayer::new_from(3, 2, sigmoid(), || 0.25f32);
+        let params = layer.parameters();
+        assert_eq!(params, FeedforwardParameters { inputs: 3, coeffs: vec![0.25; 6], biases: vec![0.25; 2],
+                                                   bias: true, activation: None });
+        let rebuilt = FeedforwardLayer::from_weights(params.inputs, params.coeffs, params.biases, sigmoid()).unwrap();
+        assert_eq!(rebuilt.compute(&[1.0, 2.0, 3.0]), layer.compute(&[1.0, 2.0, 3.0]));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_round_trip() {
+        use serde_json;
+
+        let layer = FeedforwardLayer::new_from(3, 2, sigmoid(), random());
+        let json = serde_json::to_string(&layer).unwrap();
+        let params: FeedforwardParameters<f64> = serde_json::from_str(&json).unwrap();
+        let rebuilt = FeedforwardLayer::from_weights(params.inputs, params.coeffs, params.biases, sigmoid()).unwrap();
+        let input = [0.3, -1.2, 0.7];
+        assert_eq!(rebuilt.compute(&input), layer.compute(&input));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_round_trip_with_kind() {
+        use serde_json;
+        use activations::ActivationKind;
+
+        let mut layer = FeedforwardLayer::new_from(2, 2, sigmoid(), || 0.5f64);
+        let json = serde_json::to_string(&layer).unwrap();
+        let params: FeedforwardParameters<f64> = serde_json::from_str(&json).unwrap();
+        assert!(params.into_layer().is_none());
+
+        layer.set_activation_kind(Some(ActivationKind::Sigmoid));
+        let json = serde_json::to_string(&layer).unwrap();
+        let params: FeedforwardParameters<f64> = serde_json::from_str(&json).unwrap();
+        let rebuilt = params.into_layer().unwrap();
+        assert_eq!(rebuilt.activation_kind(), Some(ActivationKind::Sigmoid));
+        assert_eq!(rebuilt.compute(&[0.3, -1.2]), layer.compute(&[0.3, -1.2]));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_round_trip_no_bias() {
+        use serde_json;
+        use activations::ActivationKind;
+
+        let mut layer = FeedforwardLayer::new_no_bias(2, 2, sigmoid());
+        layer.biases_mut()[0] = 1.0f64;
+        layer.set_activation_kind(Some(ActivationKind::Sigmoid));
+        let json = serde_json::to_string(&layer).unwrap();
+        let params: FeedforwardParameters<f64> = serde_json::from_str(&json).unwrap();
+        assert!(!params.bias);
+        let rebuilt = params.into_layer().unwrap();
+        assert!(!rebuilt.has_bias());
+        assert_eq!(rebuilt.compute(&[0.3, -1.2]), vec![0.5, 0.5]);
+
+        // parameters saved without the flag have biases
+        let json = r#"{"inputs":1,"coeffs":[1.0],"biases":[2.0],"activation":"Identity"}"#;
+        let params: FeedforwardParameters<f64> = serde_json::from_str(json).unwrap();
+        assert!(params.bias);
+        assert_eq!(params.into_layer().unwrap().compute(&[1.0]), vec![3.0]);
+    }
+
+    #[test]
+    fn save_and_load_weights() {
+        let mut layer = FeedforwardLayer::new_from(2, 2, identity(), || 1.0f32);
+        let (coeffs, biases) = layer.save_weights();
+        assert_eq!(coeffs, [1.0f32; 4]);
+        assert_eq!(biases, [1.0f32; 2]);
+        layer.supervised_train(&GradientDescent::new(0.1), &[1.0, 1.0], &[0.0, 0.0]);
+        assert!(layer.compute(&[1.0, 1.0]) != [3.0, 3.0]);
+        assert_eq!(layer.load_weights(&coeffs, &biases), Ok(()));
+        assert_eq!(layer.compute(&[1.0, 1.0]), [3.0, 3.0]);
+    }
+
+    #[test]
+    fn load_weights_mismatch() {
+        let mut layer = FeedforwardLayer::new(2, 2, identity());
+        assert_eq!(layer.load_weights(&[1.0f32; 3], &[1.0; 2]),
+                   Err(ShapeError { expected: 4, actual: 3 }));
+        assert_eq!(layer.load_weights(&[1.0f32; 4], &[1.0; 3]),
+                   Err(ShapeError { expected: 2, actual: 3 }));
+        // nothing was changed
+        assert_eq!(layer.save_weights(), (vec![0.0f32; 4], vec![0.0; 2]));
+    }
+
+    #[test]
+    fn from_weights() {
+        // 3 inputs, 2 outputs
+        let layer = FeedforwardLayer::from_weights(3,
+                                                   vec![1.0f32, 2.0, 3.0,
+                                                        -1.0, 0.5, 0.0],
+                                                   vec![0.5, -0.5],
+                                                   identity()).unwrap();
+        assert_eq!(layer.input_size(), 3);
+        assert_eq!(layer.output_size(), 2);
+        // [1 2 3; -1 0.5 0] * [1 2 -1] + [0.5 -0.5]
+        assert_eq!(layer.compute(&[1.0, 2.0, -1.0]), [2.5f32, -0.5]);
+
+        assert_eq!(FeedforwardLayer::from_weights(3, vec![1.0f32; 5], vec![0.0; 2], identity()).err(),
+                   Some(ShapeError { expected: 6, actual: 5 }));
+    }
+
+    #[test]
+    fn accessors() {
+        let mut layer = FeedforwardLayer::new(3, 2, identity());
+        assert_eq!(layer.weights().len(), 6);
+        assert_eq!(layer.biases().len(), 2);
+        // weight from input 2 to output 1, at 1*3 + 2
+        layer.weights_mut()[5] = 2.0f32;
+        layer.biases_mut()[0] = 0.5;
+        assert_eq!(layer.compute(&[0.0, 0.0, 1.0]), [0.5f32, 2.0]);
+        assert_eq!(layer.compute(&[1.0, 1.0, 0.0]), [0.5f32, 0.0]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn xavier_uniform() {
+        let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+        let layer = FeedforwardLayer::xavier_uniform(30, 20, sigmoid(), || rng.gen::<f64>());
+        let limit = (6.0f64 / 50.0).sqrt();
+        assert!(layer.weights().iter().all(|w| w.abs() <= limit));
+        assert!(layer.biases().iter().all(|&b| b == 0.0));
+        // the samples should spread over the whole range
+        assert!(layer.weights().iter().any(|&w| w > 0.9 * limit));
+        assert!(layer.weights().iter().any(|&w| w < -0.9 * limit));
+        let mean = layer.weights().iter().fold(0.0, |acc, w| acc + w) / 600.0;
+        assert!(mean.abs() < 0.05 * limit);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn he_normal() {
+        let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+        let layer = FeedforwardLayer::he_normal(50, 200, relu(), || rng.gen::<StandardNormal>().0);
+        let n = layer.weights().len() as f64;
+        let mean = layer.weights().iter().fold(0.0, |acc, w| acc + w) / n;
+        let variance = layer.weights().iter().fold(0.0, |acc, w| acc + (w - mean).powi(2)) / n;
+        assert!(mean.abs() < 0.01);
+        assert!((variance - 2.0 / 50.0).abs() < 0.1 * 2.0 / 50.0);
+        assert!(layer.biases().iter().all(|&b| b == 0.0));
+    }
+
+    #[test]
+    fn compute_into() {
+        let layer = FeedforwardLayer::new_from(4, 3, sigmoid(), random());
+        let input = [0.5f32, -1.0, 2.0, 0.0];
+        let mut out = [0.0f32; 3];
+        layer.compute_into(&input, &mut out);
+        assert_eq!(layer.compute(&input), out);
+    }
+
+    #[cfg(feature = "unstable")]
+    #[bench]
+    fn bench_compute_into(b: &mut ::test::Bencher) {
+        let layer = FeedforwardLayer::new_from(100, 100, sigmoid(), || 0.01f32);
+        let input = vec![1.0f32; 100];
+        let mut out = vec![0.0f32; 100];
+        b.iter(|| layer.compute_into(&input, &mut out));
+    }
+
+    #[test]
+    fn compute_batch() {
+        let layer = FeedforwardLayer::new_from(3, 4, sigmoid(), random());
+        let inputs = vec![vec![0.5f32, -1.0, 2.0], vec![0.0, 0.0, 0.0],
+                          vec![1.0, 1.0], vec![-3.0, 0.25, 1.0, 7.0]];
+        let outputs = layer.compute_batch(&inputs);
+        assert_eq!(outputs.len(), inputs.len());
+        for (input, output) in inputs.iter().zip(outputs.iter()) {
+            assert_eq!(&layer.compute(input), output);
+        }
+    }
+
+    #[test]
+    fn train_batch() {
+        let mut layer = FeedforwardLayer::new_from(2, 2, sigmoid(), random::<f64>());
+        let inputs = vec![vec![1.0, -1.0], vec![0.5, 2.0], vec![-1.0, 0.0]];
+        let targets = vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![1.0, 1.0]];
+
+        // average of the individual gradients
+        let mut expected_coeffs = layer.coeffs.clone();
+        let mut expected_biases = layer.biases.clone();
+        for (input, target) in inputs.iter().zip(targets.iter()) {
+            let out = layer.compute(input);
+            let error = [out[0] - target[0], out[1] - target[1]];
+            let (cg, bg, _) = layer.gradients(input, &error);
+            for k in 0..4 { expected_coeffs[k] -= 0.5 * cg[k] / 3.0; }
+            for k in 0..2 { expected_biases[k] -= 0.5 * bg[k] / 3.0; }
+        }
+
+        layer.train_batch(&GradientDescent::new(0.5), &inputs, &targets);
+        for (w, e) in layer.coeffs.iter().zip(&expected_coeffs) { assert!((w - e).abs() < 1e-12); }
+        for (b, e) in layer.biases.iter().zip(&expected_biases) { assert!((b - e).abs() < 1e-12); }
+    }
+
+    #[test]
+    fn try_compute() {
+        let layer = FeedforwardLayer::from_weights(2, vec![1.0f32, 2.0], vec![0.5], identity()).unwrap();
+        assert_eq!(layer.try_compute(&[1.0, 1.0]), Ok(vec![3.5]));
+        assert_eq!(layer.try_compute(&[1.0]), Err(ShapeError { expected: 2, actual: 1 }));
+        assert_eq!(layer.try_compute(&[1.0, 1.0, 1.0]), Err(ShapeError { expected: 2, actual: 3 }));
+    }
+
+    #[test]
+    fn debug() {
+        let layer = FeedforwardLayer::from_weights(2, vec![1.0f32, 2.0], vec![0.5], identity()).unwrap();
+        assert_eq!(format!("{:?}", layer),
+                   "FeedforwardLayer { inputs: 2, outputs: 1, coeffs: [1.0, 2.0], biases: [0.5] }");
+        let layer = FeedforwardLayer::new_from(4, 3, identity(), || 1.0f32);
+        let debug = format!("{:?}", layer);
+        assert!(debug.contains("inputs: 4"));
+        assert!(debug.contains("outputs: 3"));
+        assert!(debug.contains("coeffs: Summary { count: 12, min: 1.0, max: 1.0, mean: 1.0 }"));
+    }
+
+    #[test]
+    fn num_parameters() {
+        let layer = FeedforwardLayer::new(4, 2, identity::<f32>());
+        assert_eq!(layer.num_parameters(), 4*2 + 2);
+    }
+
+    #[test]
+    fn backprop_reference() {
+        let mut layer = FeedforwardLayer::new_from(3, 3, sigmoid(), random::<f64>());
+        let input = [0.5f64, -1.0, 2.0];
+        let error = [1.0f64, -0.5, 0.25];
+        let rate = 0.1;
+
+        // straightforward implementation, updating a copy of the weights
+        let (w, b) = layer.save_weights();
+        let mut new_w = w.clone();
+        let mut new_b = b.clone();
+        let mut returned = [0.0; 3];
+        for j in 0..3 {
+            let mut z = b[j];
+            for i in 0..3 { z += w[j*3 + i] * input[i]; }
+            let s = 1.0 / (1.0 + (-z).exp());
+            let delta = error[j] * s * (1.0 - s);
+            for i in 0..3 {
+                returned[i] += w[j*3 + i] * delta;
+                new_w[j*3 + i] -= rate * delta * input[i];
+            }
+            new_b[j] -= rate * delta;
+        }
+
+        let result = layer.backprop_train(&GradientDescent::new(rate), &input, &error);
+        for i in 0..3 { assert!((result[i] - returned[i]).abs() < 1e-12); }
+        for (w, e) in layer.weights().iter().zip(&new_w) { assert!((w - e).abs() < 1e-12); }
+        for (b, e) in layer.biases().iter().zip(&new_b) { assert!((b - e).abs() < 1e-12); }
+    }
+
+    #[test]
+    fn has_nan() {
+        let mut layer = FeedforwardLayer::new_from(2, 2, identity(), || 1.0f32);
+        assert!(!layer.has_nan());
+        layer.weights_mut()[3] = f32::INFINITY;
+        assert!(layer.has_nan());
+        layer.weights_mut()[3] = 1.0;
+        layer.biases_mut()[0] = f32::NAN;
+        assert!(layer.has_nan());
+    }
+
+    #[test]
+    fn frozen() {
+        let mut layer = FeedforwardLayer::new_from(3, 2, sigmoid(), random());
+        let reference = FeedforwardLayer::from_weights(3, layer.weights().to_vec(),
+                                                       layer.biases().to_vec(), sigmoid()).unwrap();
+        let input = [0.5f64, -1.0, 2.0];
+        let error = [0.3f64, -0.2];
+        let rule = GradientDescent::new(0.5);
+        layer.set_frozen(true);
+        assert!(layer.is_frozen());
+
+        let returned = layer.backprop_train(&rule, &input, &error);
+        assert_eq!(layer.weights(), reference.weights());
+        assert_eq!(layer.biases(), reference.biases());
+        let (_, _, expected) = reference.gradients(&input, &error);
+        assert_eq!(returned, expected);
+
+        layer.supervised_train(&rule, &input, &[1.0, 0.0]);
+        layer.train_batch(&rule, &[input.to_vec()], &[vec![1.0, 0.0]]);
+        assert_eq!(layer.weights(), reference.weights());
+
+        layer.set_frozen(false);
+        layer.backprop_train(&rule, &input, &error);
+        assert!(layer.weights() != reference.weights());
+    }
+
+    #[test]
+    fn accumulate_gradients() {
+        let mut accumulating = FeedforwardLayer::new_from(3, 2, sigmoid(), random::<f64>());
+        let mut reference = FeedforwardLayer::new_from(3, 2, sigmoid(), random());
+        let inputs = vec![vec![1.0, 0.5, -1.0], vec![0.0, 2.0, 1.0], vec![-1.5, 1.0, 0.5]];
+        let targets = vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![0.5, 0.5]];
+        let rule = GradientDescent::new(0.5);
+
+        accumulating.set_accumulate(true);
+        assert!(accumulating.is_accumulating());
+        accumulating.train_batch(&rule, &inputs[..2], &targets[..2]);
+        accumulating.train_batch(&rule, &inputs[2..], &targets[2..]);
+        assert_eq!(accumulating.weights(), reference.weights());
+        accumulating.apply_accumulated(&rule);
+
+        reference.train_batch(&rule, &inputs, &targets);
+        for (a, b) in accumulating.weights().iter().zip(reference.weights()) {
+            assert!((a - b).abs() < 1e-12);
+        }
+        for (a, b) in accumulating.biases().iter().zip(reference.biases()) {
+            assert!((a - b).abs() < 1e-12);
+        }
+
+        // the buffer is cleared after each update
+        let weights = accumulating.weights().to_vec();
+        accumulating.apply_accumulated(&rule);
+        assert_eq!(accumulating.weights(), &weights[..]);
+    }
+
+    #[test]
+    fn builder() {
+        let built = FeedforwardLayer::builder().inputs(3).outputs(2).build().unwrap();
+        let reference = FeedforwardLayer::new(3, 2, identity());
+        assert_eq!(built.weights(), reference.weights());
+        assert_eq!(built.biases(), reference.biases());
+        assert_eq!(built.num_parameters(), reference.num_parameters());
+        assert!(!built.is_frozen());
+        assert_eq!(built.compute(&[1.0f64, 2.0, 3.0]), reference.compute(&[1.0, 2.0, 3.0]));
+
+        let built = FeedforwardLayer::builder().inputs(3)
+                                               .outputs(2)
+                                               .activation(sigmoid())
+                                               .init(WeightInit::Generator(Box::new(random::<f64>())))
+                                               .frozen(true)
+                                               .build()
+                                               .unwrap();
+        let reference = FeedforwardLayer::new_from(3, 2, sigmoid(), random());
+        assert_eq!(built.weights(), reference.weights());
+        assert_eq!(built.biases(), reference.biases());
+        assert_eq!(built.compute(&[1.0, 2.0, 3.0]), reference.compute(&[1.0, 2.0, 3.0]));
+        assert!(built.is_frozen());
+
+        let built = FeedforwardLayer::builder().inputs(3)
+                                               .outputs(2)
+                                               .init(WeightInit::XavierUniform(Box::new(random::<f64>())))
+                                               .with_bias(false)
+                                               .build()
+                                               .unwrap();
+        let reference = FeedforwardLayer::xavier_uniform(3, 2, identity(), random::<f64>());
+        assert_eq!(built.weights(), reference.weights());
+        assert_eq!(built.num_parameters(), 6);
+
+        let missing = FeedforwardLayer::<f32, _, _>::builder().outputs(2).build();
+        assert_eq!(missing.err(), Some(BuildError { missing: "inputs" }));
+        let missing = FeedforwardLayer::<f32, _, _>::builder().inputs(2).build();
+        assert_eq!(missing.err(), Some(BuildError { missing: "outputs" }));
+    }
+
+    #[test]
+    fn no_bias() {
+        let mut layer = FeedforwardLayer::new_no_bias(2, 2, sigmoid());
+        assert!(!layer.has_bias());
+        assert_eq!(layer.num_parameters(), 4);
+        layer.weights_mut().copy_from_slice(&[1.0f64, -1.0, 0.5, 2.0]);
+        let input = [0.25, 1.5];
+        let f = |x: f64| 1.0 / (1.0 + (-x).exp());
+        assert_eq!(layer.compute(&input), vec![f(0.25 - 1.5), f(0.125 + 3.0)]);
+
+        let rule = GradientDescent::new(0.5);
+        layer.backprop_train(&rule, &input, &[1.0, -1.0]);
+        layer.supervised_train(&rule, &input, &[0.0, 1.0]);
+        layer.train_batch(&rule, &[input.to_vec()], &[vec![0.0, 1.0]]);
+        layer.supervised_train(&PerceptronRule { rate: 0.5 }, &input, &[0.0, 1.0]);
+        assert_eq!(layer.biases(), &[0.0, 0.0]);
+        assert!(layer.weights() != [1.0, -1.0, 0.5, 2.0]);
+
+        // biases set by hand are ignored
+        layer.biases_mut()[0] = 10.0;
+        let w = layer.weights().to_vec();
+        assert!((layer.compute(&input)[0] - f(w[0] * 0.25 + w[1] * 1.5)).abs() < 1e-12);
+        assert!((layer.compute_batch(&[input.to_vec()])[0][0] - f(w[0] * 0.25 + w[1] * 1.5)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn reinitialize() {
+        let mut layer = FeedforwardLayer::new_from(3, 2, sigmoid(), random());
+        layer.train_batch(&Adam::new(0.1), &[vec![1.0, 2.0, 3.0]], &[vec![0.0, 1.0]]);
+        layer.reinitialize(|| 0.25);
+        assert!(layer.weights().iter().chain(layer.biases()).all(|&w| w == 0.25));
+        assert_eq!((layer.input_size(), layer.output_size()), (3, 2));
+
+        // same values as a new layer, including the state of the rule
+        let mut reference = FeedforwardLayer::new_from(3, 2, sigmoid(), random());
+        layer.reinitialize(random());
+        assert_eq!(layer.weights(), reference.weights());
+        assert_eq!(layer.biases(), reference.biases());
+        let rule = Adam::new(0.1);
+        layer.train_batch(&rule, &[vec![1.0, 2.0, 3.0]], &[vec![0.0, 1.0]]);
+        reference.train_batch(&rule, &[vec![1.0, 2.0, 3.0]], &[vec![0.0, 1.0]]);
+        assert_eq!(layer.weights(), reference.weights());
+
+        let mut layer = FeedforwardLayer::new_no_bias(2, 2, identity());
+        layer.reinitialize(|| 1.0f32);
+        assert_eq!(layer.weights(), &[1.0; 4]);
+        assert_eq!(layer.biases(), &[0.0; 2]);
+    }
+
+    #[test]
+    fn scale_and_normalize_weights() {
+        let mut layer = FeedforwardLayer::from_weights(2, vec![3.0f64, 0.0, -4.0, 12.0], vec![1.0, 2.0], identity()).unwrap();
+        layer.scale_weights(0.5);
+        assert_eq!(layer.weights(), &[1.5, 0.0, -2.0, 6.0]);
+        layer.normalize_weights(1.0);
+        let norm = layer.weights().iter().map(|w| w * w).sum::<f64>().sqrt();
+        assert!((norm - 1.0).abs() < 1e-12);
+        assert!((layer.weights()[3] - 12.0 / 13.0).abs() < 1e-12);
+        layer.normalize_weights(2.0);
+        assert!((layer.weights()[2] + 8.0 / 13.0).abs() < 1e-12);
+        assert_eq!(layer.biases(), &[1.0, 2.0]);
+
+        let mut layer = FeedforwardLayer::new(2, 2, identity());
+        layer.normalize_weights(1.0f32);
+        assert_eq!(layer.weights(), &[0.0; 4]);
+    }
+
+    #[test]
+    fn parameters_iterator() {
+        let mut layer = FeedforwardLayer::from_weights(2, vec![1.0f64, 2.0, 3.0, 4.0], vec![0.5, -0.5], identity()).unwrap();
+        assert_eq!(layer.parameters_iter().cloned().collect::<Vec<_>>(), vec![1.0, 2.0, 3.0, 4.0, 0.5, -0.5]);
+        assert_eq!(layer.parameters_iter().count(), layer.num_parameters());
+        assert_eq!(layer.compute(&[1.0, 1.0]), vec![3.5, 6.5]);
+        for p in layer.parameters_mut() {
+            *p *= 2.0;
+        }
+        assert_eq!(layer.weights(), &[2.0, 4.0, 6.0, 8.0]);
+        assert_eq!(layer.compute(&[1.0, 1.0]), vec![7.0, 13.0]);
+
+        let mut layer = FeedforwardLayer::new_no_bias(2, 1, identity());
+        assert_eq!(layer.parameters_mut().count(), 2);
+        for p in layer.parameters_mut() {
+            *p = 1.0f64;
+        }
+        assert_eq!(layer.compute(&[2.0, 3.0]), vec![5.0]);
+    }
+
+    #[test]
+    fn prune() {
+        let mut layer = FeedforwardLayer::from_weights(3, vec![0.5f64, -3.0, 0.1, 2.0, -0.2, 1.0], vec![0.01, 0.02], identity()).unwrap();
+        let mask = layer.prune(0.5);
+        assert_eq!(mask, vec![true, false, true, false, true, false]);
+        assert_eq!(layer.weights(), &[0.0, -3.0, 0.0, 2.0, 0.0, 1.0]);
+        assert_eq!(layer.biases(), &[0.01, 0.02]);
+
+        // the mask keeps the pruned weights at 0 during training
+        layer.backprop_train(&GradientDescent::new(0.1), &[1.0, 1.0, 1.0], &[1.0, 1.0]);
+        assert!(layer.weights()[0] != 0.0);
+        layer.apply_mask(&mask);
+        assert_eq!(layer.weights()[0], 0.0);
+        assert_eq!(layer.weights()[2], 0.0);
+        assert_eq!(layer.weights()[4], 0.0);
+        assert!(layer.weights()[1] != -3.0);
+
+        assert_eq!(layer.prune(0.0), vec![false; 6]);
+        layer.prune(1.0);
+        assert_eq!(layer.weights(), &[0.0; 6]);
+    }
+
+    #[test]
+    fn quantize() {
+        let mut random = random();
+        let layer = FeedforwardLayer::new_from(4, 3, sigmoid(), &mut random);
+        let quantized: QuantizedLayer<f64, _, _> = layer.quantize();
+        assert!((quantized.scale() - 1.0 / 127.0).abs() < 1e-12);
+        assert!(quantized.weights().iter().any(|&q| q == 127 || q == -127));
+
+        let restored = quantized.dequantize();
+        for (a, b) in layer.weights().iter().zip(restored.weights()) {
+            assert!((a - b).abs() < quantized.scale());
+        }
+        for (a, b) in layer.biases().iter().zip(restored.biases()) {
+            assert!((a - b).abs() < quantized.scale());
+        }
+
+        let input = [0.5, -1.0, 0.25, 1.0];
+        let output = quantized.compute(&input);
+        assert_eq!(quantized.output_size(), 3);
+        // the input is rounded to multiples of 1/127 as well
+        let rounded = input.iter().map(|&x| (x * 127.0f64).round() / 127.0).collect::<Vec<_>>();
+        for (q, (a, b)) in output.iter().zip(layer.compute(&input).iter().zip(restored.compute(&rounded))) {
+            assert!((q - b).abs() < 1e-12);
+            assert!((q - a).abs() < 0.01);
+        }
+
+        let zeros = FeedforwardLayer::new_no_bias(2, 2, identity()).quantize();
+        assert_eq!(zeros.weights(), &[0i8; 4]);
+        assert!(!zeros.dequantize().has_bias());
+        assert_eq!(zeros.compute(&[1.0f32, 2.0]), vec![0.0, 0.0]);
+    }
+
+    #[test]
+    fn compute_linear() {
+        let mut random = random::<f64>();
+        let layer = FeedforwardLayer::new_from(3, 4, sigmoid(), &mut random);
+        let input = [0.5, -1.0, 2.0];
+        let linear = layer.compute_linear(&input);
+        assert_eq!(linear.len(), 4);
+        let activated = linear.iter().map(|&x| 1.0 / (1.0 + (-x).exp())).collect::<Vec<_>>();
+        for (a, b) in layer.compute(&input).iter().zip(&activated) {
+            assert!((a - b).abs() < 1e-12);
+        }
+        for (j, l) in linear.iter().enumerate() {
+            let expected = layer.biases()[j] + (0..3).map(|i| layer.weights()[j*3 + i] * input[i]).sum::<f64>();
+            assert!((l - expected).abs() < 1e-12);
+        }
+    }
+
+    #[cfg(feature = "half")]
+    #[test]
+    fn half_precision() {
+        use half::f16;
+        use testing::random_scaled;
+
+        let mut random = random_scaled(0.1);
+        let weights = (0..256*4).map(|_| random()).collect::<Vec<f32>>();
+        let biases = vec![0.1f32, -0.2, 0.3, 0.0];
+        let input = (0..256).map(|i| 1.0 + (i % 5) as f32 / 10.0).collect::<Vec<f32>>();
+        let reference = FeedforwardLayer::from_weights(256, weights.clone(), biases.clone(), sigmoid()).unwrap();
+
+        let to_half = |v: &[f32]| v.iter().map(|&x| f16::from_f32(x)).collect::<Vec<_>>();
+        let layer = FeedforwardLayer::from_weights(256, to_half(&weights), to_half(&biases), sigmoid()).unwrap();
+        let output = layer.compute_upcast::<f32>(&to_half(&input));
+        for (h, f) in output.iter().zip(reference.compute(&input)) {
+            assert!((h.to_f32() - f).abs() < 2e-3);
+        }
+        // the same layer also computes natively in half precision
+        assert_eq!(layer.compute(&to_half(&input)).len(), 4);
+    }
+
+    #[test]
+    fn average_with() {
+        let mut layer = FeedforwardLayer::from_weights(2, vec![1.0f64, 2.0, 3.0, 4.0], vec![0.0, 1.0], identity()).unwrap();
+        let other = FeedforwardLayer::from_weights(2, vec![3.0f64, 0.0, -1.0, 4.0], vec![2.0, -1.0], sigmoid()).unwrap();
+        assert_eq!(layer.average_with(&other, 0.5), Ok(()));
+        assert_eq!(layer.weights(), &[2.0, 1.0, 1.0, 4.0]);
+        assert_eq!(layer.biases(), &[1.0, 0.0]);
+        assert_eq!(layer.compute(&[1.0, 1.0]), vec![4.0, 5.0]);
+
+        assert_eq!(layer.average_with(&other, 0.0), Ok(()));
+        assert_eq!(layer.weights(), &[2.0, 1.0, 1.0, 4.0]);
+        assert_eq!(layer.average_with(&other, 1.0), Ok(()));
+        assert_eq!(layer.weights(), other.weights());
+
+        let transposed = FeedforwardLayer::new(3, 2, identity());
+        assert_eq!(layer.average_with(&transposed, 0.5), Err(ShapeError { expected: 2, actual: 3 }));
+        let wider = FeedforwardLayer::new(2, 3, identity());
+        assert_eq!(layer.average_with(&wider, 0.5), Err(ShapeError { expected: 2, actual: 3 }));
+        assert_eq!(layer.weights(), other.weights());
+    }
+}
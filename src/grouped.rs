@@ -0,0 +1,249 @@
+//! Grouped linear layers
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use num::{Float, zero};
+
+use {Compute, BackpropTrain, Trainable, Parametrized, ModeAware, ParameterError, any_non_finite};
+use diagnostics::Weights;
+use training::{GradientRule, RuleState};
+
+/// A linear layer whose inputs and outputs are split in `groups` groups of
+/// consecutive values, each output only being connected to the inputs of its
+/// own group:
+///
+/// ```text
+/// Y[g][j] = B[g][j] + sum_i W[g][j][i] * X[g][i]
+/// ```
+///
+/// This is the same as a `FeedforwardLayer` with an identity activation whose
+/// weight matrix is block-diagonal, but it only stores the
+/// `groups * (inputs/groups) * (outputs/groups)` weights of the blocks.
+///
+/// It can be trained by backpropagation using any `GradientRule`.
+pub struct GroupedLinear<F: Float> {
+    groups: usize,
+    inputs: usize,
+    coeffs: Vec<F>,
+    biases: Vec<F>,
+    coeffs_state: RuleState<F>,
+    biases_state: RuleState<F>
+}
+
+impl<F: Float> GroupedLinear<F> {
+    /// Creates a new grouped layer with all its weights and biases set to 0.
+    ///
+    /// Fails if `groups` is 0 or does not divide both `inputs` and `outputs`.
+    pub fn new(inputs: usize, outputs: usize, groups: usize) -> Result<GroupedLinear<F>, ParameterError> {
+        GroupedLinear::new_from(inputs, outputs, groups, zero)
+    }
+
+    /// Creates a new grouped layer with all its weights and biases generated
+    /// by provided closure (for example a random number generator).
+    ///
+    /// Fails if `groups` is 0 or does not divide both `inputs` and `outputs`.
+    pub fn new_from<G>(inputs: usize, outputs: usize, groups: usize, mut generator: G)
+        -> Result<GroupedLinear<F>, ParameterError>
+        where G: FnMut() -> F
+    {
+        if groups == 0 || inputs % groups != 0 || outputs % groups != 0 {
+            return Err(ParameterError { name: "groups", value: groups as f64 });
+        }
+        Ok(GroupedLinear {
+            groups,
+            inputs,
+            coeffs: (0..inputs*outputs/groups).map(|_| generator()).collect(),
+            biases: (0..outputs).map(|_| generator()).collect(),
+            coeffs_state: RuleState::new(),
+            biases_state: RuleState::new()
+        })
+    }
+
+    /// The number of groups of this layer.
+    pub fn groups(&self) -> usize {
+        self.groups
+    }
+
+    /// Get access to the weights of this layer.
+    ///
+    /// The weight connecting input `i` to output `j` of group `g` (both
+    /// counted within the group) is at index
+    /// `(g*group_outputs + j)*group_inputs + i`.
+    pub fn weights(&self) -> &[F] {
+        &self.coeffs
+    }
+
+    /// Get mutable access to the weights of this layer.
+    ///
+    /// See `weights()` for their layout.
+    pub fn weights_mut(&mut self) -> &mut [F] {
+        &mut self.coeffs
+    }
+
+    /// Get access to the biases of this layer, one per output.
+    pub fn biases(&self) -> &[F] {
+        &self.biases
+    }
+
+    /// Get mutable access to the biases of this layer.
+    pub fn biases_mut(&mut self) -> &mut [F] {
+        &mut self.biases
+    }
+
+    /// The number of inputs of each group.
+    fn group_inputs(&self) -> usize {
+        self.inputs / self.groups
+    }
+
+    /// The number of outputs of each group.
+    fn group_outputs(&self) -> usize {
+        self.biases.len() / self.groups
+    }
+}
+
+impl<F: Float> Compute<F> for GroupedLinear<F> {
+    fn compute(&self, input: &[F]) -> Vec<F> {
+        let (n, m) = (self.group_inputs(), self.group_outputs());
+        (0..self.biases.len()).map(|o| {
+            // output `o` is output `o % m` of group `o / m`
+            let g = o / m;
+            let row = &self.coeffs[o*n..(o+1)*n];
+            row.iter().enumerate().fold(self.biases[o], |acc, (i, &w)| {
+                acc + w * input.get(g*n + i).cloned().unwrap_or(zero())
+            })
+        }).collect()
+    }
+
+    fn input_size(&self) -> usize {
+        self.inputs
+    }
+
+    fn output_size(&self) -> usize {
+        self.biases.len()
+    }
+}
+
+impl<F, R> BackpropTrain<F, R> for GroupedLinear<F>
+    where F: Float,
+          R: GradientRule<Float = F>
+{
+    fn backprop_train(&mut self, rule: &R, input: &[F], error: &[F]) -> Vec<F> {
+        let (n, m) = (self.group_inputs(), self.group_outputs());
+        let mut coeffs_grad = vec![zero(); self.coeffs.len()];
+        let mut biases_grad = vec![zero(); self.biases.len()];
+        let mut returned = vec![zero(); self.inputs];
+        for o in 0..self.biases.len() {
+            let g = o / m;
+            let delta = error.get(o).cloned().unwrap_or(zero());
+            biases_grad[o] = delta;
+            for i in 0..n {
+                let x = g*n + i;
+                coeffs_grad[o*n + i] = delta * input.get(x).cloned().unwrap_or(zero());
+                returned[x] = returned[x] + delta * self.coeffs[o*n + i];
+            }
+        }
+        rule.apply(&mut self.coeffs_state, &mut self.coeffs, &coeffs_grad, true);
+        rule.apply(&mut self.biases_state, &mut self.biases, &biases_grad, false);
+        returned
+    }
+}
+
+impl<F: Float> Weights<F> for GroupedLinear<F> {
+    fn weights(&self) -> &[F] {
+        &self.coeffs
+    }
+
+    fn weights_mut(&mut self) -> &mut [F] {
+        &mut self.coeffs
+    }
+}
+
+impl<F: Float> ModeAware for GroupedLinear<F> {}
+
+impl<F: Float> Trainable for GroupedLinear<F> {
+    fn num_parameters(&self) -> usize {
+        self.coeffs.len() + self.biases.len()
+    }
+
+    fn has_nan(&self) -> bool {
+        any_non_finite(&self.coeffs) || any_non_finite(&self.biases)
+    }
+}
+
+impl<F: Float> Parametrized<F> for GroupedLinear<F> {
+    fn parameters_iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a F> + 'a> {
+        Box::new(self.coeffs.iter().chain(self.biases.iter()))
+    }
+
+    fn parameters_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = &'a mut F> + 'a> {
+        Box::new(self.coeffs.iter_mut().chain(self.biases.iter_mut()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {Compute, BackpropTrain, Trainable, ParameterError};
+    use diagnostics::gradient_check;
+    use loss::MeanSquaredError;
+    use testing::random;
+    use training::GradientDescent;
+
+    use super::GroupedLinear;
+
+    #[test]
+    fn shapes() {
+        let layer = GroupedLinear::<f32>::new(6, 4, 2).unwrap();
+        assert_eq!(layer.input_size(), 6);
+        assert_eq!(layer.output_size(), 4);
+        assert_eq!(layer.weights().len(), 2 * 3 * 2);
+        assert_eq!(layer.num_parameters(), 12 + 4);
+        assert_eq!(GroupedLinear::<f32>::new(6, 4, 1).unwrap().weights().len(), 24);
+
+        for &(inputs, outputs, groups) in &[(6, 4, 0), (6, 4, 4), (5, 4, 2), (6, 3, 2)] {
+            assert_eq!(GroupedLinear::<f32>::new(inputs, outputs, groups).err(),
+                       Some(ParameterError { name: "groups", value: groups as f64 }));
+        }
+    }
+
+    #[test]
+    fn groups_are_independent() {
+        let layer = GroupedLinear::new_from(6, 4, 2, random()).unwrap();
+        let input = [0.5, -1.0, 2.0, 1.0, 0.25, -0.5];
+        let output = layer.compute(&input);
+        // changing the inputs of the second group leaves the first one alone
+        let mut changed = input;
+        changed[3] += 10.0;
+        changed[5] -= 3.0;
+        let other = layer.compute(&changed);
+        assert_eq!(other[..2], output[..2]);
+        assert!(other[2] != output[2] && other[3] != output[3]);
+
+        // the first output only depends on the first group
+        let expected = layer.biases()[0] + (0..3).map(|i| layer.weights()[i] * input[i]).sum::<f64>();
+        assert!((output[0] - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn backprop_train() {
+        let mut layer = GroupedLinear::new_from(4, 4, 2, random()).unwrap();
+        let input = [0.5, -1.0, 2.0, 1.0];
+        let target = [1.0, -1.0, 0.5, 0.0];
+        let rule = GradientDescent::new(0.05f64);
+        let error = |layer: &GroupedLinear<f64>| {
+            layer.compute(&input).iter().zip(&target).map(|(y, t)| (y - t).powi(2)).sum::<f64>()
+        };
+
+        // the gradients match finite differences
+        let mut reference = GroupedLinear::new_from(4, 4, 2, random()).unwrap();
+        assert!(gradient_check(&mut reference, &MeanSquaredError, &input, &target, 1e-6) < 1e-4);
+
+        let before = error(&layer);
+        for _ in 0..20 {
+            let output = layer.compute(&input);
+            let grad = (0..4).map(|j| 2.0 * (output[j] - target[j])).collect::<Vec<_>>();
+            layer.backprop_train(&rule, &input, &grad);
+        }
+        assert!(error(&layer) < before * 0.1);
+    }
+}
@@ -5,25 +5,98 @@
 //! be inferred to be `0.0`. Samewise, superfluous values are ignored.
 //!
 //! The whole library is parametred over a type `F`, which can be any `Float` type
-//! (currently `f32` or `f64`, but maybe others in the future).
+//! (currently `f32` or `f64`, but maybe others in the future). With the `half`
+//! feature, the half-precision `half::f16` can also be used to store the
+//! parameters of a network, see `FeedforwardLayer::compute_upcast()`.
+//!
+//! The parts of the library relying on the operating system, like reading
+//! files, printing the training progress or drawing random numbers, are
+//! behind the `std` feature, which is enabled by default. Without it, the
+//! library only depends on `core` and `alloc`, and the `libm` feature must be
+//! enabled to provide the floating point operations.
 
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 #![warn(missing_docs)]
+#![cfg_attr(feature = "unstable", feature(test))]
+
+#[cfg(not(any(feature = "std", feature = "libm")))]
+compile_error!("silinapse needs either the `std` or the `libm` feature for its floating point operations");
 
-extern crate num;
+#[macro_use]
+extern crate alloc;
+#[cfg(any(feature = "std", test))]
+extern crate core;
+#[cfg(feature = "half")]
+extern crate half;
+#[cfg(feature = "ndarray")]
+extern crate ndarray;
+extern crate num_traits as num;
+#[cfg(feature = "std")]
 extern crate rand;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(test)]
+extern crate serde_json;
+#[cfg(all(test, feature = "unstable"))]
+extern crate test;
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
 
 use num::Float;
 
+use loss::Loss;
+
+pub use error::{ShapeError, LabelError, ParameterError, NonFiniteError, BuildError};
 pub use linalg::SymmetricMatrix;
 
+#[cfg(feature = "std")]
 pub use boltzmann::BoltzmannMachine;
-pub use feedforward::FeedforwardLayer;
+pub use conv::Conv1D;
+#[cfg(feature = "ndarray")]
+pub use dense::DenseLayer;
+#[cfg(feature = "std")]
+pub use dropout::Dropout;
+pub use embedding::Embedding;
+pub use feedforward::{FeedforwardLayer, FeedforwardParameters, FeedforwardLayerBuilder, WeightInit, QuantizedLayer};
+pub use grouped::GroupedLinear;
+pub use logistic::LogisticRegression;
+pub use normalization::{BatchNorm, LayerNorm};
+pub use pooling::{MaxPool1D, AvgPool1D};
+pub use prelu::PReLULayer;
+pub use recurrent::{SimpleRNN, GRU, LSTM};
+pub use softmax::{SoftmaxLayer, TemperedSoftmaxLayer};
 
+#[cfg(feature = "std")]
 mod boltzmann;
+mod conv;
+#[cfg(feature = "ndarray")]
+mod dense;
+#[cfg(feature = "std")]
+mod dropout;
+mod embedding;
+mod error;
 mod feedforward;
+mod grouped;
 mod linalg;
+mod logistic;
+mod normalization;
+mod pooling;
+mod prelu;
+mod recurrent;
+mod softmax;
+#[cfg(test)]
+mod testing;
 
 pub mod activations;
+pub mod data;
+pub mod diagnostics;
+pub mod loss;
+pub mod metrics;
+#[cfg(feature = "rayon")]
+pub mod parallel;
 pub mod training;
 pub mod util;
 
@@ -33,6 +106,21 @@ pub mod util;
 pub trait Compute<F: Float>{
     /// Process input into output.
     fn compute(&self, input: &[F]) -> Vec<F>;
+    /// Process input into a caller-provided output buffer, which must hold
+    /// at least `output_size()` values.
+    ///
+    /// The default implementation copies the result of `compute()`, networks
+    /// can override it to avoid allocating a new vector at each call.
+    fn compute_into(&self, input: &[F], out: &mut [F]) {
+        let result = self.compute(input);
+        out[..result.len()].copy_from_slice(&result);
+    }
+    /// Process a batch of inputs, returning the output for each of them.
+    ///
+    /// The default implementation simply calls `compute()` on each input.
+    fn compute_batch(&self, inputs: &[Vec<F>]) -> Vec<Vec<F>> {
+        inputs.iter().map(|input| self.compute(input)).collect()
+    }
     /// The number of inputs this network expects.
     fn input_size(&self) -> usize;
     /// The number of outputs generated by this network.
@@ -60,13 +148,90 @@ pub trait SupervisedTrain<F: Float, M: Method> {
     fn supervised_train(&mut self, rule: &M, input: &[F], target: &[F]);
 }
 
+/// A trait for networks that can be trained using a certain method of
+/// supervised training on mini-batches.
+pub trait BatchSupervisedTrain<F: Float, M: Method> {
+    /// Performs one step of supervised training on given batch of inputs and
+    /// matching targets, using the learning parameters defined by `rule`.
+    ///
+    /// The gradients of all the samples are averaged and applied as a single
+    /// update.
+    fn train_batch(&mut self, rule: &M, inputs: &[Vec<F>], targets: &[Vec<F>]);
+}
+
 /// A trait for networks that can be trained using a certain method in a
-/// back-propagation way: the training returns a values vector that is
-/// to be used as a target value for the previous layer.
+/// back-propagation way: the training is driven by the gradient of the error
+/// with respect to the output of the network, and returns the gradient of the
+/// error with respect to its input, to be used to train the previous layer.
 pub trait BackpropTrain<F: Float, M: Method> {
-    /// Performs one step of supervised training on given input and target
-    /// value using the learning parameters defined by `rule`.
+    /// Performs one step of training on given input using the learning
+    /// parameters defined by `rule`.
+    ///
+    /// `error` is the gradient of the error with respect to the output of the
+    /// network for this input.
+    ///
+    /// Returns the gradient of the error with respect to the input, to feed to
+    /// the previous layer.
+    fn backprop_train(&mut self, rule: &M, input: &[F], error: &[F]) -> Vec<F>;
+}
+
+/// A trait for networks made of trainable parameters.
+pub trait Trainable {
+    /// The number of trainable parameters of this network.
     ///
-    /// Returns the value to feed to the previous layer.
-    fn backprop_train(&mut self, rule: &M, input: &[F], target: &[F]) -> Vec<F>;
-}
\ No newline at end of file
+    /// Defaults to 0, for networks without parameters.
+    fn num_parameters(&self) -> usize {
+        0
+    }
+
+    /// Whether any parameter of this network is NaN or infinite, which
+    /// typically happens when the learning rate is too high.
+    ///
+    /// Defaults to `false`, for networks without parameters.
+    fn has_nan(&self) -> bool {
+        false
+    }
+}
+
+/// A trait for networks giving access to their trainable parameters, for
+/// example to implement optimizers or regularizers outside of this crate.
+///
+/// The parameters are yielded in a fixed order specific to each network,
+/// typically the weights before the biases, and networks made of several
+/// layers chain the parameters of their layers. The number of parameters is
+/// the one given by `Trainable::num_parameters()`.
+///
+/// Defaults to no parameter at all.
+pub trait Parametrized<F: Float> {
+    /// An iterator over the parameters of this network.
+    fn parameters_iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a F> + 'a> {
+        Box::new(::core::iter::empty())
+    }
+
+    /// An iterator over mutable references to the parameters of this network,
+    /// in the same order as `parameters_iter()`.
+    fn parameters_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = &'a mut F> + 'a> {
+        Box::new(::core::iter::empty())
+    }
+}
+
+/// A trait for networks behaving differently during training and inference,
+/// like `Dropout` and `BatchNorm`.
+///
+/// Networks made of several layers propagate the mode to all of them, so that
+/// a whole network can be switched at once. Defaults to ignoring the mode.
+pub trait ModeAware {
+    /// Switches between training mode (`true`) and inference mode (`false`).
+    fn set_mode(&mut self, _training: bool) {}
+}
+
+/// Whether any of `values` is NaN or infinite.
+fn any_non_finite<F: Float>(values: &[F]) -> bool {
+    values.iter().any(|x| !x.is_finite())
+}
+
+/// The error signal of an output layer trained towards `target`, the gradient
+/// of the mean squared error `sum( (y_i - t_i)^2 ) / n`.
+fn output_error<F: Float>(output: &[F], target: &[F]) -> Vec<F> {
+    loss::MeanSquaredError.grad(output, target)
+}
@@ -1,4 +1,5 @@
-use std::ops::{Index, IndexMut};
+use alloc::vec::Vec;
+use core::ops::{Index, IndexMut};
 
 use num::{Float, zero};
 
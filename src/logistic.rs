@@ -0,0 +1,104 @@
+//! Logistic regression
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use num::Float;
+
+use {Compute, Trainable, Parametrized, ModeAware};
+use activations::sigmoid;
+use feedforward::FeedforwardLayer;
+use loss::{BinaryCrossEntropy, EpochOptions, train_epochs};
+use training::GradientDescent;
+
+type SigmoidLayer<F> = FeedforwardLayer<F, fn(F) -> F, fn(F) -> F>;
+
+/// A binary classifier, made of a single sigmoid unit trained with the binary
+/// cross-entropy.
+///
+/// This is a shortcut for the common case of a `FeedforwardLayer` with one
+/// output and a `sigmoid()` activation, trained with `loss::train_epochs()`
+/// and `GradientDescent`.
+pub struct LogisticRegression<F: Float> {
+    layer: SigmoidLayer<F>
+}
+
+impl<F: Float> LogisticRegression<F> {
+    /// Creates a new classifier for inputs of `inputs` features, with all its
+    /// weights set to 0.
+    pub fn new(inputs: usize) -> LogisticRegression<F> {
+        LogisticRegression {
+            layer: FeedforwardLayer::new(inputs, 1, sigmoid())
+        }
+    }
+
+    /// Get access to the underlying layer.
+    pub fn layer(&self) -> &SigmoidLayer<F> {
+        &self.layer
+    }
+}
+
+/// Training and prediction need floats that can be shared between threads, as
+/// the layer may be computed by several of them with the `rayon` feature.
+impl<F: Float + Send + Sync> LogisticRegression<F> {
+    /// Trains the classifier for `epochs` passes over the samples `x` and
+    /// their labels `y` (`0.0` or `1.0`), with a learning rate of `rate`.
+    ///
+    /// Returns the average binary cross-entropy over each epoch.
+    pub fn fit(&mut self, x: &[Vec<F>], y: &[F], epochs: usize, rate: F) -> Vec<F> {
+        let samples = x.iter().zip(y).map(|(x, &y)| (x.clone(), vec![y])).collect::<Vec<_>>();
+        let rule = GradientDescent::new(rate);
+        train_epochs(&mut self.layer, &rule, &BinaryCrossEntropy, &samples, epochs, EpochOptions::new())
+    }
+
+    /// The probability for `x` to belong to the class `1.0`.
+    pub fn predict_proba(&self, x: &[F]) -> F {
+        self.layer.compute(x)[0]
+    }
+}
+
+impl<F: Float> ModeAware for LogisticRegression<F> {}
+
+impl<F: Float> Trainable for LogisticRegression<F> {
+    fn num_parameters(&self) -> usize {
+        self.layer.num_parameters()
+    }
+
+    fn has_nan(&self) -> bool {
+        self.layer.has_nan()
+    }
+}
+
+impl<F: Float> Parametrized<F> for LogisticRegression<F> {
+    fn parameters_iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a F> + 'a> {
+        self.layer.parameters_iter()
+    }
+
+    fn parameters_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = &'a mut F> + 'a> {
+        self.layer.parameters_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LogisticRegression;
+
+    #[test]
+    fn separable() {
+        let mut random = {
+            let mut acc = 0;
+            move || { acc += 1; (((37*acc) % 101) as f64 - 50.0) / 25.0 }
+        };
+        let x = (0..200).map(|_| vec![random(), random()]).collect::<Vec<_>>();
+        let y = x.iter().map(|x| if x[0] + 2.0 * x[1] > 0.5 { 1.0 } else { 0.0 }).collect::<Vec<_>>();
+        assert!(y.contains(&0.0) && y.contains(&1.0));
+
+        let mut classifier = LogisticRegression::new(2);
+        let losses = classifier.fit(&x, &y, 100, 0.5);
+        assert!(losses[99] < losses[0]);
+        let correct = x.iter().zip(&y).filter(|&(x, &y)| {
+            (classifier.predict_proba(x) >= 0.5) == (y == 1.0)
+        }).count();
+        assert!(correct as f64 / 200.0 > 0.95);
+    }
+}
@@ -0,0 +1,606 @@
+//! Error functions measuring how far an output is from its target
+//!
+//! Their gradients are the error signals used to train networks by
+//! backpropagation.
+
+use alloc::vec::Vec;
+
+use num::{Float, zero};
+#[cfg(feature = "std")]
+use rand::Rng;
+
+use {Compute, BackpropTrain, Method, Parametrized, Trainable, NonFiniteError};
+use training::TrainingCallback;
+
+/// An error function, comparing the output of a network to its target.
+pub trait Loss<F: Float> {
+    /// The value of the error for given output and target.
+    fn loss(&self, output: &[F], target: &[F]) -> F;
+    /// The gradient of the error with respect to the output.
+    fn grad(&self, output: &[F], target: &[F]) -> Vec<F>;
+}
+
+/// Performs one step of training of `network` on given input and target,
+/// backpropagating the gradient of `loss`.
+///
+/// Returns the value of the error before the training step.
+pub fn train_step<F, N, M, L>(network: &mut N, rule: &M, loss: &L, input: &[F], target: &[F]) -> F
+    where F: Float,
+          N: Compute<F> + BackpropTrain<F, M>,
+          M: Method,
+          L: Loss<F>
+{
+    let output = network.compute(input);
+    network.backprop_train(rule, input, &loss.grad(&output, target));
+    loss.loss(&output, target)
+}
+
+/// The options of `train_epochs()`, none of which is set by default.
+pub struct EpochOptions<'a, F: 'a> {
+    #[cfg(feature = "std")]
+    shuffle: Option<&'a mut dyn Rng>,
+    callback: Option<&'a mut dyn TrainingCallback<F>>
+}
+
+impl<'a, F: Float> EpochOptions<'a, F> {
+    /// Options with no shuffling and no callback.
+    pub fn new() -> EpochOptions<'a, F> {
+        EpochOptions {
+            #[cfg(feature = "std")]
+            shuffle: None,
+            callback: None
+        }
+    }
+
+    /// Shuffles the order of the samples at the start of each epoch using
+    /// `rng`. Requires the `std` feature.
+    #[cfg(feature = "std")]
+    pub fn shuffle(mut self, rng: &'a mut dyn Rng) -> EpochOptions<'a, F> {
+        self.shuffle = Some(rng);
+        self
+    }
+
+    /// Notifies `callback` of the error of each sample and of each epoch, and
+    /// shows it the network at the end of each epoch: a `training::Checkpoint`
+    /// can be given to keep snapshots of it.
+    pub fn callback(mut self, callback: &'a mut dyn TrainingCallback<F>) -> EpochOptions<'a, F> {
+        self.callback = Some(callback);
+        self
+    }
+
+    #[cfg(feature = "std")]
+    fn shuffle_order(&mut self, order: &mut [usize]) {
+        if let Some(ref mut rng) = self.shuffle { rng.shuffle(order); }
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn shuffle_order(&mut self, _order: &mut [usize]) {}
+}
+
+impl<'a, F: Float> Default for EpochOptions<'a, F> {
+    fn default() -> EpochOptions<'a, F> {
+        EpochOptions::new()
+    }
+}
+
+/// Trains `network` for `epochs` passes over `samples`, a list of
+/// `(input, target)` pairs, backpropagating the gradient of `loss` after each
+/// sample.
+///
+/// The samples can be shuffled and the training observed through `options`.
+///
+/// Returns the average value of the error over each epoch.
+pub fn train_epochs<F, N, M, L>(network: &mut N,
+                                rule: &M,
+                                loss: &L,
+                                samples: &[(Vec<F>, Vec<F>)],
+                                epochs: usize,
+                                mut options: EpochOptions<F>) -> Vec<F>
+    where F: Float,
+          N: Compute<F> + BackpropTrain<F, M> + Parametrized<F>,
+          M: Method,
+          L: Loss<F>
+{
+    let mut order: Vec<usize> = (0..samples.len()).collect();
+    (0..epochs).map(|epoch| {
+        options.shuffle_order(&mut order);
+        train_epoch(network, rule, loss, samples, epoch, &order, &mut options.callback)
+    }).collect()
+}
+
+/// Same as `train_epochs()`, but checks the parameters of `network` after each
+/// epoch, and stops with an error as soon as one of them is NaN or infinite.
+pub fn train_epochs_checked<F, N, M, L>(network: &mut N,
+                                        rule: &M,
+                                        loss: &L,
+                                        samples: &[(Vec<F>, Vec<F>)],
+                                        epochs: usize,
+                                        mut options: EpochOptions<F>)
+    -> Result<Vec<F>, NonFiniteError>
+    where F: Float,
+          N: Compute<F> + BackpropTrain<F, M> + Parametrized<F> + Trainable,
+          M: Method,
+          L: Loss<F>
+{
+    let mut order: Vec<usize> = (0..samples.len()).collect();
+    let mut losses = Vec::with_capacity(epochs);
+    for epoch in 0..epochs {
+        options.shuffle_order(&mut order);
+        losses.push(train_epoch(network, rule, loss, samples, epoch, &order, &mut options.callback));
+        if network.has_nan() {
+            return Err(NonFiniteError { epoch });
+        }
+    }
+    Ok(losses)
+}
+
+/// Trains `network` over one pass of `samples`, in given `order`, and returns
+/// the average error.
+fn train_epoch<F, N, M, L>(network: &mut N,
+                           rule: &M,
+                           loss: &L,
+                           samples: &[(Vec<F>, Vec<F>)],
+                           epoch: usize,
+                           order: &[usize],
+                           callback: &mut Option<&mut dyn TrainingCallback<F>>) -> F
+    where F: Float,
+          N: Compute<F> + BackpropTrain<F, M> + Parametrized<F>,
+          M: Method,
+          L: Loss<F>
+{
+    let n = F::from(samples.len().max(1)).unwrap();
+    let error = order.iter().enumerate().fold(zero::<F>(), |acc, (batch, &i)| {
+        let (ref input, ref target) = samples[i];
+        let error = train_step(network, rule, loss, input, target);
+        if let Some(ref mut callback) = *callback { callback.on_batch_end(batch, error); }
+        acc + error
+    }) / n;
+    if let Some(ref mut callback) = *callback {
+        callback.on_epoch_end(epoch, error);
+        callback.on_epoch_parameters(epoch, error, network);
+    }
+    error
+}
+
+/// Performs one step of training of a classifier whose outputs are the
+/// logits of the classes, with a softmax and the cross-entropy error fused
+/// together.
+///
+/// The gradient of the cross-entropy of the softmax of the logits with
+/// respect to them is `softmax(logits) - target`, which is directly
+/// backpropagated into `network` (for example a linear `FeedforwardLayer`),
+/// rather than going through the `1/y` gradient of the cross-entropy that
+/// overflows for confident predictions.
+///
+/// Returns the value of the cross-entropy before the training step, computed
+/// using the log-sum-exp of the logits so that it stays finite.
+pub fn train_classification<F, N, M>(network: &mut N, rule: &M, input: &[F], target: &[F]) -> F
+    where F: Float,
+          N: Compute<F> + BackpropTrain<F, M>,
+          M: Method
+{
+    let logits = network.compute(input);
+    let max = logits.iter().fold(F::neg_infinity(), |m, &x| m.max(x));
+    let log_sum = logits.iter().fold(zero::<F>(), |acc, &x| acc + (x - max).exp()).ln() + max;
+    let mut loss = zero::<F>();
+    let grad = logits.iter().enumerate().map(|(i, &x)| {
+        let t = target_at(target, i);
+        loss = loss - t * (x - log_sum);
+        (x - log_sum).exp() - t
+    }).collect::<Vec<_>>();
+    network.backprop_train(rule, input, &grad);
+    loss
+}
+
+fn target_at<F: Float>(target: &[F], i: usize) -> F {
+    target.get(i).cloned().unwrap_or(zero())
+}
+
+/// The mean squared error: `sum( (y_i - t_i)^2 ) / n`.
+///
+/// This is also the error minimized by the `SupervisedTrain` implementations
+/// of the networks of this crate, whose error signal is its gradient
+/// `2*(y_i - t_i)/n`.
+pub struct MeanSquaredError;
+
+impl<F: Float> Loss<F> for MeanSquaredError {
+    fn loss(&self, output: &[F], target: &[F]) -> F {
+        if output.is_empty() { return zero(); }
+        let n = F::from(output.len()).unwrap();
+        output.iter().enumerate()
+              .fold(zero::<F>(), |acc, (i, &y)| acc + (y - target_at(target, i)).powi(2)) / n
+    }
+
+    fn grad(&self, output: &[F], target: &[F]) -> Vec<F> {
+        let n = F::from(output.len()).unwrap();
+        let two = F::from(2.0).unwrap();
+        output.iter().enumerate()
+              .map(|(i, &y)| two * (y - target_at(target, i)) / n)
+              .collect()
+    }
+}
+
+/// The cross-entropy between a target probability distribution and the
+/// output one: `-sum( t_i * ln(y_i) )`.
+///
+/// The outputs are expected to be probabilities, as given by a `SoftmaxLayer`.
+/// They are clamped away from zero to keep the error finite.
+pub struct CrossEntropy;
+
+impl<F: Float> Loss<F> for CrossEntropy {
+    fn loss(&self, output: &[F], target: &[F]) -> F {
+        output.iter().enumerate().fold(zero::<F>(), |acc, (i, &y)| {
+            acc - target_at(target, i) * y.max(F::min_positive_value()).ln()
+        })
+    }
+
+    fn grad(&self, output: &[F], target: &[F]) -> Vec<F> {
+        output.iter().enumerate()
+              .map(|(i, &y)| -target_at(target, i) / y.max(F::min_positive_value()))
+              .collect()
+    }
+}
+
+/// The Kullback-Leibler divergence of the output probability distribution
+/// from the target one: `sum( t_i * ln(t_i / y_i) )`.
+///
+/// It only differs from the `CrossEntropy` by the entropy of the target,
+/// which does not depend on the output, and is thus zero when the output is
+/// equal to the target. The outputs are clamped away from zero to keep the
+/// error finite, and the terms of zero targets are zero.
+pub struct KLDivergence;
+
+impl<F: Float> Loss<F> for KLDivergence {
+    fn loss(&self, output: &[F], target: &[F]) -> F {
+        output.iter().enumerate().fold(zero::<F>(), |acc, (i, &y)| {
+            let t = target_at(target, i);
+            if t > zero() {
+                acc + t * (t / y.max(F::min_positive_value())).ln()
+            } else {
+                acc
+            }
+        })
+    }
+
+    fn grad(&self, output: &[F], target: &[F]) -> Vec<F> {
+        output.iter().enumerate()
+              .map(|(i, &y)| -target_at(target, i) / y.max(F::min_positive_value()))
+              .collect()
+    }
+}
+
+/// The binary cross-entropy, for outputs that are independent probabilities
+/// in `(0,1)`: `-sum( t_i * ln(y_i) + (1 - t_i) * ln(1 - y_i) )`.
+///
+/// This is the error of choice for a logistic classifier, such as a
+/// `FeedforwardLayer` with a sigmoid activation. The outputs are clamped away
+/// from `0` and `1` to keep the error finite.
+pub struct BinaryCrossEntropy;
+
+fn clamp_probability<F: Float>(y: F) -> F {
+    y.max(F::epsilon()).min(F::one() - F::epsilon())
+}
+
+impl<F: Float> Loss<F> for BinaryCrossEntropy {
+    fn loss(&self, output: &[F], target: &[F]) -> F {
+        output.iter().enumerate().fold(zero::<F>(), |acc, (i, &y)| {
+            let (y, t) = (clamp_probability(y), target_at(target, i));
+            acc - ( t * y.ln() + (F::one() - t) * (F::one() - y).ln() )
+        })
+    }
+
+    fn grad(&self, output: &[F], target: &[F]) -> Vec<F> {
+        output.iter().enumerate()
+              .map(|(i, &y)| {
+                  let y = clamp_probability(y);
+                  (y - target_at(target, i)) / (y * (F::one() - y))
+              })
+              .collect()
+    }
+}
+
+/// The Huber loss, quadratic for small residuals and linear for large ones,
+/// which makes it less sensitive to outliers than the squared error.
+///
+/// For each residual `r = y - t`:
+///
+/// ```text
+/// 0.5 * r^2                     if |r| <= delta
+/// delta * ( |r| - 0.5*delta )   otherwise
+/// ```
+pub struct Huber<F: Float> {
+    /// The residual size at which the loss switches from quadratic to linear.
+    pub delta: F
+}
+
+impl<F: Float> Loss<F> for Huber<F> {
+    fn loss(&self, output: &[F], target: &[F]) -> F {
+        let half = F::from(0.5).unwrap();
+        output.iter().enumerate().fold(zero::<F>(), |acc, (i, &y)| {
+            let r = (y - target_at(target, i)).abs();
+            if r <= self.delta {
+                acc + half * r * r
+            } else {
+                acc + self.delta * (r - half * self.delta)
+            }
+        })
+    }
+
+    fn grad(&self, output: &[F], target: &[F]) -> Vec<F> {
+        output.iter().enumerate()
+              .map(|(i, &y)| {
+                  let r = y - target_at(target, i);
+                  if r.abs() <= self.delta { r } else { self.delta * r.signum() }
+              })
+              .collect()
+    }
+}
+
+/// The hinge loss of support vector machines, for targets that are either
+/// `-1.0` or `1.0` (not `0.0` and `1.0`): `sum( max(0, margin - t_i * y_i) )`.
+///
+/// Outputs of the right sign and beyond the margin (typically `1.0`) have no
+/// error, and the others get a constant gradient of `-t_i`. Training a
+/// `FeedforwardLayer` with an identity activation and a weight decay, like
+/// `GradientDescentL2`, with this error makes it a linear SVM.
+pub struct Hinge<F: Float> {
+    /// The value that `t_i * y_i` must reach to have no error.
+    pub margin: F
+}
+
+impl<F: Float> Loss<F> for Hinge<F> {
+    fn loss(&self, output: &[F], target: &[F]) -> F {
+        output.iter().enumerate().fold(zero::<F>(), |acc, (i, &y)| {
+            acc + (self.margin - target_at(target, i) * y).max(zero())
+        })
+    }
+
+    fn grad(&self, output: &[F], target: &[F]) -> Vec<F> {
+        output.iter().enumerate()
+              .map(|(i, &y)| {
+                  let t = target_at(target, i);
+                  if t * y < self.margin { -t } else { zero() }
+              })
+              .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {Compute, BackpropTrain, SoftmaxLayer};
+    use activations::{identity, sigmoid};
+    use feedforward::FeedforwardLayer;
+    use testing::random;
+    use training::{GradientDescent, LossHistory, Checkpoint};
+
+    #[cfg(feature = "std")]
+    use rand::{SeedableRng, XorShiftRng};
+
+    use super::{Loss, MeanSquaredError, CrossEntropy, BinaryCrossEntropy, Huber, KLDivergence, Hinge, train_step, train_epochs};
+    use super::{train_classification, train_epochs_checked, EpochOptions};
+    use NonFiniteError;
+
+    #[test]
+    fn mean_squared_error() {
+        let output = [1.0f64, 2.0, 3.0, 4.0];
+        let target = [1.5f64, 2.0, 1.0, 4.0];
+        assert!((MeanSquaredError.loss(&output, &target) - 4.25 / 4.0).abs() < 1e-12);
+        let grad = MeanSquaredError.grad(&output, &target);
+        for i in 0..4 {
+            assert!((grad[i] - 2.0 * (output[i] - target[i]) / 4.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn cross_entropy_with_softmax() {
+        let mut layer = SoftmaxLayer::new(3);
+        let input = [2.0f64, -1.0, 0.5];
+        let target = [0.0f64, 0.0, 1.0];
+        let output = layer.compute(&input);
+        assert!((CrossEntropy.loss(&output, &target) + output[2].ln()).abs() < 1e-12);
+        let grad = layer.backprop_train(&GradientDescent::new(0.1),
+                                        &input,
+                                        &CrossEntropy.grad(&output, &target));
+        for i in 0..3 {
+            assert!((grad[i] - (output[i] - target[i])).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn training_step() {
+        let mut layer = FeedforwardLayer::new(2, 1, identity());
+        let rule = GradientDescent::new(0.1f64);
+        let first = train_step(&mut layer, &rule, &MeanSquaredError, &[1.0, 2.0], &[3.0]);
+        assert_eq!(first, 9.0);
+        let mut last = first;
+        for _ in 0..20 {
+            last = train_step(&mut layer, &rule, &MeanSquaredError, &[1.0, 2.0], &[3.0]);
+        }
+        assert!(last < 1e-3);
+    }
+
+    #[test]
+    fn binary_cross_entropy() {
+        assert!(BinaryCrossEntropy.loss(&[0.999f64, 0.001], &[1.0, 0.0]) < 0.01);
+        assert!(BinaryCrossEntropy.loss(&[0.001f64, 0.999], &[1.0, 0.0]) > 10.0);
+        // no infinities on saturated outputs
+        assert!(BinaryCrossEntropy.loss(&[0.0f64, 1.0], &[1.0, 0.0]).is_finite());
+        assert!(BinaryCrossEntropy.grad(&[0.0f64, 1.0], &[1.0, 0.0]).iter().all(|g| g.is_finite()));
+
+        let grad = BinaryCrossEntropy.grad(&[0.25f64], &[1.0]);
+        assert!((grad[0] + 1.0 / 0.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn logistic_classifier() {
+        let samples = [([2.0f64, 1.0], [1.0f64]), ([1.0, 3.0], [1.0]),
+                       ([-1.0, -2.0], [0.0]), ([-2.0, 0.5], [0.0])];
+        let mut layer = FeedforwardLayer::new(2, 1, sigmoid());
+        let rule = GradientDescent::new(0.5f64);
+        for _ in 0..100 {
+            for (input, target) in &samples {
+                train_step(&mut layer, &rule, &BinaryCrossEntropy, input, target);
+            }
+        }
+        for (input, target) in &samples {
+            assert!((layer.compute(input)[0] - target[0]).abs() < 0.1);
+        }
+    }
+
+    #[test]
+    fn huber() {
+        let huber = Huber { delta: 1.0f64 };
+        // quadratic region
+        assert!((huber.loss(&[0.5], &[0.0]) - 0.125).abs() < 1e-12);
+        assert!((huber.grad(&[0.5], &[0.0])[0] - 0.5).abs() < 1e-12);
+        // linear region
+        assert!((huber.loss(&[-3.0], &[0.0]) - 2.5).abs() < 1e-12);
+        assert!((huber.grad(&[-3.0], &[0.0])[0] + 1.0).abs() < 1e-12);
+        assert!((huber.grad(&[5.0], &[1.0])[0] - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn kl_divergence() {
+        let target = [0.2f64, 0.0, 0.8];
+        assert_eq!(KLDivergence.loss(&target, &target), 0.0);
+        let output = [0.5f64, 0.1, 0.4];
+        assert!(KLDivergence.loss(&output, &target) > 0.0);
+        let expected = 0.2 * (0.2f64 / 0.5).ln() + 0.8 * (0.8f64 / 0.4).ln();
+        assert!((KLDivergence.loss(&output, &target) - expected).abs() < 1e-12);
+        // only differs from the cross-entropy by a constant
+        let entropy = -(0.2 * 0.2f64.ln() + 0.8 * 0.8f64.ln());
+        assert!((KLDivergence.loss(&output, &target) + entropy - CrossEntropy.loss(&output, &target)).abs() < 1e-12);
+
+        let grad = KLDivergence.grad(&output, &target);
+        for i in 0..3 {
+            let mut plus = output;
+            plus[i] += 1e-6;
+            let mut minus = output;
+            minus[i] -= 1e-6;
+            let num = (KLDivergence.loss(&plus, &target) - KLDivergence.loss(&minus, &target)) / 2e-6;
+            assert!((grad[i] - num).abs() < 1e-6);
+        }
+        assert!(KLDivergence.loss(&[0.0f64, 1.0], &[0.5, 0.5]).is_finite());
+    }
+
+    #[test]
+    fn hinge() {
+        let hinge = Hinge { margin: 1.0f64 };
+        // correctly classified beyond the margin
+        assert_eq!(hinge.loss(&[2.0], &[1.0]), 0.0);
+        assert_eq!(hinge.grad(&[2.0], &[1.0]), vec![0.0]);
+        assert_eq!(hinge.loss(&[-1.5], &[-1.0]), 0.0);
+        // correctly classified but within the margin
+        assert_eq!(hinge.loss(&[0.25], &[1.0]), 0.75);
+        assert_eq!(hinge.grad(&[0.25], &[1.0]), vec![-1.0]);
+        // misclassified
+        assert_eq!(hinge.loss(&[0.5], &[-1.0]), 1.5);
+        assert_eq!(hinge.grad(&[0.5], &[-1.0]), vec![1.0]);
+
+        // a linear SVM separating two points
+        let mut layer = FeedforwardLayer::new(2, 1, identity());
+        let rule = GradientDescent::new(0.1f64);
+        for _ in 0..50 {
+            train_step(&mut layer, &rule, &hinge, &[1.0, 2.0], &[1.0]);
+            train_step(&mut layer, &rule, &hinge, &[-1.0, -0.5], &[-1.0]);
+        }
+        assert!(layer.compute(&[1.0, 2.0])[0] >= 1.0);
+        assert!(layer.compute(&[-1.0, -0.5])[0] <= -1.0);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn epochs_decrease_loss() {
+        let samples: Vec<(Vec<f64>, Vec<f64>)> = vec![
+            (vec![1.0, 1.0], vec![1.0]),
+            (vec![2.0, 0.5], vec![1.25]),
+            (vec![-1.0, -0.5], vec![-0.75]),
+            (vec![-0.5, -2.0], vec![-1.25]),
+        ];
+        let mut layer = FeedforwardLayer::new(2, 1, identity());
+        let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+        let losses = train_epochs(&mut layer, &GradientDescent::new(0.05), &MeanSquaredError,
+                                  &samples, 20, EpochOptions::new().shuffle(&mut rng));
+        assert_eq!(losses.len(), 20);
+        for w in losses.windows(2) {
+            assert!(w[1] < w[0]);
+        }
+    }
+
+    #[test]
+    fn fused_classification() {
+        let mut fused = FeedforwardLayer::new_from(2, 3, identity(), random());
+        let mut reference = FeedforwardLayer::from_weights(2, fused.weights().to_vec(),
+                                                           fused.biases().to_vec(), identity()).unwrap();
+        let input = [0.5f64, -1.0];
+        let target = [0.0f64, 1.0, 0.0];
+        let rule = GradientDescent::new(0.5);
+
+        let probabilities = SoftmaxLayer::new(3).compute(&reference.compute(&input));
+        let loss = train_classification(&mut fused, &rule, &input, &target);
+        assert!((loss - CrossEntropy.loss(&probabilities, &target)).abs() < 1e-12);
+        let error = (0..3).map(|i| probabilities[i] - target[i]).collect::<Vec<_>>();
+        reference.backprop_train(&rule, &input, &error);
+        for (a, b) in fused.weights().iter().zip(reference.weights().iter()) {
+            assert!((a - b).abs() < 1e-12);
+        }
+
+        // extreme logits
+        let mut layer = FeedforwardLayer::from_weights(1, vec![1000.0f64, -1000.0], vec![0.0, 0.0], identity()).unwrap();
+        let loss = train_classification(&mut layer, &rule, &[1.0], &[0.0, 1.0]);
+        assert!((loss - 2000.0).abs() < 1e-9);
+        assert!(layer.weights().iter().all(|w| w.is_finite()));
+        assert!((layer.weights()[0] - 999.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn epochs_checked() {
+        let samples = vec![(vec![1.0f64, 2.0], vec![1.0]), (vec![-2.0, 1.0], vec![0.0])];
+        let mut layer = FeedforwardLayer::new(2, 1, identity());
+        let losses = train_epochs_checked(&mut layer, &GradientDescent::new(0.05), &MeanSquaredError,
+                                          &samples, 5, EpochOptions::new());
+        assert_eq!(losses.map(|l| l.len()), Ok(5));
+
+        // a far too high learning rate makes the weights diverge
+        let mut layer = FeedforwardLayer::new(2, 1, identity());
+        let losses = train_epochs_checked(&mut layer, &GradientDescent::new(1e100), &MeanSquaredError,
+                                          &samples, 10, EpochOptions::new());
+        assert_eq!(losses, Err(NonFiniteError { epoch: 1 }));
+    }
+
+    #[test]
+    fn loss_history() {
+        let samples = vec![(vec![1.0f64, 2.0], vec![1.0]), (vec![-2.0, 1.0], vec![0.0])];
+        let mut layer = FeedforwardLayer::new(2, 1, identity());
+        let mut history = LossHistory::new();
+        let losses = train_epochs(&mut layer, &GradientDescent::new(0.05), &MeanSquaredError,
+                                  &samples, 7, EpochOptions::new().callback(&mut history));
+        assert_eq!(history.losses, losses);
+        assert_eq!(history.batch_losses.len(), 14);
+        assert_eq!((history.batch_losses[12] + history.batch_losses[13]) / 2.0, losses[6]);
+    }
+
+    #[test]
+    fn checkpointed() {
+        let samples = vec![(vec![1.0f64, 2.0], vec![1.0]), (vec![-2.0, 1.0], vec![0.0])];
+        let mut layer = FeedforwardLayer::new(2, 1, identity());
+        let mut checkpoint = Checkpoint::new(2, 2);
+        let losses = train_epochs(&mut layer, &GradientDescent::new(0.05), &MeanSquaredError,
+                                  &samples, 7, EpochOptions::new().callback(&mut checkpoint));
+        assert!(losses[6] < losses[0]);
+        // snapshots at epochs 0, 2, 4 and 6, keeping the last two
+        assert_eq!(checkpoint.snapshots().iter().map(|s| s.epoch).collect::<Vec<_>>(), vec![4, 6]);
+        assert_eq!(checkpoint.best().map(|b| b.epoch), Some(6));
+        let trained = layer.weights().iter().chain(layer.biases()).cloned().collect::<Vec<_>>();
+        assert_eq!(checkpoint.snapshots()[1].parameters, trained);
+
+        // a too large learning rate makes the following epochs worse
+        checkpoint.every = 0;
+        let diverged = train_epochs(&mut layer, &GradientDescent::new(2.0), &MeanSquaredError,
+                                    &samples, 3, EpochOptions::new().callback(&mut checkpoint));
+        assert!(diverged.iter().all(|&l| l > losses[6]));
+        assert_eq!(checkpoint.best().map(|b| b.epoch), Some(6));
+        assert_eq!(checkpoint.restore_best(&mut layer), Some(6));
+        assert_eq!(layer.weights().iter().chain(layer.biases()).cloned().collect::<Vec<_>>(), trained);
+    }
+}
@@ -0,0 +1,163 @@
+//! Metrics to evaluate trained networks.
+
+use alloc::vec::Vec;
+
+use num::Float;
+
+/// The index of the largest value of `values`, the first one in case of a tie.
+///
+/// Returns `None` if `values` is empty.
+pub fn argmax<F: Float>(values: &[F]) -> Option<usize> {
+    values.iter().enumerate().fold(None, |best: Option<(usize, F)>, (i, &x)| {
+        match best {
+            Some((_, max)) if max >= x => best,
+            _ => Some((i, x))
+        }
+    }).map(|(i, _)| i)
+}
+
+/// The fraction of `predictions` whose largest value is at the same position
+/// as the one of the matching one-hot encoded `targets`.
+///
+/// Returns `0.0` if there are no predictions.
+pub fn accuracy<F: Float>(predictions: &[Vec<F>], targets: &[Vec<F>]) -> f64 {
+    if predictions.is_empty() {
+        return 0.0;
+    }
+    let correct = predictions.iter()
+                             .zip(targets.iter())
+                             .filter(|&(p, t)| argmax(p) == argmax(t))
+                             .count();
+    correct as f64 / predictions.len() as f64
+}
+
+/// The confusion matrix of a classifier over `num_classes` classes: the entry
+/// `[t][p]` counts the predictions of class `p` for a sample of class `t`.
+///
+/// As for `accuracy()`, the classes are given by the position of the largest
+/// value of the predictions and of the one-hot encoded targets. Samples whose
+/// class is not below `num_classes` are ignored.
+pub fn confusion_matrix<F: Float>(predictions: &[Vec<F>],
+                                  targets: &[Vec<F>],
+                                  num_classes: usize)
+    -> Vec<Vec<usize>>
+{
+    let mut matrix = vec![vec![0; num_classes]; num_classes];
+    for (p, t) in predictions.iter().zip(targets.iter()) {
+        if let (Some(p), Some(t)) = (argmax(p), argmax(t)) {
+            if p < num_classes && t < num_classes {
+                matrix[t][p] += 1;
+            }
+        }
+    }
+    matrix
+}
+
+/// The mean absolute difference between the values of `predictions` and of
+/// the matching `targets`, over all the components of all the outputs.
+///
+/// Returns `0.0` if there are no values.
+pub fn mean_absolute_error<F: Float>(predictions: &[Vec<F>], targets: &[Vec<F>]) -> f64 {
+    let (sum, count) = pairs(predictions, targets).fold((0.0, 0), |(sum, count), (p, t)| {
+        (sum + (p - t).abs(), count + 1)
+    });
+    if count == 0 { 0.0 } else { sum / count as f64 }
+}
+
+/// The coefficient of determination of `predictions` with respect to
+/// `targets`, over all the components of all the outputs:
+///
+/// ```text
+/// 1 - sum((target - prediction)^2) / sum((target - mean(target))^2)
+/// ```
+///
+/// It is `1.0` for a perfect predictor and `0.0` for one always predicting
+/// the mean of the targets, and can be arbitrarily negative.
+///
+/// If the targets all have the same value (including when there are no
+/// targets), the ratio is undefined: `1.0` is then returned if the
+/// predictions are exact, and `0.0` otherwise.
+pub fn r2_score<F: Float>(predictions: &[Vec<F>], targets: &[Vec<F>]) -> f64 {
+    let (sum, count) = pairs(predictions, targets).fold((0.0, 0), |(sum, count), (_, t)| {
+        (sum + t, count + 1)
+    });
+    let mean = if count == 0 { 0.0 } else { sum / count as f64 };
+    let (residual, total) = pairs(predictions, targets).fold((0.0, 0.0), |(r, v), (p, t)| {
+        (r + (t - p).powi(2), v + (t - mean).powi(2))
+    });
+    if total > 0.0 {
+        1.0 - residual / total
+    } else if residual == 0.0 {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+/// The pairs of matching components of `predictions` and `targets`, as `f64`.
+fn pairs<'a, F: Float>(predictions: &'a [Vec<F>], targets: &'a [Vec<F>])
+    -> impl Iterator<Item = (f64, f64)> + 'a
+{
+    predictions.iter().zip(targets.iter()).flat_map(|(p, t)| {
+        p.iter().zip(t.iter()).map(|(&p, &t)| (p.to_f64().unwrap_or(0.0), t.to_f64().unwrap_or(0.0)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{argmax, accuracy, confusion_matrix, mean_absolute_error, r2_score};
+
+    #[test]
+    fn argmax_values() {
+        assert_eq!(argmax(&[0.1f32, 0.7, 0.2]), Some(1));
+        assert_eq!(argmax(&[0.5f32, 0.2, 0.5]), Some(0));
+        assert_eq!(argmax::<f32>(&[]), None);
+    }
+
+    #[test]
+    fn accuracy_fraction() {
+        let predictions = vec![vec![0.9f64, 0.1, 0.0], vec![0.2, 0.3, 0.5],
+                               vec![0.1, 0.8, 0.1], vec![0.6, 0.3, 0.1]];
+        let targets = vec![vec![1.0f64, 0.0, 0.0], vec![0.0, 0.0, 1.0],
+                           vec![1.0, 0.0, 0.0], vec![0.0, 1.0, 0.0]];
+        assert_eq!(accuracy(&predictions, &targets), 0.5);
+        assert_eq!(accuracy::<f64>(&[], &[]), 0.0);
+    }
+
+    #[test]
+    fn confusion() {
+        let predictions = vec![vec![0.9f64, 0.1, 0.0], vec![0.2, 0.3, 0.5],
+                               vec![0.1, 0.8, 0.1], vec![0.6, 0.3, 0.1],
+                               vec![0.0, 0.1, 0.9]];
+        let targets = vec![vec![1.0f64, 0.0, 0.0], vec![0.0, 0.0, 1.0],
+                           vec![1.0, 0.0, 0.0], vec![0.0, 1.0, 0.0],
+                           vec![0.0, 1.0, 0.0]];
+        let matrix = confusion_matrix(&predictions, &targets, 3);
+        assert_eq!(matrix, vec![vec![1, 1, 0], vec![1, 0, 1], vec![0, 0, 1]]);
+        let diagonal = (0..3).map(|i| matrix[i][i]).sum::<usize>();
+        let correct = (accuracy(&predictions, &targets) * 5.0).round() as usize;
+        assert_eq!(diagonal, correct);
+    }
+
+    #[test]
+    fn regression() {
+        let targets = vec![vec![1.0f64, 2.0], vec![3.0, 4.0], vec![5.0, 6.0]];
+        assert_eq!(mean_absolute_error(&targets, &targets), 0.0);
+        assert_eq!(r2_score(&targets, &targets), 1.0);
+
+        // always predicting the mean of the targets
+        let constant = vec![vec![3.5f64, 3.5]; 3];
+        assert_eq!(r2_score(&constant, &targets), 0.0);
+        assert_eq!(mean_absolute_error(&constant, &targets), 1.5);
+
+        let worse = vec![vec![10.0f64, 10.0]; 3];
+        assert!(r2_score(&worse, &targets) < 0.0);
+
+        // targets without variance
+        let flat = vec![vec![2.0f64], vec![2.0]];
+        assert_eq!(r2_score(&flat, &flat), 1.0);
+        assert_eq!(r2_score(&[vec![1.0f64], vec![3.0]], &flat), 0.0);
+        assert_eq!(r2_score::<f64>(&[], &[]), 1.0);
+        assert_eq!(mean_absolute_error::<f64>(&[], &[]), 0.0);
+    }
+}
@@ -0,0 +1,546 @@
+//! Normalization layers
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use num::{Float, zero, one};
+
+use {Compute, BackpropTrain, Trainable, Parametrized, ModeAware, ShapeError, any_non_finite};
+use training::{GradientRule, RuleState};
+
+/// A batch-normalization layer
+///
+/// In training mode, each feature of a batch is normalized to zero mean and
+/// unit variance over the batch, and then scaled and shifted by the learnable
+/// parameters `gamma` and `beta`:
+///
+/// ```text
+/// Y_i = gamma_i * (X_i - mean_i) / sqrt(var_i + epsilon) + beta_i
+/// ```
+///
+/// The statistics of a batch are only available when processing a whole batch
+/// at once. Training is thus done with `forward_batch()`, which normalizes a
+/// batch with its own statistics, updates running estimates of the mean and
+/// variance of the features with an exponential moving average of factor
+/// `momentum`, and keeps what `backprop_batch()` needs to backpropagate
+/// through the batch statistics afterwards.
+///
+/// `compute()`, which only sees a single sample, as well as `compute_batch()`
+/// and `forward_batch()` in inference mode, normalize using the running
+/// estimates instead. `compute_batch()` in training mode normalizes with the
+/// batch statistics like `forward_batch()`, but leaves the layer untouched.
+pub struct BatchNorm<F: Float> {
+    gamma: Vec<F>,
+    beta: Vec<F>,
+    running_mean: Vec<F>,
+    running_var: Vec<F>,
+    /// The factor of the moving average of the running estimates.
+    pub momentum: F,
+    /// A small value added to the variance to avoid dividing by zero.
+    pub epsilon: F,
+    training: bool,
+    last_batch: Option<BatchStats<F>>,
+    gamma_state: RuleState<F>,
+    beta_state: RuleState<F>
+}
+
+/// What the last training forward pass of a `BatchNorm` leaves for the
+/// backpropagation.
+struct BatchStats<F> {
+    std_dev: Vec<F>,
+    /// The normalized samples, before `gamma` and `beta` are applied.
+    normalized: Vec<Vec<F>>
+}
+
+impl<F: Float> BatchNorm<F> {
+    /// Creates a new batch-normalization layer of `size` features.
+    ///
+    /// `gamma` and `beta` start at 1 and 0, the running estimates of the mean
+    /// and variance at 0 and 1, `momentum` is `0.1` and `epsilon` is `1e-5`.
+    /// The layer starts in training mode.
+    pub fn new(size: usize) -> BatchNorm<F> {
+        BatchNorm {
+            gamma: vec![one(); size],
+            beta: vec![zero(); size],
+            running_mean: vec![zero(); size],
+            running_var: vec![one(); size],
+            momentum: F::from(0.1).unwrap(),
+            epsilon: F::from(1e-5).unwrap(),
+            training: true,
+            last_batch: None,
+            gamma_state: RuleState::new(),
+            beta_state: RuleState::new()
+        }
+    }
+
+    /// Switches the layer between training mode (`true`) and inference
+    /// mode (`false`).
+    ///
+    /// This forgets the batch kept by the last `forward_batch()`.
+    pub fn train_mode(&mut self, training: bool) {
+        self.training = training;
+        self.last_batch = None;
+    }
+
+    /// Whether the layer is in training mode.
+    pub fn is_training(&self) -> bool {
+        self.training
+    }
+
+    /// Get access to the scales of the features.
+    pub fn gamma(&self) -> &[F] {
+        &self.gamma
+    }
+
+    /// Get mutable access to the scales of the features.
+    pub fn gamma_mut(&mut self) -> &mut [F] {
+        &mut self.gamma
+    }
+
+    /// Get access to the shifts of the features.
+    pub fn beta(&self) -> &[F] {
+        &self.beta
+    }
+
+    /// Get mutable access to the shifts of the features.
+    pub fn beta_mut(&mut self) -> &mut [F] {
+        &mut self.beta
+    }
+
+    /// The running estimate of the mean of each feature.
+    pub fn running_mean(&self) -> &[F] {
+        &self.running_mean
+    }
+
+    /// The running estimate of the variance of each feature.
+    pub fn running_var(&self) -> &[F] {
+        &self.running_var
+    }
+
+    /// Processes a batch of inputs for training.
+    ///
+    /// In training mode, the batch is normalized with its own statistics,
+    /// which also update the running estimates, and is kept for a following
+    /// call to `backprop_batch()`. In inference mode, this is the same as
+    /// `compute_batch()`.
+    pub fn forward_batch(&mut self, inputs: &[Vec<F>]) -> Vec<Vec<F>> {
+        if !self.training || inputs.is_empty() {
+            self.last_batch = None;
+            return self.compute_batch(inputs);
+        }
+        let (mean, var) = self.batch_statistics(inputs);
+        for i in 0..self.gamma.len() {
+            self.running_mean[i] = (one::<F>() - self.momentum) * self.running_mean[i] + self.momentum * mean[i];
+            self.running_var[i] = (one::<F>() - self.momentum) * self.running_var[i] + self.momentum * var[i];
+        }
+        let std_dev = var.iter().map(|&v| (v + self.epsilon).sqrt()).collect::<Vec<_>>();
+        let normalized = inputs.iter().map(|input| {
+            (0..self.gamma.len()).map(|i| (input.get(i).cloned().unwrap_or(zero()) - mean[i]) / std_dev[i])
+                                 .collect::<Vec<_>>()
+        }).collect::<Vec<_>>();
+        let outputs = normalized.iter().map(|x| {
+            x.iter().enumerate().map(|(i, &x)| self.gamma[i] * x + self.beta[i]).collect()
+        }).collect();
+        self.last_batch = Some(BatchStats { std_dev, normalized });
+        outputs
+    }
+
+    /// Backpropagates through the batch processed by the last `forward_batch()`
+    /// in training mode, `errors` holding the gradient of the error with
+    /// respect to each of its outputs.
+    ///
+    /// As the statistics of the batch depend on all of its samples, the
+    /// gradient returned for each input accounts for its effect on them:
+    ///
+    /// ```text
+    /// dE/dX_k = gamma / std_dev * ( g_k - mean(g) - Z_k * mean(g * Z) )
+    /// ```
+    ///
+    /// where `g` are the errors and `Z` the normalized inputs. The gradients of
+    /// `gamma` and `beta` are averaged over the batch and applied as a single
+    /// update.
+    ///
+    /// Fails if `errors` does not hold one gradient per sample of that batch,
+    /// and in particular if no such batch was kept.
+    pub fn backprop_batch<R>(&mut self, rule: &R, errors: &[Vec<F>]) -> Result<Vec<Vec<F>>, ShapeError>
+        where R: GradientRule<Float = F>
+    {
+        let stats = match self.last_batch {
+            Some(ref stats) if stats.normalized.len() == errors.len() => stats,
+            ref other => return Err(ShapeError {
+                expected: other.as_ref().map_or(0, |stats| stats.normalized.len()),
+                actual: errors.len()
+            })
+        };
+        let size = self.gamma.len();
+        let n = F::from(errors.len()).unwrap();
+        let error = |k: usize, i: usize| errors[k].get(i).cloned().unwrap_or(zero());
+        let mut returned = vec![vec![zero(); size]; errors.len()];
+        let mut gamma_grad = Vec::with_capacity(size);
+        let mut beta_grad = Vec::with_capacity(size);
+        for i in 0..size {
+            let sum = (0..errors.len()).fold(zero::<F>(), |acc, k| acc + error(k, i));
+            let sum_z = (0..errors.len()).fold(zero::<F>(), |acc, k| acc + error(k, i) * stats.normalized[k][i]);
+            for (k, r) in returned.iter_mut().enumerate() {
+                r[i] = self.gamma[i] / stats.std_dev[i]
+                       * (error(k, i) - sum / n - stats.normalized[k][i] * sum_z / n);
+            }
+            gamma_grad.push(sum_z / n);
+            beta_grad.push(sum / n);
+        }
+        rule.apply(&mut self.gamma_state, &mut self.gamma, &gamma_grad, false);
+        rule.apply(&mut self.beta_state, &mut self.beta, &beta_grad, false);
+        Ok(returned)
+    }
+
+    /// The mean and variance of each feature over a non-empty batch.
+    fn batch_statistics(&self, inputs: &[Vec<F>]) -> (Vec<F>, Vec<F>) {
+        let n = F::from(inputs.len()).unwrap();
+        let feature = |input: &Vec<F>, i: usize| input.get(i).cloned().unwrap_or(zero());
+        let mean = (0..self.gamma.len()).map(|i| {
+            inputs.iter().fold(zero::<F>(), |acc, x| acc + feature(x, i)) / n
+        }).collect::<Vec<_>>();
+        let var = (0..self.gamma.len()).map(|i| {
+            inputs.iter().fold(zero::<F>(), |acc, x| acc + (feature(x, i) - mean[i]).powi(2)) / n
+        }).collect::<Vec<_>>();
+        (mean, var)
+    }
+
+    /// Normalizes `input` with given statistics, and applies `gamma` and `beta`.
+    fn normalize(&self, input: &[F], mean: &[F], var: &[F]) -> Vec<F> {
+        (0..self.gamma.len()).map(|i| {
+            let x = input.get(i).cloned().unwrap_or(zero());
+            self.gamma[i] * (x - mean[i]) / (var[i] + self.epsilon).sqrt() + self.beta[i]
+        }).collect()
+    }
+}
+
+impl<F: Float> Compute<F> for BatchNorm<F> {
+    fn compute(&self, input: &[F]) -> Vec<F> {
+        self.normalize(input, &self.running_mean, &self.running_var)
+    }
+
+    fn compute_batch(&self, inputs: &[Vec<F>]) -> Vec<Vec<F>> {
+        if !self.training || inputs.is_empty() {
+            return inputs.iter().map(|input| self.compute(input)).collect();
+        }
+        let (mean, var) = self.batch_statistics(inputs);
+        inputs.iter().map(|input| self.normalize(input, &mean, &var)).collect()
+    }
+
+    fn input_size(&self) -> usize {
+        self.gamma.len()
+    }
+
+    fn output_size(&self) -> usize {
+        self.gamma.len()
+    }
+}
+
+/// A single sample carries no batch statistics: this backpropagates through
+/// `compute()`, the running estimates being constants, and only trains `gamma`
+/// and `beta`. In training mode, use `backprop_batch()` instead.
+impl<F, R> BackpropTrain<F, R> for BatchNorm<F>
+    where F: Float,
+          R: GradientRule<Float = F>
+{
+    fn backprop_train(&mut self, rule: &R, input: &[F], error: &[F]) -> Vec<F> {
+        let size = self.gamma.len();
+        let mut gamma_grad = Vec::with_capacity(size);
+        let mut beta_grad = Vec::with_capacity(size);
+        let mut returned = Vec::with_capacity(size);
+        for i in 0..size {
+            let std_dev = (self.running_var[i] + self.epsilon).sqrt();
+            let x = input.get(i).cloned().unwrap_or(zero());
+            let grad = error.get(i).cloned().unwrap_or(zero());
+            gamma_grad.push(grad * (x - self.running_mean[i]) / std_dev);
+            beta_grad.push(grad);
+            returned.push(grad * self.gamma[i] / std_dev);
+        }
+        rule.apply(&mut self.gamma_state, &mut self.gamma, &gamma_grad, false);
+        rule.apply(&mut self.beta_state, &mut self.beta, &beta_grad, false);
+        returned
+    }
+}
+
+/// A layer-normalization layer
+///
+/// The features of each input vector are normalized to zero mean and unit
+/// variance, and then scaled and shifted by the learnable parameters `gamma`
+/// and `beta`:
+///
+/// ```text
+/// Y_i = gamma_i * (X_i - mean(X)) / sqrt(var(X) + epsilon) + beta_i
+/// ```
+///
+/// Unlike `BatchNorm`, the statistics are computed over a single sample, so
+/// this layer behaves the same in training and inference.
+pub struct LayerNorm<F: Float> {
+    gamma: Vec<F>,
+    beta: Vec<F>,
+    /// A small value added to the variance to avoid dividing by zero.
+    pub epsilon: F,
+    gamma_state: RuleState<F>,
+    beta_state: RuleState<F>
+}
+
+impl<F: Float> LayerNorm<F> {
+    /// Creates a new layer-normalization layer of `size` features.
+    ///
+    /// `gamma` and `beta` start at 1 and 0, and `epsilon` is `1e-5`.
+    pub fn new(size: usize) -> LayerNorm<F> {
+        LayerNorm {
+            gamma: vec![one(); size],
+            beta: vec![zero(); size],
+            epsilon: F::from(1e-5).unwrap(),
+            gamma_state: RuleState::new(),
+            beta_state: RuleState::new()
+        }
+    }
+
+    /// Get access to the scales of the features.
+    pub fn gamma(&self) -> &[F] {
+        &self.gamma
+    }
+
+    /// Get mutable access to the scales of the features.
+    pub fn gamma_mut(&mut self) -> &mut [F] {
+        &mut self.gamma
+    }
+
+    /// Get access to the shifts of the features.
+    pub fn beta(&self) -> &[F] {
+        &self.beta
+    }
+
+    /// Get mutable access to the shifts of the features.
+    pub fn beta_mut(&mut self) -> &mut [F] {
+        &mut self.beta
+    }
+
+    /// The normalized input, before `gamma` and `beta` are applied, and the
+    /// standard deviation it was divided by.
+    fn normalize(&self, input: &[F]) -> (Vec<F>, F) {
+        let size = self.gamma.len();
+        if size == 0 { return (Vec::new(), one()); }
+        let n = F::from(size).unwrap();
+        let x = (0..size).map(|i| input.get(i).cloned().unwrap_or(zero())).collect::<Vec<_>>();
+        let mean = x.iter().fold(zero::<F>(), |acc, &v| acc + v) / n;
+        let var = x.iter().fold(zero::<F>(), |acc, &v| acc + (v - mean).powi(2)) / n;
+        let std_dev = (var + self.epsilon).sqrt();
+        (x.into_iter().map(|v| (v - mean) / std_dev).collect(), std_dev)
+    }
+}
+
+impl<F: Float> Compute<F> for LayerNorm<F> {
+    fn compute(&self, input: &[F]) -> Vec<F> {
+        self.normalize(input).0.iter().enumerate()
+            .map(|(i, &x)| self.gamma[i] * x + self.beta[i])
+            .collect()
+    }
+
+    fn input_size(&self) -> usize {
+        self.gamma.len()
+    }
+
+    fn output_size(&self) -> usize {
+        self.gamma.len()
+    }
+}
+
+impl<F, R> BackpropTrain<F, R> for LayerNorm<F>
+    where F: Float,
+          R: GradientRule<Float = F>
+{
+    fn backprop_train(&mut self, rule: &R, input: &[F], error: &[F]) -> Vec<F> {
+        let size = self.gamma.len();
+        if size == 0 { return Vec::new(); }
+        let n = F::from(size).unwrap();
+        let (normalized, std_dev) = self.normalize(input);
+        let grad = (0..size).map(|i| error.get(i).cloned().unwrap_or(zero())).collect::<Vec<_>>();
+
+        // gradient with respect to the normalized values
+        let grad_normalized = (0..size).map(|i| grad[i] * self.gamma[i]).collect::<Vec<_>>();
+        let mean_grad = grad_normalized.iter().fold(zero::<F>(), |acc, &g| acc + g) / n;
+        let mean_grad_x = grad_normalized.iter().zip(normalized.iter())
+                                         .fold(zero::<F>(), |acc, (&g, &x)| acc + g * x) / n;
+        let returned = (0..size).map(|i| {
+            (grad_normalized[i] - mean_grad - normalized[i] * mean_grad_x) / std_dev
+        }).collect();
+
+        let gamma_grad = (0..size).map(|i| grad[i] * normalized[i]).collect::<Vec<_>>();
+        rule.apply(&mut self.gamma_state, &mut self.gamma, &gamma_grad, false);
+        rule.apply(&mut self.beta_state, &mut self.beta, &grad, false);
+        returned
+    }
+}
+
+impl<F: Float> ModeAware for BatchNorm<F> {
+    fn set_mode(&mut self, training: bool) {
+        self.train_mode(training);
+    }
+}
+
+impl<F: Float> Trainable for BatchNorm<F> {
+    fn num_parameters(&self) -> usize {
+        self.gamma.len() + self.beta.len()
+    }
+
+    fn has_nan(&self) -> bool {
+        any_non_finite(&self.gamma) || any_non_finite(&self.beta)
+    }
+}
+
+impl<F: Float> Parametrized<F> for BatchNorm<F> {
+    fn parameters_iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a F> + 'a> {
+        Box::new(self.gamma.iter().chain(self.beta.iter()))
+    }
+
+    fn parameters_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = &'a mut F> + 'a> {
+        Box::new(self.gamma.iter_mut().chain(self.beta.iter_mut()))
+    }
+}
+
+impl<F: Float> ModeAware for LayerNorm<F> {}
+
+impl<F: Float> Trainable for LayerNorm<F> {
+    fn num_parameters(&self) -> usize {
+        self.gamma.len() + self.beta.len()
+    }
+
+    fn has_nan(&self) -> bool {
+        any_non_finite(&self.gamma) || any_non_finite(&self.beta)
+    }
+}
+
+impl<F: Float> Parametrized<F> for LayerNorm<F> {
+    fn parameters_iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a F> + 'a> {
+        Box::new(self.gamma.iter().chain(self.beta.iter()))
+    }
+
+    fn parameters_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = &'a mut F> + 'a> {
+        Box::new(self.gamma.iter_mut().chain(self.beta.iter_mut()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {Compute, BackpropTrain};
+    use diagnostics::gradient_check;
+    use loss::MeanSquaredError;
+    use training::GradientDescent;
+
+    use super::{BatchNorm, LayerNorm};
+
+    #[test]
+    fn normalized_batch() {
+        let mut layer = BatchNorm::new(2);
+        let batch = vec![vec![1.0f64, 10.0], vec![2.0, -4.0], vec![6.0, 3.0], vec![-1.0, 7.0]];
+        let output = layer.compute_batch(&batch);
+        // computing alone does not touch the running estimates
+        assert_eq!(layer.running_mean(), &[0.0, 0.0]);
+        assert_eq!(layer.forward_batch(&batch), output);
+        for i in 0..2 {
+            let mean = output.iter().map(|y| y[i]).sum::<f64>() / 4.0;
+            let var = output.iter().map(|y| (y[i] - mean).powi(2)).sum::<f64>() / 4.0;
+            assert!(mean.abs() < 1e-12);
+            assert!((var - 1.0).abs() < 1e-4);
+        }
+        // the running estimates moved towards the batch statistics
+        assert!((layer.running_mean()[0] - 0.2).abs() < 1e-12);
+    }
+
+    #[test]
+    fn inference_after_warmup() {
+        let mut layer = BatchNorm::new(2);
+        let batch = vec![vec![3.0f64, -1.0], vec![1.0, 1.0]];
+        for _ in 0..200 {
+            layer.forward_batch(&batch);
+        }
+        layer.train_mode(false);
+        assert_eq!(layer.compute_batch(&batch), vec![layer.compute(&batch[0]), layer.compute(&batch[1])]);
+        // the second feature has mean 0 and variance 1, and is left unchanged
+        let output = layer.compute(&[2.5, 0.5]);
+        assert!((output[0] - 0.5).abs() < 1e-4);
+        assert!((output[1] - 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn backprop_train() {
+        let mut layer = BatchNorm::new(2);
+        layer.train_mode(false);
+        let returned = layer.backprop_train(&GradientDescent::new(0.5), &[2.0f64, -1.0], &[1.0, 1.0]);
+        assert!((returned[0] - 1.0).abs() < 1e-4);
+        assert!((layer.gamma()[0] - 0.0).abs() < 1e-4);
+        assert!((layer.gamma()[1] - 1.5).abs() < 1e-4);
+        assert_eq!(layer.beta(), &[-0.5, -0.5]);
+    }
+
+    #[test]
+    fn backprop_batch() {
+        let mut layer = BatchNorm::new(2);
+        layer.gamma_mut().copy_from_slice(&[0.5, -2.0]);
+        layer.beta_mut().copy_from_slice(&[0.1, 0.2]);
+        let batch = vec![vec![1.0f64, 10.0], vec![2.0, -4.0], vec![6.0, 3.0]];
+        let errors = vec![vec![1.0f64, -0.5], vec![0.25, 2.0], vec![-1.0, 0.5]];
+        let loss = |layer: &BatchNorm<f64>, batch: &[Vec<f64>]| {
+            layer.compute_batch(batch).iter().zip(&errors)
+                 .map(|(y, e)| y.iter().zip(e).map(|(y, e)| y * e).sum::<f64>())
+                 .sum::<f64>()
+        };
+        let eps = 1e-6;
+        let old_gamma = layer.gamma().to_vec();
+        let mut gamma_grad = Vec::new();
+        for i in 0..2 {
+            let g = layer.gamma[i];
+            layer.gamma[i] = g + eps;
+            let plus = loss(&layer, &batch);
+            layer.gamma[i] = g - eps;
+            let minus = loss(&layer, &batch);
+            layer.gamma[i] = g;
+            gamma_grad.push((plus - minus) / (2.0 * eps));
+        }
+
+        assert!(layer.backprop_batch(&GradientDescent::new(1.0), &errors).is_err());
+        layer.forward_batch(&batch);
+        assert!(layer.backprop_batch(&GradientDescent::new(1.0), &errors[..2]).is_err());
+        let returned = layer.backprop_batch(&GradientDescent::new(1.0), &errors).unwrap();
+        // the input gradient goes through the batch statistics
+        for k in 0..3 {
+            for i in 0..2 {
+                let (mut plus, mut minus) = (batch.clone(), batch.clone());
+                plus[k][i] += eps;
+                minus[k][i] -= eps;
+                let mut reference = BatchNorm::new(2);
+                reference.gamma_mut().copy_from_slice(&old_gamma);
+                reference.beta_mut().copy_from_slice(&[0.1, 0.2]);
+                let num = (loss(&reference, &plus) - loss(&reference, &minus)) / (2.0 * eps);
+                assert!((returned[k][i] - num).abs() < 1e-6);
+            }
+        }
+        // the parameters follow the gradient averaged over the batch
+        for i in 0..2 {
+            assert!((old_gamma[i] - layer.gamma()[i] - gamma_grad[i] / 3.0).abs() < 1e-6);
+        }
+        assert!((layer.beta()[0] - (0.1 - 0.25 / 3.0)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn layer_norm() {
+        let layer = LayerNorm::new(4);
+        let output = layer.compute(&[1.0f64, 5.0, -3.0, 2.0]);
+        let mean = output.iter().sum::<f64>() / 4.0;
+        let var = output.iter().map(|y| (y - mean).powi(2)).sum::<f64>() / 4.0;
+        assert!(mean.abs() < 1e-12);
+        assert!((var - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn layer_norm_gradient_check() {
+        let mut layer = LayerNorm::new(3);
+        layer.gamma_mut().copy_from_slice(&[0.5, -1.0, 2.0]);
+        layer.beta_mut().copy_from_slice(&[0.1, 0.2, 0.3]);
+        let error = gradient_check(&mut layer, &MeanSquaredError, &[0.3f64, -0.8, 1.5], &[1.0, -0.5, 0.25], 1e-6);
+        assert!(error < 1e-4);
+    }
+}
@@ -0,0 +1,79 @@
+//! Parallel computation, using rayon.
+//!
+//! This module is only available with the `rayon` feature, with which
+//! `FeedforwardLayer` also spreads large layers and batches over threads in
+//! its `Compute` implementation. As the `Compute` trait does not require
+//! networks to be shared between threads, the parallel version of its batch
+//! method is provided here for the other networks that can.
+//!
+//! Each output is computed by the same sequence of operations as in the serial
+//! versions, so the results do not depend on the number of threads.
+
+use alloc::vec::Vec;
+
+use num::Float;
+use rayon::prelude::*;
+
+use Compute;
+
+/// Batches smaller than this are computed serially, as the cost of spreading
+/// them over threads would exceed the gain.
+pub const MIN_PARALLEL_BATCH: usize = 16;
+
+/// Feedforward layers with fewer outputs than this are computed serially.
+pub const MIN_PARALLEL_OUTPUTS: usize = 256;
+
+/// Computes the output of `network` for each of the `inputs`, spreading the
+/// batch over the threads of the rayon pool.
+///
+/// The result is the same as `network.compute_batch(inputs)`, to which small
+/// batches are delegated.
+pub fn par_compute_batch<F, N>(network: &N, inputs: &[Vec<F>]) -> Vec<Vec<F>>
+    where F: Float + Send + Sync,
+          N: Compute<F> + Sync
+{
+    if inputs.len() < MIN_PARALLEL_BATCH {
+        return network.compute_batch(inputs);
+    }
+    inputs.par_iter().map(|input| network.compute(input)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use Compute;
+    use activations::sigmoid;
+    use feedforward::FeedforwardLayer;
+    use testing::random;
+
+    use super::{par_compute_batch, MIN_PARALLEL_BATCH, MIN_PARALLEL_OUTPUTS};
+
+    /// The output of `layer` computed serially, one input at a time.
+    fn serial<N: Compute<f64>>(layer: &N, input: &[f64]) -> Vec<f64> {
+        let mut out = vec![0.0; layer.output_size()];
+        layer.compute_into(input, &mut out);
+        out
+    }
+
+    #[test]
+    fn batch() {
+        let layer = FeedforwardLayer::new_from(5, 3, sigmoid(), random());
+        let mut gen = random::<f64>();
+        for &n in &[3, MIN_PARALLEL_BATCH * 4] {
+            let inputs = (0..n).map(|_| (0..5).map(|_| gen() * 0.3).collect()).collect::<Vec<Vec<f64>>>();
+            let expected = inputs.iter().map(|input| serial(&layer, input)).collect::<Vec<_>>();
+            assert_eq!(layer.compute_batch(&inputs), expected);
+            assert_eq!(par_compute_batch(&layer, &inputs), expected);
+        }
+    }
+
+    #[test]
+    fn rows() {
+        let mut gen = random();
+        for &outputs in &[4, MIN_PARALLEL_OUTPUTS * 2] {
+            let layer = FeedforwardLayer::new_from(7, outputs, sigmoid(), random());
+            let input = (0..7).map(|_| gen()).collect::<Vec<f64>>();
+            assert_eq!(layer.compute(&input), serial(&layer, &input));
+            assert_eq!(layer.par_compute(&input), serial(&layer, &input));
+        }
+    }
+}
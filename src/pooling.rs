@@ -0,0 +1,244 @@
+//! Pooling layers
+
+use alloc::vec::Vec;
+
+use num::{Float, zero};
+
+use {Compute, BackpropTrain, Method, Trainable, Parametrized, ModeAware};
+
+/// A 1-D max-pooling layer
+///
+/// The input is made of `channels` signals of `length` samples each, stored
+/// one channel after the other. In each channel, a window of `window` samples
+/// is slid with step `stride`, and the maximum value of each window is output,
+/// in the same channel-major layout.
+///
+/// `backprop_train()` finds the position of the maximum of each window in the
+/// input it is given, and routes the gradient of each output to the input it
+/// came from only, all the other inputs of
+/// the window getting a zero gradient. Nothing is learned by the pooling
+/// itself, so it accepts any training method.
+pub struct MaxPool1D<F: Float> {
+    channels: usize,
+    length: usize,
+    window: usize,
+    stride: usize,
+    _marker: ::core::marker::PhantomData<F>
+}
+
+impl<F: Float> MaxPool1D<F> {
+    /// Creates a new max-pooling layer.
+    ///
+    /// Panics if `window` or `stride` is 0.
+    pub fn new(channels: usize, length: usize, window: usize, stride: usize) -> MaxPool1D<F> {
+        assert!(window > 0 && stride > 0, "The window and stride of a pooling cannot be 0.");
+        MaxPool1D {
+            channels,
+            length,
+            window,
+            stride,
+            _marker: ::core::marker::PhantomData
+        }
+    }
+
+    /// The index in `input` of the maximum of each window, in output order.
+    ///
+    /// The first maximum of a window is picked in case of ties.
+    fn argmax(&self, input: &[F]) -> Vec<usize> {
+        let out_length = out_length(self.length, self.window, self.stride);
+        let mut argmax = Vec::with_capacity(self.channels * out_length);
+        for c in 0..self.channels {
+            for i in 0..out_length {
+                let start = c*self.length + i*self.stride;
+                let mut best = start;
+                let mut max = input.get(start).cloned().unwrap_or(zero());
+                for k in start+1..start+self.window {
+                    let x = input.get(k).cloned().unwrap_or(zero());
+                    if x > max {
+                        best = k;
+                        max = x;
+                    }
+                }
+                argmax.push(best);
+            }
+        }
+        argmax
+    }
+}
+
+impl<F: Float> Compute<F> for MaxPool1D<F> {
+    fn compute(&self, input: &[F]) -> Vec<F> {
+        self.argmax(input).into_iter().map(|i| input.get(i).cloned().unwrap_or(zero())).collect()
+    }
+
+    fn input_size(&self) -> usize {
+        self.channels * self.length
+    }
+
+    fn output_size(&self) -> usize {
+        self.channels * out_length(self.length, self.window, self.stride)
+    }
+}
+
+impl<F: Float, M: Method> BackpropTrain<F, M> for MaxPool1D<F> {
+    fn backprop_train(&mut self, _rule: &M, input: &[F], error: &[F]) -> Vec<F> {
+        let mut returned = vec![zero(); self.input_size()];
+        for (j, i) in self.argmax(input).into_iter().enumerate() {
+            returned[i] = returned[i] + error.get(j).cloned().unwrap_or(zero());
+        }
+        returned
+    }
+}
+
+/// A 1-D average-pooling layer
+///
+/// The input is made of `channels` signals of `length` samples each, stored
+/// one channel after the other. In each channel, a window of `window` samples
+/// is slid with step `stride`, and the mean value of each window is output,
+/// in the same channel-major layout.
+///
+/// If the windows do not evenly cover a channel, the trailing samples that do
+/// not fill a whole window are ignored, and get a zero gradient. The gradient
+/// of each output is spread evenly over its window, and as there is nothing
+/// to learn, any training method is accepted.
+pub struct AvgPool1D<F: Float> {
+    channels: usize,
+    length: usize,
+    window: usize,
+    stride: usize,
+    _marker: ::core::marker::PhantomData<F>
+}
+
+impl<F: Float> AvgPool1D<F> {
+    /// Creates a new average-pooling layer.
+    ///
+    /// Panics if `window` or `stride` is 0.
+    pub fn new(channels: usize, length: usize, window: usize, stride: usize) -> AvgPool1D<F> {
+        assert!(window > 0 && stride > 0, "The window and stride of a pooling cannot be 0.");
+        AvgPool1D {
+            channels,
+            length,
+            window,
+            stride,
+            _marker: ::core::marker::PhantomData
+        }
+    }
+}
+
+impl<F: Float> Compute<F> for AvgPool1D<F> {
+    fn compute(&self, input: &[F]) -> Vec<F> {
+        let out_length = out_length(self.length, self.window, self.stride);
+        let n = F::from(self.window).unwrap();
+        let mut output = Vec::with_capacity(self.channels * out_length);
+        for c in 0..self.channels {
+            for i in 0..out_length {
+                let start = c*self.length + i*self.stride;
+                let sum = (start..start+self.window)
+                    .fold(zero::<F>(), |acc, k| acc + input.get(k).cloned().unwrap_or(zero()));
+                output.push(sum / n);
+            }
+        }
+        output
+    }
+
+    fn input_size(&self) -> usize {
+        self.channels * self.length
+    }
+
+    fn output_size(&self) -> usize {
+        self.channels * out_length(self.length, self.window, self.stride)
+    }
+}
+
+impl<F: Float, M: Method> BackpropTrain<F, M> for AvgPool1D<F> {
+    fn backprop_train(&mut self, _rule: &M, _input: &[F], error: &[F]) -> Vec<F> {
+        let out_length = out_length(self.length, self.window, self.stride);
+        let n = F::from(self.window).unwrap();
+        let mut returned = vec![zero(); self.input_size()];
+        for c in 0..self.channels {
+            for i in 0..out_length {
+                let grad = error.get(c*out_length + i).cloned().unwrap_or(zero()) / n;
+                let start = c*self.length + i*self.stride;
+                for x in &mut returned[start..start+self.window] {
+                    *x = *x + grad;
+                }
+            }
+        }
+        returned
+    }
+}
+
+/// The number of windows of `window` samples with step `stride` fitting in
+/// `length` samples, a trailing partial window being ignored.
+fn out_length(length: usize, window: usize, stride: usize) -> usize {
+    if length < window { 0 } else { (length - window) / stride + 1 }
+}
+
+impl<F: Float> ModeAware for MaxPool1D<F> {}
+
+impl<F: Float> Trainable for MaxPool1D<F> {}
+
+impl<F: Float> ModeAware for AvgPool1D<F> {}
+
+impl<F: Float> Trainable for AvgPool1D<F> {}
+
+impl<F: Float> Parametrized<F> for MaxPool1D<F> {}
+
+impl<F: Float> Parametrized<F> for AvgPool1D<F> {}
+
+#[cfg(test)]
+mod tests {
+    use {Compute, BackpropTrain};
+    use training::GradientDescent;
+
+    use super::{MaxPool1D, AvgPool1D};
+
+    #[test]
+    fn max_pool() {
+        let mut layer = MaxPool1D::new(2, 5, 2, 2);
+        assert_eq!(layer.input_size(), 10);
+        assert_eq!(layer.output_size(), 4);
+        let input = [1.0f32, 3.0, -1.0, -2.0, 7.0, 0.5, 0.2, 4.0, 6.0, 9.0];
+        assert_eq!(layer.compute(&input), vec![3.0, -1.0, 0.5, 6.0]);
+        let returned = layer.backprop_train(&GradientDescent::new(0.1), &input, &[1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(returned, vec![0.0, 1.0, 2.0, 0.0, 0.0, 3.0, 0.0, 0.0, 4.0, 0.0]);
+    }
+
+    #[test]
+    fn overlapping_windows() {
+        let mut layer = MaxPool1D::new(1, 4, 3, 1);
+        let input = [1.0f32, 5.0, 2.0, 0.0];
+        assert_eq!(layer.compute(&input), vec![5.0, 5.0]);
+        let returned = layer.backprop_train(&GradientDescent::new(0.1), &input, &[1.0, 2.0]);
+        assert_eq!(returned, vec![0.0, 3.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn backprop_follows_input() {
+        let mut layer = MaxPool1D::new(1, 4, 2, 2);
+        layer.compute(&[1.0f32, 0.0, 0.0, 1.0]);
+        // the gradient is routed according to the input given to the
+        // backpropagation, not the one of the last forward pass
+        let returned = layer.backprop_train(&GradientDescent::new(0.1), &[0.0, 1.0, 1.0, 0.0], &[1.0, 2.0]);
+        assert_eq!(returned, vec![0.0, 1.0, 2.0, 0.0]);
+    }
+
+    #[test]
+    fn avg_pool() {
+        let mut layer = AvgPool1D::new(2, 5, 2, 2);
+        assert_eq!(layer.output_size(), 4);
+        let input = [1.0f32, 3.0, -1.0, -2.0, 7.0, 0.5, 0.25, 4.0, 6.0, 9.0];
+        assert_eq!(layer.compute(&input), vec![2.0, -1.5, 0.375, 5.0]);
+        let returned = layer.backprop_train(&GradientDescent::new(0.1), &input, &[1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(returned, vec![0.5, 0.5, 1.0, 1.0, 0.0, 1.5, 1.5, 2.0, 2.0, 0.0]);
+    }
+
+    #[test]
+    fn avg_pool_overlapping() {
+        let mut layer = AvgPool1D::new(1, 4, 2, 1);
+        let input = [1.0f32, 3.0, 2.0, 0.0];
+        assert_eq!(layer.compute(&input), vec![2.0, 2.5, 1.0]);
+        let returned = layer.backprop_train(&GradientDescent::new(0.1), &input, &[1.0, 2.0, 4.0]);
+        assert_eq!(returned, vec![0.5, 1.5, 3.0, 2.0]);
+    }
+}
@@ -0,0 +1,141 @@
+//! Parametric rectified linear units
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use num::{Float, zero};
+
+use {Compute, BackpropTrain, Trainable, Parametrized, ModeAware, any_non_finite};
+use training::{GradientRule, RuleState};
+
+/// A layer of parametric rectified linear units.
+///
+/// Each unit outputs its input if it is positive, and `alpha` times its
+/// input otherwise, `alpha` being a trainable parameter specific to
+/// each unit:
+///
+/// ```text
+/// y_i = x_i              if x_i >= 0
+/// y_i = alpha_i * x_i    otherwise
+/// ```
+pub struct PReLULayer<F: Float> {
+    alphas: Vec<F>,
+    state: RuleState<F>
+}
+
+impl<F: Float> PReLULayer<F> {
+    /// Creates a new layer of `size` units, with all their slopes set to `alpha`.
+    pub fn new(size: usize, alpha: F) -> PReLULayer<F> {
+        PReLULayer {
+            alphas: vec![alpha; size],
+            state: RuleState::new()
+        }
+    }
+
+    /// The current negative-side slopes of the units.
+    pub fn alphas(&self) -> &[F] {
+        &self.alphas
+    }
+}
+
+impl<F: Float> Compute<F> for PReLULayer<F> {
+    fn compute(&self, input: &[F]) -> Vec<F> {
+        self.alphas.iter().enumerate().map(|(i, &alpha)| {
+            let x = input.get(i).cloned().unwrap_or(zero());
+            if x >= zero() { x } else { alpha * x }
+        }).collect()
+    }
+
+    fn input_size(&self) -> usize {
+        self.alphas.len()
+    }
+
+    fn output_size(&self) -> usize {
+        self.alphas.len()
+    }
+}
+
+/// The gradient of the error with respect to each slope is `dE/dy_i * x_i`
+/// for a negative input, and 0 otherwise. The slopes are not connection
+/// weights, so rules like `AdamW` do not decay them.
+impl<F, R> BackpropTrain<F, R> for PReLULayer<F>
+    where F: Float,
+          R: GradientRule<Float = F>
+{
+    fn backprop_train(&mut self, rule: &R, input: &[F], error: &[F]) -> Vec<F> {
+        let mut returned = Vec::with_capacity(self.alphas.len());
+        let mut grads = Vec::with_capacity(self.alphas.len());
+        for (i, &alpha) in self.alphas.iter().enumerate() {
+            let x = input.get(i).cloned().unwrap_or(zero());
+            let grad = error.get(i).cloned().unwrap_or(zero());
+            if x >= zero() {
+                returned.push(grad);
+                grads.push(zero());
+            } else {
+                returned.push(grad * alpha);
+                grads.push(grad * x);
+            }
+        }
+        rule.apply(&mut self.state, &mut self.alphas, &grads, false);
+        returned
+    }
+}
+
+impl<F: Float> ModeAware for PReLULayer<F> {}
+
+impl<F: Float> Trainable for PReLULayer<F> {
+    fn num_parameters(&self) -> usize {
+        self.alphas.len()
+    }
+
+    fn has_nan(&self) -> bool {
+        any_non_finite(&self.alphas)
+    }
+}
+
+impl<F: Float> Parametrized<F> for PReLULayer<F> {
+    fn parameters_iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a F> + 'a> {
+        Box::new(self.alphas.iter())
+    }
+
+    fn parameters_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = &'a mut F> + 'a> {
+        Box::new(self.alphas.iter_mut())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {Compute, BackpropTrain};
+    use training::GradientDescent;
+
+    use super::PReLULayer;
+
+    #[test]
+    fn compute() {
+        let layer = PReLULayer::new(3, 0.25f32);
+        assert_eq!(layer.compute(&[-4.0, 0.0, 2.0]), [-1.0f32, 0.0, 2.0]);
+    }
+
+    #[test]
+    fn backprop_train() {
+        let mut layer = PReLULayer::new(2, 0.25f32);
+        let rule = GradientDescent::new(0.1f32);
+        let grad = layer.backprop_train(&rule, &[-2.0, 3.0], &[1.0, 1.0]);
+        // input-side gradient uses the slope in effect for each input
+        assert_eq!(grad, [0.25f32, 1.0]);
+        // a positive gradient asks for a smaller output, so the slope of the
+        // negative input must increase, the other one being left untouched
+        assert!(layer.alphas()[0] > 0.25);
+        assert_eq!(layer.alphas()[1], 0.25);
+    }
+
+    #[test]
+    fn clipped_gradient() {
+        let mut layer = PReLULayer::new(2, 0.25f32);
+        let rule = GradientDescent { rate: 0.1, clip: Some(1.0) };
+        layer.backprop_train(&rule, &[-2.0, -3.0], &[1e6, -1e6]);
+        for &alpha in layer.alphas() {
+            assert!((alpha - 0.25).abs() <= 0.1 * 1.0 + 1e-6);
+        }
+    }
+}
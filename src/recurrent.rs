@@ -0,0 +1,1138 @@
+//! Recurrent layers
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+use num::{Float, one, zero};
+
+use {Compute, Trainable, Parametrized, ModeAware, any_non_finite};
+use activations::ActivationFunction;
+use training::{GradientRule, RuleState};
+
+/// A simple recurrent layer (Elman network)
+///
+/// This layer keeps a hidden state `H` of `hidden` values, which is both its
+/// output and an additional input for the next step. At each step, if `X` is
+/// the vector of inputs, `Wx` and `Wh` the weight matrices of the inputs and
+/// of the hidden state, `B` the vector of biases and `f()` the activation
+/// function:
+///
+/// ```text
+/// H_t = f( Wx*X_t + Wh*H_{t-1} + B )
+/// ```
+///
+/// As the layer is stateful, each call to `compute()` advances time by one
+/// step: feeding the same input twice generally gives different outputs. Use
+/// `reset_state()` to start a new sequence from a zero hidden state.
+///
+/// It is trained on whole sequences with `train_sequence()`, using
+/// backpropagation through time.
+pub struct SimpleRNN<F: Float, V: Fn(F) -> F, D: Fn(F) -> F> {
+    inputs: usize,
+    input_coeffs: Vec<F>,
+    hidden_coeffs: Vec<F>,
+    biases: Vec<F>,
+    activation: ActivationFunction<F, V, D>,
+    state: RefCell<Vec<F>>,
+    input_state: RuleState<F>,
+    hidden_state: RuleState<F>,
+    biases_state: RuleState<F>
+}
+
+impl<F, V, D> SimpleRNN<F, V, D>
+    where F: Float,
+          V: Fn(F) -> F,
+          D: Fn(F) -> F
+{
+    /// Creates a new recurrent layer with all its weights and biases set to 0.
+    pub fn new(inputs: usize,
+               hidden: usize,
+               activation: ActivationFunction<F, V, D>)
+        -> SimpleRNN<F, V, D>
+    {
+        SimpleRNN::new_from(inputs, hidden, activation, zero)
+    }
+
+    /// Creates a new recurrent layer with all its weights and biases generated
+    /// by provided closure (for example a random number generator).
+    pub fn new_from<G>(inputs: usize,
+                       hidden: usize,
+                       activation: ActivationFunction<F, V, D>,
+                       mut generator: G)
+        -> SimpleRNN<F, V, D>
+        where G: FnMut() -> F
+    {
+        SimpleRNN {
+            inputs,
+            input_coeffs: (0..hidden*inputs).map(|_| generator()).collect(),
+            hidden_coeffs: (0..hidden*hidden).map(|_| generator()).collect(),
+            biases: (0..hidden).map(|_| generator()).collect(),
+            activation,
+            state: RefCell::new(vec![zero(); hidden]),
+            input_state: RuleState::new(),
+            hidden_state: RuleState::new(),
+            biases_state: RuleState::new()
+        }
+    }
+
+    /// Get access to the weights applied to the inputs.
+    ///
+    /// The weight from input `i` to hidden unit `j` is at index `j*inputs + i`.
+    pub fn input_weights(&self) -> &[F] {
+        &self.input_coeffs
+    }
+
+    /// Get mutable access to the weights applied to the inputs.
+    pub fn input_weights_mut(&mut self) -> &mut [F] {
+        &mut self.input_coeffs
+    }
+
+    /// Get access to the weights applied to the previous hidden state.
+    ///
+    /// The weight from hidden unit `i` to hidden unit `j` is at index
+    /// `j*hidden + i`.
+    pub fn hidden_weights(&self) -> &[F] {
+        &self.hidden_coeffs
+    }
+
+    /// Get mutable access to the weights applied to the previous hidden state.
+    pub fn hidden_weights_mut(&mut self) -> &mut [F] {
+        &mut self.hidden_coeffs
+    }
+
+    /// Get access to the biases of this layer, one per hidden unit.
+    pub fn biases(&self) -> &[F] {
+        &self.biases
+    }
+
+    /// Get mutable access to the biases of this layer.
+    pub fn biases_mut(&mut self) -> &mut [F] {
+        &mut self.biases
+    }
+
+    /// The current hidden state, the output of the last step.
+    pub fn state(&self) -> Vec<F> {
+        self.state.borrow().clone()
+    }
+
+    /// Clears the hidden state, to start a new sequence.
+    pub fn reset_state(&mut self) {
+        for h in self.state.get_mut().iter_mut() {
+            *h = zero();
+        }
+    }
+
+    /// Performs one step of training over a whole sequence, using
+    /// backpropagation through time.
+    ///
+    /// The sequence of `inputs` is processed starting from the current hidden
+    /// state, and `errors` contains, for each step, the gradient of the error
+    /// with respect to the hidden state output at this step. The gradients of
+    /// all steps are accumulated and applied in a single update using `rule`.
+    ///
+    /// As with `compute()`, the hidden state is left at the last step of the
+    /// sequence. Returns the gradient of the error with respect to the input
+    /// of each step.
+    pub fn train_sequence<R>(&mut self, rule: &R, inputs: &[Vec<F>], errors: &[Vec<F>]) -> Vec<Vec<F>>
+        where R: GradientRule<Float = F>
+    {
+        let hidden = self.biases.len();
+        // forward pass, keeping the pre-activations and hidden states
+        let mut states = vec![self.state()];
+        let mut pre_activations = Vec::with_capacity(inputs.len());
+        for input in inputs {
+            let pre = self.pre_activations(input, &states[states.len()-1]);
+            states.push(pre.iter().map(|&x| (self.activation.value)(x)).collect());
+            pre_activations.push(pre);
+        }
+
+        // backward pass
+        let mut input_grad = vec![zero(); self.input_coeffs.len()];
+        let mut hidden_grad = vec![zero(); self.hidden_coeffs.len()];
+        let mut biases_grad = vec![zero(); hidden];
+        let mut returned = vec![Vec::new(); inputs.len()];
+        let mut next_error = vec![zero(); hidden];
+        for t in (0..inputs.len()).rev() {
+            let deltas = (0..hidden).map(|j| {
+                let error = errors.get(t).and_then(|e| e.get(j)).cloned().unwrap_or(zero());
+                (self.activation.derivative)(pre_activations[t][j]) * (error + next_error[j])
+            }).collect::<Vec<_>>();
+            let mut input_error = vec![zero(); self.inputs];
+            for h in &mut next_error { *h = zero(); }
+            for j in 0..hidden {
+                biases_grad[j] = biases_grad[j] + deltas[j];
+                for i in 0..self.inputs {
+                    let x = inputs[t].get(i).cloned().unwrap_or(zero());
+                    input_grad[j*self.inputs + i] = input_grad[j*self.inputs + i] + deltas[j] * x;
+                    input_error[i] = input_error[i] + self.input_coeffs[j*self.inputs + i] * deltas[j];
+                }
+                for i in 0..hidden {
+                    hidden_grad[j*hidden + i] = hidden_grad[j*hidden + i] + deltas[j] * states[t][i];
+                    next_error[i] = next_error[i] + self.hidden_coeffs[j*hidden + i] * deltas[j];
+                }
+            }
+            returned[t] = input_error;
+        }
+
+        rule.apply(&mut self.input_state, &mut self.input_coeffs, &input_grad, true);
+        rule.apply(&mut self.hidden_state, &mut self.hidden_coeffs, &hidden_grad, true);
+        rule.apply(&mut self.biases_state, &mut self.biases, &biases_grad, false);
+        if let Some(last) = states.pop() {
+            self.state.get_mut().copy_from_slice(&last);
+        }
+        returned
+    }
+
+    /// The values of the hidden units before the activation function, for
+    /// given input and previous hidden state.
+    fn pre_activations(&self, input: &[F], previous: &[F]) -> Vec<F> {
+        let hidden = self.biases.len();
+        (0..hidden).map(|j| {
+            let mut acc = self.biases[j];
+            for (i, &x) in input.iter().take(self.inputs).enumerate() {
+                acc = acc + self.input_coeffs[j*self.inputs + i] * x;
+            }
+            for (i, &h) in previous.iter().enumerate() {
+                acc = acc + self.hidden_coeffs[j*hidden + i] * h;
+            }
+            acc
+        }).collect()
+    }
+}
+
+impl<F, V, D> Compute<F> for SimpleRNN<F, V, D>
+    where F: Float,
+          V: Fn(F) -> F,
+          D: Fn(F) -> F
+{
+    /// Advances the layer by one step, returning the new hidden state.
+    fn compute(&self, input: &[F]) -> Vec<F> {
+        let mut state = self.state.borrow_mut();
+        let next = self.pre_activations(input, &state)
+                       .into_iter()
+                       .map(|x| (self.activation.value)(x))
+                       .collect::<Vec<_>>();
+        state.copy_from_slice(&next);
+        next
+    }
+
+    fn input_size(&self) -> usize {
+        self.inputs
+    }
+
+    fn output_size(&self) -> usize {
+        self.biases.len()
+    }
+}
+
+impl<F, V, D> ModeAware for SimpleRNN<F, V, D>
+    where F: Float,
+          V: Fn(F) -> F,
+          D: Fn(F) -> F
+{}
+
+impl<F, V, D> Trainable for SimpleRNN<F, V, D>
+    where F: Float,
+          V: Fn(F) -> F,
+          D: Fn(F) -> F
+{
+    fn num_parameters(&self) -> usize {
+        self.input_coeffs.len() + self.hidden_coeffs.len() + self.biases.len()
+    }
+
+    fn has_nan(&self) -> bool {
+        any_non_finite(&self.input_coeffs) || any_non_finite(&self.hidden_coeffs) || any_non_finite(&self.biases)
+    }
+}
+
+impl<F, V, D> Parametrized<F> for SimpleRNN<F, V, D>
+    where F: Float,
+          V: Fn(F) -> F,
+          D: Fn(F) -> F
+{
+    fn parameters_iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a F> + 'a> {
+        Box::new(self.input_coeffs.iter().chain(self.hidden_coeffs.iter()).chain(self.biases.iter()))
+    }
+
+    fn parameters_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = &'a mut F> + 'a> {
+        Box::new(self.input_coeffs.iter_mut().chain(self.hidden_coeffs.iter_mut()).chain(self.biases.iter_mut()))
+    }
+}
+
+/// The logistic function, used by the gates of the recurrent cells.
+fn logistic<F: Float>(x: F) -> F {
+    one::<F>() / (one::<F>() + (-x).exp())
+}
+
+/// The parameters of a gated recurrent cell: for each of its `gates`, a weight
+/// matrix applied to the input, another one applied to (a function of) the
+/// hidden state, and a vector of biases.
+///
+/// The matrices of all the gates are stacked: the row of unit `j` of gate `g`
+/// is the row `g*hidden + j`.
+struct Gates<F: Float> {
+    inputs: usize,
+    hidden: usize,
+    input_coeffs: Vec<F>,
+    hidden_coeffs: Vec<F>,
+    biases: Vec<F>,
+    input_state: RuleState<F>,
+    hidden_state: RuleState<F>,
+    biases_state: RuleState<F>
+}
+
+/// The accumulated gradients of the parameters of some `Gates`.
+struct GatesGradients<F> {
+    input_coeffs: Vec<F>,
+    hidden_coeffs: Vec<F>,
+    biases: Vec<F>
+}
+
+impl<F: Float> Gates<F> {
+    fn new_from<G>(gates: usize, inputs: usize, hidden: usize, mut generator: G) -> Gates<F>
+        where G: FnMut() -> F
+    {
+        Gates {
+            inputs,
+            hidden,
+            input_coeffs: (0..gates*hidden*inputs).map(|_| generator()).collect(),
+            hidden_coeffs: (0..gates*hidden*hidden).map(|_| generator()).collect(),
+            biases: (0..gates*hidden).map(|_| generator()).collect(),
+            input_state: RuleState::new(),
+            hidden_state: RuleState::new(),
+            biases_state: RuleState::new()
+        }
+    }
+
+    /// The pre-activation values of the units of gate `gate`, for given input
+    /// and hidden vector.
+    fn pre_activations(&self, gate: usize, input: &[F], hidden: &[F]) -> Vec<F> {
+        (gate*self.hidden..(gate+1)*self.hidden).map(|k| {
+            let mut acc = self.biases[k];
+            for (i, &x) in input.iter().take(self.inputs).enumerate() {
+                acc = acc + self.input_coeffs[k*self.inputs + i] * x;
+            }
+            for (i, &h) in hidden.iter().enumerate() {
+                acc = acc + self.hidden_coeffs[k*self.hidden + i] * h;
+            }
+            acc
+        }).collect()
+    }
+
+    fn zero_gradients(&self) -> GatesGradients<F> {
+        GatesGradients {
+            input_coeffs: vec![zero(); self.input_coeffs.len()],
+            hidden_coeffs: vec![zero(); self.hidden_coeffs.len()],
+            biases: vec![zero(); self.biases.len()]
+        }
+    }
+
+    /// Accumulates into `grads` the gradients of the parameters of gate `gate`
+    /// given the gradients `deltas` of its pre-activation values, and adds the
+    /// gradients with respect to `input` and `hidden` to `input_error` and
+    /// `hidden_error`.
+    #[allow(clippy::too_many_arguments)]
+    fn backward(&self,
+                grads: &mut GatesGradients<F>,
+                gate: usize,
+                deltas: &[F],
+                input: &[F],
+                hidden: &[F],
+                input_error: &mut [F],
+                hidden_error: &mut [F])
+    {
+        for (j, &delta) in deltas.iter().enumerate() {
+            let k = gate*self.hidden + j;
+            grads.biases[k] = grads.biases[k] + delta;
+            for (i, e) in input_error.iter_mut().enumerate().take(self.inputs) {
+                let x = input.get(i).cloned().unwrap_or(zero());
+                let w = k*self.inputs + i;
+                grads.input_coeffs[w] = grads.input_coeffs[w] + delta * x;
+                *e = *e + self.input_coeffs[w] * delta;
+            }
+            for i in 0..self.hidden {
+                let w = k*self.hidden + i;
+                grads.hidden_coeffs[w] = grads.hidden_coeffs[w] + delta * hidden[i];
+                hidden_error[i] = hidden_error[i] + self.hidden_coeffs[w] * delta;
+            }
+        }
+    }
+
+    fn apply<R>(&mut self, rule: &R, grads: &GatesGradients<F>)
+        where R: GradientRule<Float = F>
+    {
+        rule.apply(&mut self.input_state, &mut self.input_coeffs, &grads.input_coeffs, true);
+        rule.apply(&mut self.hidden_state, &mut self.hidden_coeffs, &grads.hidden_coeffs, true);
+        rule.apply(&mut self.biases_state, &mut self.biases, &grads.biases, false);
+    }
+
+    fn num_parameters(&self) -> usize {
+        self.input_coeffs.len() + self.hidden_coeffs.len() + self.biases.len()
+    }
+
+    fn has_nan(&self) -> bool {
+        any_non_finite(&self.input_coeffs) || any_non_finite(&self.hidden_coeffs) || any_non_finite(&self.biases)
+    }
+}
+
+impl<F: Float> Parametrized<F> for Gates<F> {
+    fn parameters_iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a F> + 'a> {
+        Box::new(self.input_coeffs.iter().chain(self.hidden_coeffs.iter()).chain(self.biases.iter()))
+    }
+
+    fn parameters_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = &'a mut F> + 'a> {
+        Box::new(self.input_coeffs.iter_mut().chain(self.hidden_coeffs.iter_mut()).chain(self.biases.iter_mut()))
+    }
+}
+
+/// A gated recurrent unit (GRU) layer
+///
+/// Like `SimpleRNN`, this layer keeps a hidden state `H` of `hidden` values,
+/// which is its output, but its update is controlled by two gates: an update
+/// gate `Z` choosing how much of the state is replaced, and a reset gate `R`
+/// choosing how much of it is used to compute the candidate state `N`. With
+/// `s()` the logistic function and `*` the element-wise product:
+///
+/// ```text
+/// Z_t = s( Wz*X_t + Uz*H_{t-1} + Bz )
+/// R_t = s( Wr*X_t + Ur*H_{t-1} + Br )
+/// N_t = tanh( Wn*X_t + Un*(R_t * H_{t-1}) + Bn )
+/// H_t = (1 - Z_t) * N_t + Z_t * H_{t-1}
+/// ```
+///
+/// Each call to `compute()` advances time by one step, use `reset_state()` to
+/// start a new sequence. It is trained on whole sequences with
+/// `train_sequence()`.
+pub struct GRU<F: Float> {
+    gates: Gates<F>,
+    state: RefCell<Vec<F>>
+}
+
+/// The intermediate values of a step of a `GRU`, kept for backpropagation.
+struct GRUStep<F> {
+    previous: Vec<F>,
+    update: Vec<F>,
+    reset: Vec<F>,
+    reset_hidden: Vec<F>,
+    candidate: Vec<F>
+}
+
+impl<F: Float> GRU<F> {
+    /// Creates a new GRU layer with all its weights and biases set to 0.
+    pub fn new(inputs: usize, hidden: usize) -> GRU<F> {
+        GRU::new_from(inputs, hidden, zero)
+    }
+
+    /// Creates a new GRU layer with all its weights and biases generated by
+    /// provided closure (for example a random number generator).
+    pub fn new_from<G>(inputs: usize, hidden: usize, generator: G) -> GRU<F>
+        where G: FnMut() -> F
+    {
+        GRU {
+            gates: Gates::new_from(3, inputs, hidden, generator),
+            state: RefCell::new(vec![zero(); hidden])
+        }
+    }
+
+    /// Get access to the weights applied to the inputs.
+    ///
+    /// The matrices of the update gate, the reset gate and the candidate state
+    /// are stacked in this order: the weight from input `i` to unit `j` of
+    /// gate `g` is at index `(g*hidden + j)*inputs + i`.
+    pub fn input_weights(&self) -> &[F] {
+        &self.gates.input_coeffs
+    }
+
+    /// Get mutable access to the weights applied to the inputs.
+    pub fn input_weights_mut(&mut self) -> &mut [F] {
+        &mut self.gates.input_coeffs
+    }
+
+    /// Get access to the weights applied to the previous hidden state.
+    ///
+    /// They are stacked like the input weights: the weight from hidden unit
+    /// `i` to unit `j` of gate `g` is at index `(g*hidden + j)*hidden + i`.
+    pub fn hidden_weights(&self) -> &[F] {
+        &self.gates.hidden_coeffs
+    }
+
+    /// Get mutable access to the weights applied to the previous hidden state.
+    pub fn hidden_weights_mut(&mut self) -> &mut [F] {
+        &mut self.gates.hidden_coeffs
+    }
+
+    /// Get access to the biases of this layer, the bias of unit `j` of gate
+    /// `g` being at index `g*hidden + j`.
+    pub fn biases(&self) -> &[F] {
+        &self.gates.biases
+    }
+
+    /// Get mutable access to the biases of this layer.
+    pub fn biases_mut(&mut self) -> &mut [F] {
+        &mut self.gates.biases
+    }
+
+    /// The current hidden state, the output of the last step.
+    pub fn state(&self) -> Vec<F> {
+        self.state.borrow().clone()
+    }
+
+    /// Clears the hidden state, to start a new sequence.
+    pub fn reset_state(&mut self) {
+        for h in self.state.get_mut().iter_mut() {
+            *h = zero();
+        }
+    }
+
+    /// Performs one step of training over a whole sequence, using
+    /// backpropagation through time.
+    ///
+    /// This works like `SimpleRNN::train_sequence()`: the gradients are
+    /// backpropagated through the steps of this sequence only, the hidden
+    /// state it starts from being considered constant. Long sequences can thus
+    /// be trained with truncated backpropagation through time by splitting
+    /// them in chunks, without resetting the state between them.
+    ///
+    /// Returns the gradient of the error with respect to the input of each
+    /// step.
+    pub fn train_sequence<R>(&mut self, rule: &R, inputs: &[Vec<F>], errors: &[Vec<F>]) -> Vec<Vec<F>>
+        where R: GradientRule<Float = F>
+    {
+        let hidden = self.gates.hidden;
+        let mut state = self.state();
+        let steps = inputs.iter().map(|input| {
+            let step = self.step(input, state.clone());
+            state = self.next_state(&step);
+            step
+        }).collect::<Vec<_>>();
+
+        let mut grads = self.gates.zero_gradients();
+        let mut returned = vec![Vec::new(); inputs.len()];
+        let mut next_error = vec![zero(); hidden];
+        for t in (0..inputs.len()).rev() {
+            let step = &steps[t];
+            let error = (0..hidden).map(|j| {
+                errors.get(t).and_then(|e| e.get(j)).cloned().unwrap_or(zero::<F>()) + next_error[j]
+            }).collect::<Vec<_>>();
+            let mut input_error = vec![zero(); self.gates.inputs];
+            // the direct path from the previous state
+            for j in 0..hidden {
+                next_error[j] = error[j] * step.update[j];
+            }
+
+            let candidate_deltas = (0..hidden).map(|j| {
+                let n = step.candidate[j];
+                error[j] * (one::<F>() - step.update[j]) * (one::<F>() - n * n)
+            }).collect::<Vec<_>>();
+            let mut reset_hidden_error = vec![zero(); hidden];
+            self.gates.backward(&mut grads, 2, &candidate_deltas, &inputs[t], &step.reset_hidden,
+                                &mut input_error, &mut reset_hidden_error);
+
+            let update_deltas = (0..hidden).map(|j| {
+                let z = step.update[j];
+                error[j] * (step.previous[j] - step.candidate[j]) * z * (one::<F>() - z)
+            }).collect::<Vec<_>>();
+            let reset_deltas = (0..hidden).map(|j| {
+                let r = step.reset[j];
+                reset_hidden_error[j] * step.previous[j] * r * (one::<F>() - r)
+            }).collect::<Vec<_>>();
+            for j in 0..hidden {
+                next_error[j] = next_error[j] + reset_hidden_error[j] * step.reset[j];
+            }
+            self.gates.backward(&mut grads, 0, &update_deltas, &inputs[t], &step.previous,
+                                &mut input_error, &mut next_error);
+            self.gates.backward(&mut grads, 1, &reset_deltas, &inputs[t], &step.previous,
+                                &mut input_error, &mut next_error);
+            returned[t] = input_error;
+        }
+
+        self.gates.apply(rule, &grads);
+        self.state.get_mut().copy_from_slice(&state);
+        returned
+    }
+
+    /// Computes the gates and candidate state of a step.
+    fn step(&self, input: &[F], previous: Vec<F>) -> GRUStep<F> {
+        let update = self.gates.pre_activations(0, input, &previous)
+                               .into_iter().map(logistic).collect::<Vec<_>>();
+        let reset = self.gates.pre_activations(1, input, &previous)
+                              .into_iter().map(logistic).collect::<Vec<_>>();
+        let reset_hidden = reset.iter().zip(&previous).map(|(&r, &h)| r * h).collect::<Vec<_>>();
+        let candidate = self.gates.pre_activations(2, input, &reset_hidden)
+                                  .into_iter().map(|x| x.tanh()).collect();
+        GRUStep {
+            previous,
+            update,
+            reset,
+            reset_hidden,
+            candidate
+        }
+    }
+
+    fn next_state(&self, step: &GRUStep<F>) -> Vec<F> {
+        (0..self.gates.hidden).map(|j| {
+            let z = step.update[j];
+            (one::<F>() - z) * step.candidate[j] + z * step.previous[j]
+        }).collect()
+    }
+}
+
+impl<F: Float> Compute<F> for GRU<F> {
+    /// Advances the layer by one step, returning the new hidden state.
+    fn compute(&self, input: &[F]) -> Vec<F> {
+        let step = self.step(input, self.state());
+        let next = self.next_state(&step);
+        self.state.borrow_mut().copy_from_slice(&next);
+        next
+    }
+
+    fn input_size(&self) -> usize {
+        self.gates.inputs
+    }
+
+    fn output_size(&self) -> usize {
+        self.gates.hidden
+    }
+}
+
+impl<F: Float> ModeAware for GRU<F> {}
+
+impl<F: Float> Trainable for GRU<F> {
+    fn num_parameters(&self) -> usize {
+        self.gates.num_parameters()
+    }
+
+    fn has_nan(&self) -> bool {
+        self.gates.has_nan()
+    }
+}
+
+impl<F: Float> Parametrized<F> for GRU<F> {
+    fn parameters_iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a F> + 'a> {
+        self.gates.parameters_iter()
+    }
+
+    fn parameters_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = &'a mut F> + 'a> {
+        self.gates.parameters_mut()
+    }
+}
+
+/// A long short-term memory (LSTM) layer
+///
+/// On top of its hidden state `H`, which is its output, this layer keeps a
+/// cell state `C` of the same size, which is updated through gates: an input
+/// gate `I`, a forget gate `F` and an output gate `O`. With `s()` the logistic
+/// function and `*` the element-wise product:
+///
+/// ```text
+/// I_t = s( Wi*X_t + Ui*H_{t-1} + Bi )
+/// F_t = s( Wf*X_t + Uf*H_{t-1} + Bf )
+/// G_t = tanh( Wg*X_t + Ug*H_{t-1} + Bg )
+/// O_t = s( Wo*X_t + Uo*H_{t-1} + Bo )
+/// C_t = F_t * C_{t-1} + I_t * G_t
+/// H_t = O_t * tanh(C_t)
+/// ```
+///
+/// As the forget gate can keep the cell state unchanged over many steps, this
+/// layer can learn longer dependencies than `SimpleRNN`.
+///
+/// Each call to `compute()` advances time by one step, use `reset_state()` to
+/// start a new sequence. It is trained on whole sequences with
+/// `train_sequence()`.
+pub struct LSTM<F: Float> {
+    gates: Gates<F>,
+    state: RefCell<Vec<F>>,
+    cell: RefCell<Vec<F>>
+}
+
+/// The intermediate values of a step of a `LSTM`, kept for backpropagation.
+struct LSTMStep<F> {
+    previous: Vec<F>,
+    previous_cell: Vec<F>,
+    input: Vec<F>,
+    forget: Vec<F>,
+    candidate: Vec<F>,
+    output: Vec<F>,
+    cell: Vec<F>
+}
+
+impl<F: Float> LSTM<F> {
+    /// Creates a new LSTM layer with all its weights and biases set to 0.
+    pub fn new(inputs: usize, hidden: usize) -> LSTM<F> {
+        LSTM::new_from(inputs, hidden, zero)
+    }
+
+    /// Creates a new LSTM layer with all its weights and biases generated by
+    /// provided closure (for example a random number generator).
+    pub fn new_from<G>(inputs: usize, hidden: usize, generator: G) -> LSTM<F>
+        where G: FnMut() -> F
+    {
+        LSTM {
+            gates: Gates::new_from(4, inputs, hidden, generator),
+            state: RefCell::new(vec![zero(); hidden]),
+            cell: RefCell::new(vec![zero(); hidden])
+        }
+    }
+
+    /// Get access to the weights applied to the inputs.
+    ///
+    /// The matrices of the input gate, the forget gate, the candidate cell
+    /// values and the output gate are stacked in this order: the weight from
+    /// input `i` to unit `j` of gate `g` is at index `(g*hidden + j)*inputs + i`.
+    pub fn input_weights(&self) -> &[F] {
+        &self.gates.input_coeffs
+    }
+
+    /// Get mutable access to the weights applied to the inputs.
+    pub fn input_weights_mut(&mut self) -> &mut [F] {
+        &mut self.gates.input_coeffs
+    }
+
+    /// Get access to the weights applied to the previous hidden state.
+    ///
+    /// They are stacked like the input weights: the weight from hidden unit
+    /// `i` to unit `j` of gate `g` is at index `(g*hidden + j)*hidden + i`.
+    pub fn hidden_weights(&self) -> &[F] {
+        &self.gates.hidden_coeffs
+    }
+
+    /// Get mutable access to the weights applied to the previous hidden state.
+    pub fn hidden_weights_mut(&mut self) -> &mut [F] {
+        &mut self.gates.hidden_coeffs
+    }
+
+    /// Get access to the biases of this layer, the bias of unit `j` of gate
+    /// `g` being at index `g*hidden + j`.
+    pub fn biases(&self) -> &[F] {
+        &self.gates.biases
+    }
+
+    /// Get mutable access to the biases of this layer.
+    pub fn biases_mut(&mut self) -> &mut [F] {
+        &mut self.gates.biases
+    }
+
+    /// The current hidden state, the output of the last step.
+    pub fn state(&self) -> Vec<F> {
+        self.state.borrow().clone()
+    }
+
+    /// The current cell state.
+    pub fn cell_state(&self) -> Vec<F> {
+        self.cell.borrow().clone()
+    }
+
+    /// Clears the hidden and cell states, to start a new sequence.
+    pub fn reset_state(&mut self) {
+        for h in self.state.get_mut().iter_mut().chain(self.cell.get_mut().iter_mut()) {
+            *h = zero();
+        }
+    }
+
+    /// Performs one step of training over a whole sequence, using
+    /// backpropagation through time.
+    ///
+    /// This works like `GRU::train_sequence()`, `errors` being the gradients
+    /// of the error with respect to the hidden state output at each step.
+    ///
+    /// Returns the gradient of the error with respect to the input of each
+    /// step.
+    pub fn train_sequence<R>(&mut self, rule: &R, inputs: &[Vec<F>], errors: &[Vec<F>]) -> Vec<Vec<F>>
+        where R: GradientRule<Float = F>
+    {
+        let hidden = self.gates.hidden;
+        let (mut state, mut cell) = (self.state(), self.cell_state());
+        let steps = inputs.iter().map(|input| {
+            let step = self.step(input, state.clone(), cell.clone());
+            state = self.next_state(&step);
+            cell = step.cell.clone();
+            step
+        }).collect::<Vec<_>>();
+
+        let mut grads = self.gates.zero_gradients();
+        let mut returned = vec![Vec::new(); inputs.len()];
+        let mut next_error = vec![zero(); hidden];
+        let mut next_cell_error = vec![zero::<F>(); hidden];
+        for t in (0..inputs.len()).rev() {
+            let step = &steps[t];
+            let mut input_error = vec![zero(); self.gates.inputs];
+            let mut deltas = vec![Vec::new(); 4];
+            for j in 0..hidden {
+                let error = errors.get(t).and_then(|e| e.get(j)).cloned().unwrap_or(zero::<F>()) + next_error[j];
+                let tanh_cell = step.cell[j].tanh();
+                let cell_error = next_cell_error[j]
+                               + error * step.output[j] * (one::<F>() - tanh_cell * tanh_cell);
+                let (i, f, g, o) = (step.input[j], step.forget[j], step.candidate[j], step.output[j]);
+                deltas[0].push(cell_error * g * i * (one::<F>() - i));
+                deltas[1].push(cell_error * step.previous_cell[j] * f * (one::<F>() - f));
+                deltas[2].push(cell_error * i * (one::<F>() - g * g));
+                deltas[3].push(error * tanh_cell * o * (one::<F>() - o));
+                next_cell_error[j] = cell_error * f;
+                next_error[j] = zero();
+            }
+            for (gate, deltas) in deltas.iter().enumerate() {
+                self.gates.backward(&mut grads, gate, deltas, &inputs[t], &step.previous,
+                                    &mut input_error, &mut next_error);
+            }
+            returned[t] = input_error;
+        }
+
+        self.gates.apply(rule, &grads);
+        self.state.get_mut().copy_from_slice(&state);
+        self.cell.get_mut().copy_from_slice(&cell);
+        returned
+    }
+
+    /// Computes the gates and new cell state of a step.
+    fn step(&self, input: &[F], previous: Vec<F>, previous_cell: Vec<F>) -> LSTMStep<F> {
+        let gate = |g: usize, f: fn(F) -> F| {
+            self.gates.pre_activations(g, input, &previous).into_iter().map(f).collect::<Vec<_>>()
+        };
+        let input_gate = gate(0, logistic);
+        let forget = gate(1, logistic);
+        let candidate = gate(2, F::tanh);
+        let output = gate(3, logistic);
+        let cell = (0..self.gates.hidden).map(|j| {
+            forget[j] * previous_cell[j] + input_gate[j] * candidate[j]
+        }).collect();
+        LSTMStep {
+            previous,
+            previous_cell,
+            input: input_gate,
+            forget,
+            candidate,
+            output,
+            cell
+        }
+    }
+
+    fn next_state(&self, step: &LSTMStep<F>) -> Vec<F> {
+        step.output.iter().zip(&step.cell).map(|(&o, &c)| o * c.tanh()).collect()
+    }
+}
+
+impl<F: Float> Compute<F> for LSTM<F> {
+    /// Advances the layer by one step, returning the new hidden state.
+    fn compute(&self, input: &[F]) -> Vec<F> {
+        let step = self.step(input, self.state(), self.cell_state());
+        let next = self.next_state(&step);
+        self.state.borrow_mut().copy_from_slice(&next);
+        self.cell.borrow_mut().copy_from_slice(&step.cell);
+        next
+    }
+
+    fn input_size(&self) -> usize {
+        self.gates.inputs
+    }
+
+    fn output_size(&self) -> usize {
+        self.gates.hidden
+    }
+}
+
+impl<F: Float> ModeAware for LSTM<F> {}
+
+impl<F: Float> Trainable for LSTM<F> {
+    fn num_parameters(&self) -> usize {
+        self.gates.num_parameters()
+    }
+
+    fn has_nan(&self) -> bool {
+        self.gates.has_nan()
+    }
+}
+
+impl<F: Float> Parametrized<F> for LSTM<F> {
+    fn parameters_iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a F> + 'a> {
+        self.gates.parameters_iter()
+    }
+
+    fn parameters_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = &'a mut F> + 'a> {
+        self.gates.parameters_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "std")]
+    use rand::{Rng, SeedableRng, XorShiftRng};
+
+    use {Compute, Trainable};
+    use activations::{identity, tanh};
+    use testing::{random, random_scaled};
+    use training::GradientDescent;
+
+    use super::{SimpleRNN, GRU, LSTM, Gates};
+
+    /// The `k`-th parameter of `gates`, counting the input weights, then the
+    /// hidden weights and the biases.
+    fn gate_param<F: ::num::Float>(gates: &mut Gates<F>, k: usize) -> &mut F {
+        let (ni, nh) = (gates.input_coeffs.len(), gates.hidden_coeffs.len());
+        if k < ni {
+            &mut gates.input_coeffs[k]
+        } else if k < ni + nh {
+            &mut gates.hidden_coeffs[k - ni]
+        } else {
+            &mut gates.biases[k - ni - nh]
+        }
+    }
+
+    fn param(layer: &mut GRU<f64>, k: usize) -> &mut f64 {
+        gate_param(&mut layer.gates, k)
+    }
+
+    #[test]
+    fn accumulator() {
+        // h_t = x_t + h_{t-1}
+        let mut layer = SimpleRNN::new(1, 1, identity());
+        layer.input_weights_mut()[0] = 1.0f32;
+        layer.hidden_weights_mut()[0] = 1.0;
+        assert_eq!(layer.compute(&[1.0]), vec![1.0]);
+        assert_eq!(layer.compute(&[2.0]), vec![3.0]);
+        assert_eq!(layer.compute(&[0.5]), vec![3.5]);
+        assert_eq!(layer.state(), vec![3.5]);
+        layer.reset_state();
+        assert_eq!(layer.state(), vec![0.0]);
+        assert_eq!(layer.compute(&[1.0]), vec![1.0]);
+    }
+
+    #[test]
+    fn reset_state() {
+        let mut layer = SimpleRNN::new_from(2, 3, tanh(), random());
+        let sequence = [[1.0f64, 0.0], [0.0, 1.0], [0.5, -0.5]];
+        let first = sequence.iter().map(|x| layer.compute(x)).collect::<Vec<_>>();
+        assert!(first[0] != first[1] && first[1] != first[2]);
+        // without a reset, the same sequence gives different outputs
+        assert!(layer.compute(&sequence[0]) != first[0]);
+        layer.reset_state();
+        let second = sequence.iter().map(|x| layer.compute(x)).collect::<Vec<_>>();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn train_sequence_gradients() {
+        let mut layer = SimpleRNN::new_from(2, 2, tanh(), random_scaled(0.5));
+        let inputs = vec![vec![1.0, 0.0], vec![0.5, -0.5], vec![-1.0, 0.25]];
+        let errors = vec![vec![0.5, -1.0], vec![0.0, 0.25], vec![1.0, 0.5]];
+        // error whose gradient with respect to each output is `errors`
+        let loss = |layer: &mut SimpleRNN<f64, _, _>, inputs: &[Vec<f64>]| {
+            layer.reset_state();
+            inputs.iter().zip(errors.iter()).map(|(x, e)| {
+                let h = layer.compute(x);
+                h[0] * e[0] + h[1] * e[1]
+            }).sum::<f64>()
+        };
+        let eps = 1e-6;
+
+        let mut expected = Vec::new();
+        for t in 0..3 {
+            for i in 0..2 {
+                let (mut plus, mut minus) = (inputs.clone(), inputs.clone());
+                plus[t][i] += eps;
+                minus[t][i] -= eps;
+                expected.push((loss(&mut layer, &plus) - loss(&mut layer, &minus)) / (2.0 * eps));
+            }
+        }
+        let mut hidden_grad = Vec::new();
+        for k in 0..4 {
+            let w = layer.hidden_coeffs[k];
+            layer.hidden_coeffs[k] = w + eps;
+            let plus = loss(&mut layer, &inputs);
+            layer.hidden_coeffs[k] = w - eps;
+            let minus = loss(&mut layer, &inputs);
+            layer.hidden_coeffs[k] = w;
+            hidden_grad.push((plus - minus) / (2.0 * eps));
+        }
+        let old_hidden = layer.hidden_coeffs.clone();
+
+        layer.reset_state();
+        let returned = layer.train_sequence(&GradientDescent::new(1.0), &inputs, &errors);
+        for t in 0..3 {
+            for i in 0..2 {
+                assert!((returned[t][i] - expected[2*t + i]).abs() < 1e-6);
+            }
+        }
+        for k in 0..4 {
+            assert!((old_hidden[k] - layer.hidden_coeffs[k] - hidden_grad[k]).abs() < 1e-6);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn copy_task() {
+        // the first hidden unit must output the input of two steps before
+        let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+        let mut layer = SimpleRNN::new_from(1, 3, tanh(), || rng.gen::<f64>() - 0.5);
+        let mut rng = XorShiftRng::from_seed([5, 6, 7, 8]);
+        let rule = GradientDescent::new(0.1);
+        let mut epoch = |layer: &mut SimpleRNN<f64, _, _>| {
+            let inputs = (0..10).map(|_| vec![rng.gen::<f64>() - 0.5]).collect::<Vec<_>>();
+            layer.reset_state();
+            let mut loss = 0.0;
+            let errors = (0..10).map(|t| {
+                let h = layer.compute(&inputs[t]);
+                if t < 2 { return vec![0.0]; }
+                loss += 0.5 * (h[0] - inputs[t-2][0]).powi(2);
+                vec![h[0] - inputs[t-2][0]]
+            }).collect::<Vec<_>>();
+            layer.reset_state();
+            layer.train_sequence(&rule, &inputs, &errors);
+            loss
+        };
+        let first = (0..20).map(|_| epoch(&mut layer)).sum::<f64>();
+        for _ in 0..2000 {
+            epoch(&mut layer);
+        }
+        let last = (0..20).map(|_| epoch(&mut layer)).sum::<f64>();
+        assert!(last < 0.25 * first);
+    }
+
+    #[test]
+    fn gru_gates() {
+        // one unit: Z = s(x), R = s(0), N = tanh(x + 2*R*h)
+        let mut layer = GRU::new(1, 1);
+        layer.input_weights_mut().copy_from_slice(&[1.0f64, 0.0, 1.0]);
+        layer.hidden_weights_mut().copy_from_slice(&[0.0, 0.0, 2.0]);
+        let s = |x: f64| 1.0 / (1.0 + (-x).exp());
+        let h1 = (1.0 - s(1.0)) * 1.0f64.tanh();
+        assert!((layer.compute(&[1.0])[0] - h1).abs() < 1e-12);
+        let h2 = 0.5 * h1.tanh() + 0.5 * h1;
+        assert!((layer.compute(&[0.0])[0] - h2).abs() < 1e-12);
+        layer.reset_state();
+        assert_eq!(layer.state(), vec![0.0]);
+        assert!((layer.compute(&[1.0])[0] - h1).abs() < 1e-12);
+    }
+
+    #[test]
+    fn gru_gradients() {
+        let mut layer = GRU::new_from(2, 2, random());
+        assert_eq!(layer.num_parameters(), 3 * (2*2 + 2*2 + 2));
+        // start from a non-zero state
+        layer.compute(&[0.5, 1.0]);
+        let start = layer.state();
+        let inputs = vec![vec![1.0, -0.5], vec![0.25, 0.75]];
+        let errors = vec![vec![0.5, -1.0], vec![1.0, 0.5]];
+        // error whose gradient with respect to each output is `errors`
+        let loss = |layer: &GRU<f64>, inputs: &[Vec<f64>]| {
+            layer.state.borrow_mut().copy_from_slice(&start);
+            inputs.iter().zip(errors.iter()).map(|(x, e)| {
+                let h = layer.compute(x);
+                h[0] * e[0] + h[1] * e[1]
+            }).sum::<f64>()
+        };
+        let eps = 1e-6;
+
+        let mut expected = Vec::new();
+        for t in 0..2 {
+            for i in 0..2 {
+                let (mut plus, mut minus) = (inputs.clone(), inputs.clone());
+                plus[t][i] += eps;
+                minus[t][i] -= eps;
+                expected.push((loss(&layer, &plus) - loss(&layer, &minus)) / (2.0 * eps));
+            }
+        }
+        let mut params_grad = Vec::new();
+        for k in 0..layer.num_parameters() {
+            let w = *param(&mut layer, k);
+            *param(&mut layer, k) = w + eps;
+            let plus = loss(&layer, &inputs);
+            *param(&mut layer, k) = w - eps;
+            let minus = loss(&layer, &inputs);
+            *param(&mut layer, k) = w;
+            params_grad.push((plus - minus) / (2.0 * eps));
+        }
+        let old = (0..layer.num_parameters()).map(|k| *param(&mut layer, k)).collect::<Vec<_>>();
+
+        layer.state.borrow_mut().copy_from_slice(&start);
+        let returned = layer.train_sequence(&GradientDescent::new(1.0), &inputs, &errors);
+        for t in 0..2 {
+            for i in 0..2 {
+                assert!((returned[t][i] - expected[2*t + i]).abs() < 1e-6);
+            }
+        }
+        for k in 0..layer.num_parameters() {
+            assert!((old[k] - *param(&mut layer, k) - params_grad[k]).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn lstm_forget_gate() {
+        // all weights are zero: I = O = 1/2, G = tanh(1)
+        let mut layer = LSTM::new(1, 1);
+        layer.biases_mut().copy_from_slice(&[0.0f64, 100.0, 1.0, 0.0]);
+        let c1 = 0.5 * 1.0f64.tanh();
+        assert!((layer.compute(&[0.0])[0] - 0.5 * c1.tanh()).abs() < 1e-12);
+        assert!((layer.cell_state()[0] - c1).abs() < 1e-12);
+        // with an open forget gate, the cell state accumulates
+        layer.compute(&[0.0]);
+        assert!((layer.cell_state()[0] - 2.0 * c1).abs() < 1e-12);
+
+        // with a closed one, the previous cell state is cleared
+        layer.biases_mut()[1] = -100.0;
+        layer.compute(&[0.0]);
+        assert!((layer.cell_state()[0] - c1).abs() < 1e-12);
+        layer.biases_mut()[0] = -100.0;
+        layer.compute(&[0.0]);
+        assert!(layer.cell_state()[0].abs() < 1e-12);
+
+        layer.biases_mut()[0] = 0.0;
+        layer.compute(&[0.0]);
+        layer.reset_state();
+        assert_eq!((layer.state(), layer.cell_state()), (vec![0.0], vec![0.0]));
+    }
+
+    #[test]
+    fn lstm_gradients() {
+        let mut layer = LSTM::new_from(2, 2, random());
+        assert_eq!(layer.num_parameters(), 4 * (2*2 + 2*2 + 2));
+        // start from non-zero states
+        layer.compute(&[0.5, 1.0]);
+        let (start, start_cell) = (layer.state(), layer.cell_state());
+        let inputs = vec![vec![1.0, -0.5], vec![0.25, 0.75], vec![-1.0, 0.5]];
+        let errors = vec![vec![0.5, -1.0], vec![1.0, 0.5], vec![-0.5, 0.25]];
+        // error whose gradient with respect to each output is `errors`
+        let loss = |layer: &LSTM<f64>, inputs: &[Vec<f64>]| {
+            layer.state.borrow_mut().copy_from_slice(&start);
+            layer.cell.borrow_mut().copy_from_slice(&start_cell);
+            inputs.iter().zip(errors.iter()).map(|(x, e)| {
+                let h = layer.compute(x);
+                h[0] * e[0] + h[1] * e[1]
+            }).sum::<f64>()
+        };
+        let eps = 1e-6;
+
+        let mut expected = Vec::new();
+        for t in 0..3 {
+            for i in 0..2 {
+                let (mut plus, mut minus) = (inputs.clone(), inputs.clone());
+                plus[t][i] += eps;
+                minus[t][i] -= eps;
+                expected.push((loss(&layer, &plus) - loss(&layer, &minus)) / (2.0 * eps));
+            }
+        }
+        let mut params_grad = Vec::new();
+        for k in 0..layer.num_parameters() {
+            let w = *gate_param(&mut layer.gates, k);
+            *gate_param(&mut layer.gates, k) = w + eps;
+            let plus = loss(&layer, &inputs);
+            *gate_param(&mut layer.gates, k) = w - eps;
+            let minus = loss(&layer, &inputs);
+            *gate_param(&mut layer.gates, k) = w;
+            params_grad.push((plus - minus) / (2.0 * eps));
+        }
+        let old = (0..layer.num_parameters()).map(|k| *gate_param(&mut layer.gates, k)).collect::<Vec<_>>();
+
+        layer.state.borrow_mut().copy_from_slice(&start);
+        layer.cell.borrow_mut().copy_from_slice(&start_cell);
+        let returned = layer.train_sequence(&GradientDescent::new(1.0), &inputs, &errors);
+        for t in 0..3 {
+            for i in 0..2 {
+                assert!((returned[t][i] - expected[2*t + i]).abs() < 1e-6);
+            }
+        }
+        for k in 0..layer.num_parameters() {
+            assert!((old[k] - *gate_param(&mut layer.gates, k) - params_grad[k]).abs() < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,288 @@
+//! Softmax output layers
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::slice;
+
+use num::{Float, zero};
+
+use {Compute, BackpropTrain, Trainable, Parametrized, ModeAware, any_non_finite};
+use training::{GradientRule, RuleState};
+
+/// A softmax layer, turning a vector of scores into a probability distribution.
+///
+/// ```text
+/// y_i = exp(x_i) / sum_k( exp(x_k) )
+/// ```
+///
+/// The maximum of the input is subtracted before exponentiating, so that large
+/// scores do not overflow.
+pub struct SoftmaxLayer<F: Float> {
+    _marker: PhantomData<F>,
+    size: usize
+}
+
+impl<F: Float> SoftmaxLayer<F> {
+    /// Creates a new softmax layer over vectors of given size.
+    pub fn new(size: usize) -> SoftmaxLayer<F> {
+        SoftmaxLayer {
+            _marker: PhantomData,
+            size
+        }
+    }
+}
+
+impl<F: Float> Compute<F> for SoftmaxLayer<F> {
+    fn compute(&self, input: &[F]) -> Vec<F> {
+        let mut out: Vec<F> = (0..self.size)
+                                  .map(|i| input.get(i).cloned().unwrap_or(zero()))
+                                  .collect();
+        let max = out.iter().fold(F::neg_infinity(), |m, &x| m.max(x));
+        let mut sum = zero::<F>();
+        for o in &mut out {
+            *o = (*o - max).exp();
+            sum = sum + *o;
+        }
+        for o in &mut out {
+            *o = *o / sum;
+        }
+        out
+    }
+
+    fn input_size(&self) -> usize {
+        self.size
+    }
+
+    fn output_size(&self) -> usize {
+        self.size
+    }
+}
+
+/// The layer itself has no parameters to train, the backpropagation only
+/// computes the gradient with respect to its input:
+///
+/// ```text
+/// dE/dx_i = y_i * ( dE/dy_i - sum_k( y_k * dE/dy_k ) )
+/// ```
+///
+/// When used as the output layer of a classifier trained with the cross-entropy
+/// error, whose gradient is `-target/output`, this simplifies to `output - target`.
+impl<F, R> BackpropTrain<F, R> for SoftmaxLayer<F>
+    where F: Float,
+          R: GradientRule<Float = F>
+{
+    fn backprop_train(&mut self, _rule: &R, input: &[F], error: &[F]) -> Vec<F> {
+        let mut out = self.compute(input);
+        let dot = out.iter().enumerate().fold(zero::<F>(), |acc, (i, &y)| {
+            acc + y * error.get(i).cloned().unwrap_or(zero())
+        });
+        for (i, o) in out.iter_mut().enumerate() {
+            *o = *o * (error.get(i).cloned().unwrap_or(zero()) - dot);
+        }
+        out
+    }
+}
+
+impl<F: Float> ModeAware for SoftmaxLayer<F> {}
+
+impl<F: Float> Trainable for SoftmaxLayer<F> {}
+
+impl<F: Float> Parametrized<F> for SoftmaxLayer<F> {}
+
+/// A softmax layer with a learnable temperature `T`, dividing the scores
+/// before turning them into a probability distribution.
+///
+/// ```text
+/// y_i = exp(x_i / T) / sum_k( exp(x_k / T) )
+/// ```
+///
+/// A low temperature sharpens the distribution towards the largest score,
+/// while a high one flattens it towards the uniform distribution. The
+/// temperature is trained by backpropagation along with passing the gradient
+/// back to the input, and should stay positive.
+pub struct TemperedSoftmaxLayer<F: Float> {
+    softmax: SoftmaxLayer<F>,
+    temperature: F,
+    state: RuleState<F>
+}
+
+impl<F: Float> TemperedSoftmaxLayer<F> {
+    /// Creates a new softmax layer over vectors of given size, with given
+    /// initial temperature.
+    pub fn new(size: usize, temperature: F) -> TemperedSoftmaxLayer<F> {
+        TemperedSoftmaxLayer {
+            softmax: SoftmaxLayer::new(size),
+            temperature,
+            state: RuleState::new()
+        }
+    }
+
+    /// The current temperature of this layer.
+    pub fn temperature(&self) -> F {
+        self.temperature
+    }
+
+    /// Replaces the temperature of this layer.
+    pub fn set_temperature(&mut self, temperature: F) {
+        self.temperature = temperature;
+    }
+
+    /// The input scaled by the temperature.
+    fn scaled(&self, input: &[F]) -> Vec<F> {
+        (0..self.softmax.size).map(|i| input.get(i).cloned().unwrap_or(zero()) / self.temperature)
+                              .collect()
+    }
+}
+
+impl<F: Float> Compute<F> for TemperedSoftmaxLayer<F> {
+    fn compute(&self, input: &[F]) -> Vec<F> {
+        self.softmax.compute(&self.scaled(input))
+    }
+
+    fn input_size(&self) -> usize {
+        self.softmax.size
+    }
+
+    fn output_size(&self) -> usize {
+        self.softmax.size
+    }
+}
+
+/// With `g` the gradient with respect to the scaled scores `x_i / T`, as
+/// computed by `SoftmaxLayer`:
+///
+/// ```text
+/// dE/dx_i = g_i / T
+/// dE/dT = - sum_i( g_i * x_i ) / T^2
+/// ```
+impl<F, R> BackpropTrain<F, R> for TemperedSoftmaxLayer<F>
+    where F: Float,
+          R: GradientRule<Float = F>
+{
+    fn backprop_train(&mut self, rule: &R, input: &[F], error: &[F]) -> Vec<F> {
+        let scaled = self.scaled(input);
+        let out = self.softmax.compute(&scaled);
+        let dot = out.iter().enumerate().fold(zero::<F>(), |acc, (i, &y)| {
+            acc + y * error.get(i).cloned().unwrap_or(zero())
+        });
+        let grads = out.iter().enumerate().map(|(i, &y)| {
+            y * (error.get(i).cloned().unwrap_or(zero()) - dot)
+        }).collect::<Vec<F>>();
+        // x_i / T^2 = scaled_i / T
+        let temperature_grad = grads.iter().zip(&scaled).fold(zero::<F>(), |acc, (&g, &z)| acc - g * z)
+                               / self.temperature;
+        let returned = grads.iter().map(|&g| g / self.temperature).collect();
+        rule.apply(&mut self.state, slice::from_mut(&mut self.temperature), &[temperature_grad], false);
+        returned
+    }
+}
+
+impl<F: Float> ModeAware for TemperedSoftmaxLayer<F> {}
+
+impl<F: Float> Trainable for TemperedSoftmaxLayer<F> {
+    fn num_parameters(&self) -> usize {
+        1
+    }
+
+    fn has_nan(&self) -> bool {
+        any_non_finite(&[self.temperature])
+    }
+}
+
+impl<F: Float> Parametrized<F> for TemperedSoftmaxLayer<F> {
+    fn parameters_iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a F> + 'a> {
+        Box::new(::core::iter::once(&self.temperature))
+    }
+
+    fn parameters_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = &'a mut F> + 'a> {
+        Box::new(::core::iter::once(&mut self.temperature))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {Compute, BackpropTrain};
+    use training::GradientDescent;
+
+    use super::{SoftmaxLayer, TemperedSoftmaxLayer};
+
+    #[test]
+    fn sums_to_one() {
+        let layer = SoftmaxLayer::<f32>::new(4);
+        assert_eq!(layer.input_size(), 4);
+        assert_eq!(layer.output_size(), 4);
+        let out = layer.compute(&[1.0f32, 2.0, 3.0, 4.0]);
+        assert!((out.iter().fold(0.0, |s, x| s + x) - 1.0).abs() < 1e-6);
+        assert!(out[0] < out[1] && out[1] < out[2] && out[2] < out[3]);
+    }
+
+    #[test]
+    fn no_overflow() {
+        let layer = SoftmaxLayer::new(3);
+        let out = layer.compute(&[1000.0f32, 1000.0, -1000.0]);
+        assert!(out.iter().all(|x| x.is_finite()));
+        assert!((out[0] - 0.5).abs() < 1e-6);
+        assert!((out[1] - 0.5).abs() < 1e-6);
+        assert!(out[2].abs() < 1e-6);
+    }
+
+    #[test]
+    fn backprop_train() {
+        let mut layer = SoftmaxLayer::new(3);
+        let rule = GradientDescent::new(0.1f32);
+        let input = [0.5f32, -1.0, 2.0];
+        let target = [0.0f32, 1.0, 0.0];
+        let out = layer.compute(&input);
+        // gradient of the cross-entropy error
+        let error = target.iter().zip(out.iter()).map(|(t, y)| -t / y).collect::<Vec<_>>();
+        let grad = layer.backprop_train(&rule, &input, &error);
+        for i in 0..3 {
+            assert!((grad[i] - (out[i] - target[i])).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn temperature() {
+        let input = [0.5f64, -1.0, 2.0];
+        let cold = TemperedSoftmaxLayer::new(3, 1.0).compute(&input);
+        assert_eq!(cold, SoftmaxLayer::new(3).compute(&input));
+        let hot = TemperedSoftmaxLayer::new(3, 4.0).compute(&input);
+        assert!((hot.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+        assert!(hot[2] < cold[2] && hot[1] > cold[1]);
+        let spread = |p: &[f64]| p.iter().fold(0.0f64, |m, &x| m.max(x)) - p.iter().fold(1.0f64, |m, &x| m.min(x));
+        assert!(spread(&hot) < spread(&cold));
+    }
+
+    #[test]
+    fn temperature_gradient() {
+        let rule = GradientDescent::new(0.1f64);
+        let input = [0.5f64, -1.0, 2.0];
+        let cross_entropy = |out: &[f64], target: usize| {
+            (0..3).map(|i| if i == target { -1.0 / out[i] } else { 0.0 }).collect::<Vec<_>>()
+        };
+
+        // the most likely class is the right one: sharpening helps
+        let mut layer = TemperedSoftmaxLayer::new(3, 2.0);
+        let error = cross_entropy(&layer.compute(&input), 2);
+        let grad = layer.backprop_train(&rule, &input, &error);
+        assert!(layer.temperature() < 2.0);
+
+        // the gradient with respect to the input matches finite differences
+        let reference = TemperedSoftmaxLayer::new(3, 2.0);
+        for i in 0..3 {
+            let mut plus = input;
+            plus[i] += 1e-6;
+            let mut minus = input;
+            minus[i] -= 1e-6;
+            let num = (-reference.compute(&plus)[2].ln() + reference.compute(&minus)[2].ln()) / 2e-6;
+            assert!((grad[i] - num).abs() < 1e-6);
+        }
+
+        // the least likely class is the right one: flattening helps
+        let mut layer = TemperedSoftmaxLayer::new(3, 2.0);
+        let error = cross_entropy(&layer.compute(&input), 1);
+        layer.backprop_train(&rule, &input, &error);
+        assert!(layer.temperature() > 2.0);
+    }
+}
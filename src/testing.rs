@@ -0,0 +1,15 @@
+//! Helpers shared by the tests of the crate.
+
+use num::Float;
+
+/// A deterministic sequence of pseudo-random values in `[-1, 1]`, to
+/// initialize the parameters of the networks under test.
+pub fn random<F: Float>() -> impl FnMut() -> F {
+    random_scaled(1.0)
+}
+
+/// Like `random()`, with the values scaled to `[-scale, scale]`.
+pub fn random_scaled<F: Float>(scale: f64) -> impl FnMut() -> F {
+    let mut acc = 0;
+    move || { acc += 1; F::from((((7*acc) % 11) as f64 - 5.0) / 5.0 * scale).unwrap() }
+}
@@ -3,9 +3,84 @@
 //! These types describe the parameters of each learning that can be
 //! tune by the user.
 
-use num::Float;
+use alloc::borrow::ToOwned;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use core::fmt;
 
-use Method;
+use num::{Float, one};
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+use {Method, Parametrized};
+
+/// A training method that updates a set of parameters given the gradient of
+/// the error with respect to them.
+///
+/// Layers implement their training generically over such rules, so that any
+/// of them can be used to train any of these layers.
+pub trait GradientRule: Method {
+    /// The float type of the parameters this rule updates.
+    type Float: Float;
+
+    /// Updates `params` given the gradient `grads` of the error with respect
+    /// to them.
+    ///
+    /// `state` is the memory of this rule for this set of parameters, and
+    /// `weights` tells whether they are connection weights (as opposed to
+    /// biases).
+    fn apply(&self,
+             state: &mut RuleState<Self::Float>,
+             params: &mut [Self::Float],
+             grads: &[Self::Float],
+             weights: bool);
+}
+
+/// Memory of a stateful training rule for a set of parameters.
+///
+/// Rules like `Adam` need to remember some values for every parameter across
+/// training steps. As a same rule is used by reference to train all the layers
+/// of a network, this memory is kept by the layers themselves, which own one
+/// `RuleState` for each of their sets of parameters. Stateless rules simply
+/// ignore it.
+///
+/// A state is meant to be used with a single rule: switching the rule used to
+/// train a layer should be done with a fresh state.
+#[derive(Clone, Debug, Default)]
+pub struct RuleState<F> {
+    steps: usize,
+    buffers: Vec<Vec<F>>
+}
+
+impl<F: Float> RuleState<F> {
+    /// Creates a new empty state.
+    pub fn new() -> RuleState<F> {
+        RuleState { steps: 0, buffers: Vec::new() }
+    }
+
+    /// Counts one more training step, and returns the number of steps
+    /// performed so far, this one included.
+    pub fn next_step(&mut self) -> usize {
+        self.steps += 1;
+        self.steps
+    }
+
+    /// Access the first `count` buffers of this state, each holding `len` values.
+    ///
+    /// Buffers are filled with zeros the first time they are requested, or if
+    /// their length changed since.
+    pub fn buffers(&mut self, count: usize, len: usize) -> &mut [Vec<F>] {
+        if self.buffers.len() < count {
+            self.buffers.resize(count, Vec::new());
+        }
+        for b in &mut self.buffers[..count] {
+            if b.len() != len {
+                *b = vec![F::zero(); len];
+            }
+        }
+        &mut self.buffers[..count]
+    }
+}
 
 /// The gradient descend approach, consisting on finding a minimum of the
 /// error by going down its gradient.
@@ -14,11 +89,142 @@ pub struct GradientDescent<F: Float> {
     ///
     /// A very small value will make the training slow, but a too big one
     /// will make it unstable.
-    pub rate: F
+    pub rate: F,
+    /// If set, each gradient component is clamped to `[-clip, clip]` before
+    /// being applied, to keep exploding gradients from breaking the training.
+    pub clip: Option<F>
+}
+
+impl<F: Float> GradientDescent<F> {
+    /// Creates a gradient descent rule with given learning rate and no clipping.
+    pub fn new(rate: F) -> GradientDescent<F> {
+        GradientDescent { rate, clip: None }
+    }
 }
 
 impl<F: Float> Method for GradientDescent<F> {}
 
+impl<F: Float> GradientRule for GradientDescent<F> {
+    type Float = F;
+
+    fn apply(&self, _state: &mut RuleState<F>, params: &mut [F], grads: &[F], _weights: bool) {
+        for (p, &g) in params.iter_mut().zip(grads.iter()) {
+            let g = match self.clip {
+                Some(c) => g.max(-c).min(c),
+                None => g
+            };
+            *p = *p - self.rate * g;
+        }
+    }
+}
+
+/// Gradient descent with L2 regularization (weight decay): on top of
+/// following the gradient, each connection weight shrinks proportionally
+/// to its value.
+///
+/// ```text
+/// w = w - rate * ( grad + weight_decay * w )
+/// ```
+///
+/// As is customary, biases are not decayed.
+pub struct GradientDescentL2<F: Float> {
+    /// The learning rate associated with this gradient descent rule.
+    pub rate: F,
+    /// The strength of the regularization.
+    pub weight_decay: F
+}
+
+impl<F: Float> Method for GradientDescentL2<F> {}
+
+impl<F: Float> GradientRule for GradientDescentL2<F> {
+    type Float = F;
+
+    fn apply(&self, _state: &mut RuleState<F>, params: &mut [F], grads: &[F], weights: bool) {
+        for (p, &g) in params.iter_mut().zip(grads.iter()) {
+            let decay = if weights { self.weight_decay * *p } else { F::zero() };
+            *p = *p - self.rate * (g + decay);
+        }
+    }
+}
+
+/// Gradient descent with L1 regularization: on top of following the gradient,
+/// each connection weight is pulled towards zero by a constant amount, which
+/// tends to make the weights sparse.
+///
+/// ```text
+/// w = w - rate * ( grad + lambda * sign(w) )
+/// ```
+///
+/// A weight that is exactly zero gets no pull (a zero subgradient), and biases
+/// are not regularized.
+pub struct GradientDescentL1<F: Float> {
+    /// The learning rate associated with this gradient descent rule.
+    pub rate: F,
+    /// The strength of the regularization.
+    pub lambda: F
+}
+
+impl<F: Float> Method for GradientDescentL1<F> {}
+
+impl<F: Float> GradientRule for GradientDescentL1<F> {
+    type Float = F;
+
+    fn apply(&self, _state: &mut RuleState<F>, params: &mut [F], grads: &[F], weights: bool) {
+        for (p, &g) in params.iter_mut().zip(grads.iter()) {
+            let pull = if weights && *p != F::zero() { self.lambda * p.signum() } else { F::zero() };
+            *p = *p - self.rate * (g + pull);
+        }
+    }
+}
+
+/// Rescales `grads` so that its L2 norm does not exceed `max_norm`.
+///
+/// The direction of the gradient is preserved, and a gradient whose norm is
+/// already within the limit is left untouched.
+pub fn clip_norm<F: Float>(grads: &mut [F], max_norm: F) {
+    let norm = grads.iter().fold(F::zero(), |acc, &g| acc + g * g).sqrt();
+    if norm > max_norm {
+        let factor = max_norm / norm;
+        for g in grads {
+            *g = *g * factor;
+        }
+    }
+}
+
+/// A wrapper around a training rule, rescaling the gradients before handing
+/// them to it so that their L2 norm does not exceed `max_norm`.
+///
+/// This is per-tensor clipping: the norm is computed separately over each set
+/// of parameters that is updated at once, for example the weights of a layer
+/// and then its biases, and not over the gradient of the whole network. A
+/// network whose gradients all stay under `max_norm` can thus still make a
+/// step whose global norm exceeds it. To clip the global norm, gather the
+/// gradients of the whole step in one slice and call `clip_norm()` on it
+/// before applying them.
+pub struct ClipNorm<R: GradientRule> {
+    /// The wrapped rule.
+    pub rule: R,
+    /// The maximal norm of the gradients passed to the wrapped rule.
+    pub max_norm: R::Float
+}
+
+impl<R: GradientRule> Method for ClipNorm<R> {}
+
+impl<R: GradientRule> GradientRule for ClipNorm<R> {
+    type Float = R::Float;
+
+    fn apply(&self,
+             state: &mut RuleState<R::Float>,
+             params: &mut [R::Float],
+             grads: &[R::Float],
+             weights: bool)
+    {
+        let mut grads = grads.to_owned();
+        clip_norm(&mut grads, self.max_norm);
+        self.rule.apply(state, params, &grads, weights);
+    }
+}
+
 /// The perceptron rule, a classic learning rule for one-layered
 /// feedforward networks.
 pub struct PerceptronRule<F: Float> {
@@ -29,4 +235,922 @@ pub struct PerceptronRule<F: Float> {
     pub rate: F
 }
 
-impl<F: Float> Method for PerceptronRule<F> {}
\ No newline at end of file
+impl<F: Float> Method for PerceptronRule<F> {}
+
+/// The Adam method, a gradient descent where each parameter gets its own
+/// adaptive step size, derived from running estimates of the first and
+/// second moments of its gradient.
+///
+/// The moment estimates are kept by the trained layers, see `RuleState`.
+pub struct Adam<F: Float> {
+    /// The learning rate, roughly the largest step a parameter can make.
+    pub rate: F,
+    /// Decay rate of the estimate of the first moment (typically `0.9`).
+    pub beta1: F,
+    /// Decay rate of the estimate of the second moment (typically `0.999`).
+    pub beta2: F,
+    /// Small value avoiding divisions by zero (typically `1e-8`).
+    pub epsilon: F
+}
+
+impl<F: Float> Adam<F> {
+    /// Creates an Adam rule with given learning rate and the typical values
+    /// for the other parameters.
+    pub fn new(rate: F) -> Adam<F> {
+        Adam {
+            rate,
+            beta1: F::from(0.9).unwrap(),
+            beta2: F::from(0.999).unwrap(),
+            epsilon: F::from(1e-8).unwrap()
+        }
+    }
+}
+
+impl<F: Float> Method for Adam<F> {}
+
+impl<F: Float> GradientRule for Adam<F> {
+    type Float = F;
+
+    fn apply(&self, state: &mut RuleState<F>, params: &mut [F], grads: &[F], _weights: bool) {
+        adam_step(state, params, grads, self.rate, self.beta1, self.beta2, self.epsilon);
+    }
+}
+
+/// Performs one step of the Adam method.
+fn adam_step<F: Float>(state: &mut RuleState<F>,
+                       params: &mut [F],
+                       grads: &[F],
+                       rate: F,
+                       beta1: F,
+                       beta2: F,
+                       epsilon: F)
+{
+    let t = state.next_step() as i32;
+    let correction1 = one::<F>() - beta1.powi(t);
+    let correction2 = one::<F>() - beta2.powi(t);
+    let (m, v) = state.buffers(2, params.len()).split_at_mut(1);
+    let (m, v) = (&mut m[0], &mut v[0]);
+    for (k, (p, &g)) in params.iter_mut().zip(grads.iter()).enumerate() {
+        m[k] = beta1 * m[k] + (one::<F>() - beta1) * g;
+        v[k] = beta2 * v[k] + (one::<F>() - beta2) * g * g;
+        let m_hat = m[k] / correction1;
+        let v_hat = v[k] / correction2;
+        *p = *p - rate * m_hat / (v_hat.sqrt() + epsilon);
+    }
+}
+
+/// The AdamW method: the Adam method with decoupled weight decay.
+///
+/// Rather than being added to the gradient, where it would be rescaled by the
+/// adaptive step sizes of Adam, the decay is applied directly to each
+/// connection weight before the Adam update:
+///
+/// ```text
+/// w = w - rate * weight_decay * w
+/// ```
+///
+/// As is customary, biases are not decayed. The moment estimates are kept by
+/// the trained layers, see `RuleState`.
+pub struct AdamW<F: Float> {
+    /// The learning rate, roughly the largest step a parameter can make.
+    pub rate: F,
+    /// Decay rate of the estimate of the first moment (typically `0.9`).
+    pub beta1: F,
+    /// Decay rate of the estimate of the second moment (typically `0.999`).
+    pub beta2: F,
+    /// Small value avoiding divisions by zero (typically `1e-8`).
+    pub epsilon: F,
+    /// The strength of the weight decay.
+    pub weight_decay: F
+}
+
+impl<F: Float> AdamW<F> {
+    /// Creates an AdamW rule with given learning rate and weight decay, and
+    /// the typical values for the other parameters.
+    pub fn new(rate: F, weight_decay: F) -> AdamW<F> {
+        AdamW {
+            rate,
+            beta1: F::from(0.9).unwrap(),
+            beta2: F::from(0.999).unwrap(),
+            epsilon: F::from(1e-8).unwrap(),
+            weight_decay
+        }
+    }
+}
+
+impl<F: Float> Method for AdamW<F> {}
+
+impl<F: Float> GradientRule for AdamW<F> {
+    type Float = F;
+
+    fn apply(&self, state: &mut RuleState<F>, params: &mut [F], grads: &[F], weights: bool) {
+        if weights {
+            for p in params.iter_mut() {
+                *p = *p - self.rate * self.weight_decay * *p;
+            }
+        }
+        adam_step(state, params, grads, self.rate, self.beta1, self.beta2, self.epsilon);
+    }
+}
+
+/// Gradient descent with momentum: each parameter moves according to a
+/// velocity that accumulates its past gradients.
+///
+/// ```text
+/// v = momentum * v - rate * grad
+/// w = w + v
+/// ```
+///
+/// The velocities are kept by the trained layers, see `RuleState`.
+pub struct Momentum<F: Float> {
+    /// The learning rate associated with this rule.
+    pub rate: F,
+    /// The fraction of the velocity kept from one step to the next
+    /// (typically `0.9`).
+    pub momentum: F
+}
+
+impl<F: Float> Method for Momentum<F> {}
+
+impl<F: Float> GradientRule for Momentum<F> {
+    type Float = F;
+
+    fn apply(&self, state: &mut RuleState<F>, params: &mut [F], grads: &[F], _weights: bool) {
+        let v = &mut state.buffers(1, params.len())[0];
+        for (k, (p, &g)) in params.iter_mut().zip(grads.iter()).enumerate() {
+            v[k] = self.momentum * v[k] - self.rate * g;
+            *p = *p + v[k];
+        }
+    }
+}
+
+/// The RMSprop method: the gradient of each parameter is divided by a running
+/// average of its recent magnitude.
+///
+/// ```text
+/// avg = decay * avg + (1 - decay) * grad^2
+/// w = w - rate * grad / ( sqrt(avg) + epsilon )
+/// ```
+///
+/// The averages are kept by the trained layers, see `RuleState`.
+pub struct RMSprop<F: Float> {
+    /// The learning rate associated with this rule.
+    pub rate: F,
+    /// The decay rate of the average of squared gradients (typically `0.9`).
+    pub decay: F,
+    /// Small value avoiding divisions by zero (typically `1e-8`).
+    pub epsilon: F
+}
+
+impl<F: Float> Method for RMSprop<F> {}
+
+impl<F: Float> GradientRule for RMSprop<F> {
+    type Float = F;
+
+    fn apply(&self, state: &mut RuleState<F>, params: &mut [F], grads: &[F], _weights: bool) {
+        let avg = &mut state.buffers(1, params.len())[0];
+        for (k, (p, &g)) in params.iter_mut().zip(grads.iter()).enumerate() {
+            avg[k] = self.decay * avg[k] + (one::<F>() - self.decay) * g * g;
+            *p = *p - self.rate * g / (avg[k].sqrt() + self.epsilon);
+        }
+    }
+}
+
+/// The AdaGrad method: the gradient of each parameter is divided by the
+/// square root of the sum of all its past squared gradients.
+///
+/// ```text
+/// sum = sum + grad^2
+/// w = w - rate * grad / ( sqrt(sum) + epsilon )
+/// ```
+///
+/// The steps of a parameter thus keep shrinking as it receives gradients,
+/// while rarely updated parameters (like the weights of sparse features) keep
+/// large steps. The sums are kept by the trained layers, see `RuleState`.
+pub struct AdaGrad<F: Float> {
+    /// The learning rate associated with this rule.
+    pub rate: F,
+    /// Small value avoiding divisions by zero (typically `1e-8`).
+    pub epsilon: F
+}
+
+impl<F: Float> AdaGrad<F> {
+    /// Creates an AdaGrad rule with given learning rate and the typical value
+    /// of `epsilon`.
+    pub fn new(rate: F) -> AdaGrad<F> {
+        AdaGrad { rate, epsilon: F::from(1e-8).unwrap() }
+    }
+}
+
+impl<F: Float> Method for AdaGrad<F> {}
+
+impl<F: Float> GradientRule for AdaGrad<F> {
+    type Float = F;
+
+    fn apply(&self, state: &mut RuleState<F>, params: &mut [F], grads: &[F], _weights: bool) {
+        let sum = &mut state.buffers(1, params.len())[0];
+        for (k, (p, &g)) in params.iter_mut().zip(grads.iter()).enumerate() {
+            sum[k] = sum[k] + g * g;
+            *p = *p - self.rate * g / (sum[k].sqrt() + self.epsilon);
+        }
+    }
+}
+
+/*
+ * Learning rate scheduling
+ */
+
+/// A learning rate schedule, giving the rate to use at each epoch of a
+/// training.
+pub trait Scheduler<F: Float> {
+    /// The learning rate to use at given epoch, counting from `0`.
+    fn rate(&self, epoch: usize) -> F;
+
+    /// Sets the learning rate of `rule` to the one scheduled for given epoch.
+    fn update<R: Scheduled<F>>(&self, rule: &mut R, epoch: usize) {
+        rule.set_rate(self.rate(epoch));
+    }
+}
+
+/// A training rule whose learning rate can be driven by a `Scheduler`.
+pub trait Scheduled<F: Float> {
+    /// Replaces the learning rate of this rule.
+    fn set_rate(&mut self, rate: F);
+}
+
+impl<F: Float> Scheduled<F> for GradientDescent<F> {
+    fn set_rate(&mut self, rate: F) { self.rate = rate; }
+}
+
+impl<F: Float> Scheduled<F> for GradientDescentL2<F> {
+    fn set_rate(&mut self, rate: F) { self.rate = rate; }
+}
+
+impl<F: Float> Scheduled<F> for GradientDescentL1<F> {
+    fn set_rate(&mut self, rate: F) { self.rate = rate; }
+}
+
+impl<F: Float> Scheduled<F> for PerceptronRule<F> {
+    fn set_rate(&mut self, rate: F) { self.rate = rate; }
+}
+
+impl<F: Float> Scheduled<F> for Adam<F> {
+    fn set_rate(&mut self, rate: F) { self.rate = rate; }
+}
+
+impl<F: Float> Scheduled<F> for AdamW<F> {
+    fn set_rate(&mut self, rate: F) { self.rate = rate; }
+}
+
+impl<F: Float> Scheduled<F> for Momentum<F> {
+    fn set_rate(&mut self, rate: F) { self.rate = rate; }
+}
+
+impl<F: Float> Scheduled<F> for RMSprop<F> {
+    fn set_rate(&mut self, rate: F) { self.rate = rate; }
+}
+
+impl<F: Float> Scheduled<F> for AdaGrad<F> {
+    fn set_rate(&mut self, rate: F) { self.rate = rate; }
+}
+
+impl<F: Float, R: GradientRule<Float = F> + Scheduled<F>> Scheduled<F> for ClipNorm<R> {
+    fn set_rate(&mut self, rate: F) { self.rule.set_rate(rate); }
+}
+
+/// A schedule keeping the learning rate constant.
+pub struct ConstantRate<F: Float> {
+    /// The learning rate.
+    pub rate: F
+}
+
+impl<F: Float> Scheduler<F> for ConstantRate<F> {
+    fn rate(&self, _epoch: usize) -> F {
+        self.rate
+    }
+}
+
+/// A schedule multiplying the learning rate by `factor` every `step`
+/// epochs.
+pub struct StepDecay<F: Float> {
+    /// The learning rate at the first epoch.
+    pub rate: F,
+    /// The factor applied to the rate at the end of each step.
+    pub factor: F,
+    /// The number of epochs between two decays.
+    pub step: usize
+}
+
+impl<F: Float> Scheduler<F> for StepDecay<F> {
+    fn rate(&self, epoch: usize) -> F {
+        self.rate * self.factor.powi((epoch / self.step) as i32)
+    }
+}
+
+/// A schedule multiplying the learning rate by `gamma` at each epoch:
+/// `rate * gamma^epoch`.
+pub struct ExponentialDecay<F: Float> {
+    /// The learning rate at the first epoch.
+    pub rate: F,
+    /// The factor applied to the rate at each epoch.
+    pub gamma: F
+}
+
+impl<F: Float> Scheduler<F> for ExponentialDecay<F> {
+    fn rate(&self, epoch: usize) -> F {
+        self.rate * self.gamma.powi(epoch as i32)
+    }
+}
+
+/// A schedule decreasing the learning rate from `rate_max` to `rate_min`
+/// following half a cosine period over `period` epochs:
+///
+/// ```text
+/// rate_min + 0.5 * (rate_max - rate_min) * (1 + cos(pi * t / period))
+/// ```
+///
+/// Without restarts, the rate stays at `rate_min` after `period` epochs. With
+/// warm restarts, `t` goes back to 0 at the end of each cycle, bringing the
+/// rate back to `rate_max`.
+///
+/// A `period` of 0 is treated as a period of 1 epoch.
+pub struct CosineAnnealing<F: Float> {
+    /// The learning rate at the start of each cycle.
+    pub rate_max: F,
+    /// The learning rate at the end of each cycle.
+    pub rate_min: F,
+    /// The length of a cycle, in epochs.
+    pub period: usize,
+    /// Whether to restart a new cycle after each one.
+    pub restarts: bool
+}
+
+impl<F: Float> Scheduler<F> for CosineAnnealing<F> {
+    fn rate(&self, epoch: usize) -> F {
+        let period = self.period.max(1);
+        let t = if self.restarts { epoch % period } else { epoch.min(period) };
+        let progress = F::from(t).unwrap() / F::from(period).unwrap();
+        let half = F::from(0.5).unwrap();
+        let pi = F::from(::core::f64::consts::PI).unwrap();
+        self.rate_min + half * (self.rate_max - self.rate_min) * (one::<F>() + (pi * progress).cos())
+    }
+}
+
+/*
+ * Early stopping
+ */
+
+/// A criterion to stop training when the validation error stops improving.
+///
+/// The error is reported once per epoch with `update()`, which tells when the
+/// error has not improved by at least `min_delta` for `patience` epochs in a
+/// row. The weights of the network at its best epoch can optionally be kept,
+/// to be restored once training stops:
+///
+/// ```
+/// # extern crate silinapse;
+/// # use silinapse::{Compute, FeedforwardLayer};
+/// # use silinapse::activations::identity;
+/// # use silinapse::loss::{Loss, MeanSquaredError, EpochOptions, train_epochs};
+/// # use silinapse::training::{EarlyStopping, GradientDescent};
+/// # fn main() {
+/// let train = vec![(vec![1.0f64], vec![2.0]), (vec![2.0], vec![4.0])];
+/// let validation = vec![(vec![3.0f64], vec![6.0])];
+/// let mut layer = FeedforwardLayer::new(1, 1, identity());
+/// let mut stopping = EarlyStopping::new(5, 1e-6);
+/// for _ in 0..1000 {
+///     train_epochs(&mut layer, &GradientDescent::new(0.05), &MeanSquaredError, &train, 1, EpochOptions::new());
+///     let error = validation.iter()
+///                           .map(|&(ref i, ref t)| MeanSquaredError.loss(&layer.compute(i), t))
+///                           .sum::<f64>();
+///     if stopping.update_with_weights(error, || layer.save_weights()) {
+///         break;
+///     }
+/// }
+/// if let Some(&(ref coeffs, ref biases)) = stopping.best_weights() {
+///     layer.load_weights(coeffs, biases).unwrap();
+/// }
+/// # }
+/// ```
+pub struct EarlyStopping<F: Float> {
+    /// The number of epochs without improvement after which to stop.
+    pub patience: usize,
+    /// The minimal decrease of the error counting as an improvement.
+    pub min_delta: F,
+    best: Option<F>,
+    best_epoch: usize,
+    epoch: usize,
+    best_weights: Option<(Vec<F>, Vec<F>)>
+}
+
+impl<F: Float> EarlyStopping<F> {
+    /// Creates a new early stopping criterion.
+    pub fn new(patience: usize, min_delta: F) -> EarlyStopping<F> {
+        EarlyStopping {
+            patience,
+            min_delta,
+            best: None,
+            best_epoch: 0,
+            epoch: 0,
+            best_weights: None
+        }
+    }
+
+    /// Reports the validation error of a new epoch, and returns whether
+    /// training should stop.
+    pub fn update(&mut self, error: F) -> bool {
+        self.record(error);
+        self.should_stop()
+    }
+
+    /// Same as `update()`, but also keeps the weights given by `weights` (for
+    /// example `FeedforwardLayer::save_weights`) if this is a new best epoch.
+    pub fn update_with_weights<W>(&mut self, error: F, weights: W) -> bool
+        where W: FnOnce() -> (Vec<F>, Vec<F>)
+    {
+        if self.record(error) {
+            self.best_weights = Some(weights());
+        }
+        self.should_stop()
+    }
+
+    /// Records the error of a new epoch, returning whether it improved on the
+    /// best one.
+    fn record(&mut self, error: F) -> bool {
+        let epoch = self.epoch;
+        self.epoch += 1;
+        let improved = match self.best {
+            Some(best) => error < best - self.min_delta,
+            None => true
+        };
+        if improved {
+            self.best = Some(error);
+            self.best_epoch = epoch;
+        }
+        improved
+    }
+
+    fn should_stop(&self) -> bool {
+        self.epoch - 1 - self.best_epoch >= self.patience
+    }
+
+    /// The best error reported so far.
+    pub fn best(&self) -> Option<F> {
+        self.best
+    }
+
+    /// The epoch at which the best error was reported, counting from `0`.
+    pub fn best_epoch(&self) -> usize {
+        self.best_epoch
+    }
+
+    /// The weights kept at the best epoch, if any.
+    pub fn best_weights(&self) -> Option<&(Vec<F>, Vec<F>)> {
+        self.best_weights.as_ref()
+    }
+}
+
+/*
+ * Callbacks
+ */
+
+/// An observer of the progress of training, notified by
+/// `loss::train_epochs()`.
+///
+/// All the hooks do nothing by default.
+pub trait TrainingCallback<F: Float> {
+    /// Called at the end of each epoch, with its number (counting from `0`)
+    /// and its average error.
+    fn on_epoch_end(&mut self, epoch: usize, loss: F) {
+        let _ = (epoch, loss);
+    }
+
+    /// Called after each training step, with its position in the epoch and
+    /// its error.
+    fn on_batch_end(&mut self, batch: usize, loss: F) {
+        let _ = (batch, loss);
+    }
+
+    /// Called at the end of each epoch after `on_epoch_end()`, with the
+    /// network being trained, for example to take a copy of its parameters.
+    fn on_epoch_parameters(&mut self, epoch: usize, loss: F, network: &dyn Parametrized<F>) {
+        let _ = (epoch, loss, network);
+    }
+}
+
+/// A callback doing nothing, for training loops that need no observer.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoCallback;
+
+impl<F: Float> TrainingCallback<F> for NoCallback {}
+
+/// A callback printing the error to the standard error output every
+/// `every` epochs. Requires the `std` feature.
+#[cfg(feature = "std")]
+pub struct PrintProgress {
+    /// The number of epochs between two reports.
+    pub every: usize
+}
+
+#[cfg(feature = "std")]
+impl PrintProgress {
+    /// Creates a new callback reporting every `every` epochs.
+    pub fn new(every: usize) -> PrintProgress {
+        PrintProgress { every }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<F: Float + fmt::Display> TrainingCallback<F> for PrintProgress {
+    fn on_epoch_end(&mut self, epoch: usize, loss: F) {
+        if self.every > 0 && epoch % self.every == 0 {
+            eprintln!("epoch {}: loss {}", epoch, loss);
+        }
+    }
+}
+
+/// A callback recording all the errors reported during training, for example
+/// to plot learning curves.
+#[derive(Clone, Debug, Default)]
+pub struct LossHistory<F> {
+    /// The average error of each epoch.
+    pub losses: Vec<F>,
+    /// The error of each training step, across all epochs.
+    pub batch_losses: Vec<F>
+}
+
+impl<F> LossHistory<F> {
+    /// Creates a new empty history.
+    pub fn new() -> LossHistory<F> {
+        LossHistory { losses: Vec::new(), batch_losses: Vec::new() }
+    }
+}
+
+impl<F: Float> TrainingCallback<F> for LossHistory<F> {
+    fn on_epoch_end(&mut self, _epoch: usize, loss: F) {
+        self.losses.push(loss);
+    }
+
+    fn on_batch_end(&mut self, _batch: usize, loss: F) {
+        self.batch_losses.push(loss);
+    }
+}
+
+/*
+ * Checkpoints
+ */
+
+/// A copy of the parameters of a network at the end of an epoch.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Snapshot<F> {
+    /// The epoch at the end of which the snapshot was taken.
+    pub epoch: usize,
+    /// The error reported for this epoch.
+    pub loss: F,
+    /// The parameters of the network, in the order of
+    /// `Parametrized::parameters_iter()`.
+    pub parameters: Vec<F>
+}
+
+impl<F: Float> Snapshot<F> {
+    /// Takes a snapshot of the parameters of `network`.
+    pub fn take<N: Parametrized<F> + ?Sized>(network: &N, epoch: usize, loss: F) -> Snapshot<F> {
+        Snapshot { epoch, loss, parameters: network.parameters_iter().cloned().collect() }
+    }
+
+    /// Restores the parameters of `network` to the ones of this snapshot.
+    ///
+    /// The network should be the one the snapshot was taken from: extra
+    /// parameters on either side are ignored.
+    pub fn restore<N: Parametrized<F> + ?Sized>(&self, network: &mut N) {
+        for (p, &s) in network.parameters_mut().zip(&self.parameters) {
+            *p = s;
+        }
+    }
+}
+
+/// Keeps snapshots of a network during its training, to be able to come back
+/// to an earlier state, for example after the training diverged.
+///
+/// A snapshot is taken every `every` epochs (counting from epoch `0`), and
+/// only the `keep_last` most recent ones are kept. Independently of them, the
+/// snapshot of the epoch with the lowest error so far is always kept.
+///
+/// Snapshots are taken when the checkpoint is given as the callback of
+/// `loss::train_epochs()`, or manually using `record()`. With the `serde` feature, a checkpoint can be serialized
+/// to be saved to disk.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Checkpoint<F> {
+    /// The number of epochs between two snapshots, `0` to only keep the best
+    /// one.
+    pub every: usize,
+    /// The number of periodic snapshots to keep.
+    pub keep_last: usize,
+    snapshots: Vec<Snapshot<F>>,
+    best: Option<Snapshot<F>>
+}
+
+impl<F: Float> Checkpoint<F> {
+    /// Creates a new checkpoint taking a snapshot every `every` epochs and
+    /// keeping the `keep_last` most recent ones.
+    pub fn new(every: usize, keep_last: usize) -> Checkpoint<F> {
+        Checkpoint { every, keep_last, snapshots: Vec::new(), best: None }
+    }
+
+    /// Records the state of `network` at the end of `epoch`, whose error was
+    /// `loss`.
+    ///
+    /// A non-finite error never makes a snapshot the best one.
+    pub fn record<N: Parametrized<F> + ?Sized>(&mut self, network: &N, epoch: usize, loss: F) {
+        let improved = loss.is_finite() && self.best.as_ref().map_or(true, |b| loss < b.loss);
+        let periodic = self.every > 0 && self.keep_last > 0 && epoch % self.every == 0;
+        if !improved && !periodic {
+            return;
+        }
+        let snapshot = Snapshot::take(network, epoch, loss);
+        if periodic {
+            if self.snapshots.len() == self.keep_last {
+                self.snapshots.remove(0);
+            }
+            self.snapshots.push(snapshot.clone());
+        }
+        if improved {
+            self.best = Some(snapshot);
+        }
+    }
+
+    /// The periodic snapshots kept, from the oldest to the most recent.
+    pub fn snapshots(&self) -> &[Snapshot<F>] {
+        &self.snapshots
+    }
+
+    /// The snapshot of the epoch with the lowest error so far.
+    pub fn best(&self) -> Option<&Snapshot<F>> {
+        self.best.as_ref()
+    }
+
+    /// Restores `network` to its state at the epoch with the lowest error so
+    /// far, and returns this epoch.
+    ///
+    /// Does nothing and returns `None` if no snapshot was recorded.
+    pub fn restore_best<N: Parametrized<F> + ?Sized>(&self, network: &mut N) -> Option<usize> {
+        self.best.as_ref().map(|b| { b.restore(network); b.epoch })
+    }
+
+    /// Restores `network` to its state at the most recent periodic snapshot,
+    /// and returns its epoch.
+    ///
+    /// Does nothing and returns `None` if no periodic snapshot was kept.
+    pub fn restore_last<N: Parametrized<F> + ?Sized>(&self, network: &mut N) -> Option<usize> {
+        self.snapshots.last().map(|s| { s.restore(network); s.epoch })
+    }
+}
+
+impl<F: Float> TrainingCallback<F> for Checkpoint<F> {
+    fn on_epoch_parameters(&mut self, epoch: usize, loss: F, network: &dyn Parametrized<F>) {
+        self.record(network, epoch, loss);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Scheduler, ConstantRate, StepDecay, ExponentialDecay, CosineAnnealing, EarlyStopping};
+    use super::{GradientRule, RuleState, ClipNorm, clip_norm, GradientDescent, GradientDescentL1, GradientDescentL2, Momentum, RMSprop, AdaGrad};
+    use super::{Adam, AdamW, Checkpoint};
+    use Parametrized;
+    use activations::identity;
+    use feedforward::FeedforwardLayer;
+
+    /// Number of steps needed by `rule` to bring the minimization of
+    /// `0.5 * (x^2 + 0.01 * y^2)` from `(1, 1)` to within `0.01` of its minimum.
+    fn steps_to_converge<R: GradientRule<Float = f64>>(rule: &R) -> usize {
+        let mut state = RuleState::new();
+        let mut params = [1.0f64, 1.0];
+        for step in 0..100_000 {
+            if params[0].abs() < 0.01 && params[1].abs() < 0.01 {
+                return step;
+            }
+            let grads = [params[0], 0.01 * params[1]];
+            rule.apply(&mut state, &mut params, &grads, true);
+        }
+        panic!("did not converge");
+    }
+
+    #[test]
+    fn momentum_on_ill_conditioned_quadratic() {
+        let plain = steps_to_converge(&GradientDescent::new(0.5));
+        let momentum = steps_to_converge(&Momentum { rate: 0.5, momentum: 0.9 });
+        assert!(momentum < plain);
+    }
+
+    #[test]
+    fn rmsprop_adaptive_scaling() {
+        let rule = RMSprop { rate: 0.01, decay: 0.9, epsilon: 1e-8 };
+        let mut state = RuleState::new();
+        let mut params = [0.0f64, 0.0];
+        for _ in 0..10 {
+            let before = params;
+            rule.apply(&mut state, &mut params, &[1000.0, 0.001], true);
+            let steps = [before[0] - params[0], before[1] - params[1]];
+            // both weights move by the same amount despite gradients
+            // differing by six orders of magnitude
+            assert!(steps[0] > 0.0 && steps[1] > 0.0);
+            assert!((steps[0] - steps[1]).abs() < 1e-3 * steps[0]);
+        }
+        // as the averages warm up, the steps get closer to the rate
+        let before = params[0];
+        rule.apply(&mut state, &mut params, &[1000.0, 0.001], true);
+        let expected = 0.01 / (1.0 - 0.9f64.powi(11)).sqrt();
+        assert!((before - params[0] - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn adagrad_shrinking_steps() {
+        let rule = AdaGrad::new(0.1f64);
+        let mut state = RuleState::new();
+        let mut params = [0.0f64, 0.0];
+        let mut last = f64::INFINITY;
+        for t in 1..20 {
+            let before = params;
+            // the second weight only gets a gradient on the last step
+            let sparse = if t == 19 { 1.0 } else { 0.0 };
+            rule.apply(&mut state, &mut params, &[5.0, sparse], true);
+            let step = before[0] - params[0];
+            assert!(step > 0.0 && step < last);
+            assert!((step - 0.1 / (t as f64).sqrt()).abs() < 1e-9);
+            last = step;
+        }
+        // the rarely updated weight still makes a full step
+        assert!((params[1] + 0.1).abs() < 1e-6);
+        assert!(params[1].abs() > last);
+    }
+
+    #[test]
+    fn adamw_decoupled_decay() {
+        // Adam with an L2 penalty folded into the gradient
+        let adam = Adam::new(0.01f64);
+        let mut adam_state = RuleState::new();
+        let mut adam_params = [1.0f64, 1.0];
+        let adamw = AdamW::new(0.01f64, 0.1);
+        let mut adamw_state = RuleState::new();
+        let mut adamw_params = [1.0f64, 1.0];
+        for _ in 0..5 {
+            // the first weight has no gradient from the error, only decay
+            let grads = [0.1 * adam_params[0], 1.0 + 0.1 * adam_params[1]];
+            adam.apply(&mut adam_state, &mut adam_params, &grads, true);
+            adamw.apply(&mut adamw_state, &mut adamw_params, &[0.0, 1.0], true);
+        }
+        // the normalized L2 gradient makes Adam take steps of about the rate,
+        // while the decoupled decay is proportional to the weight
+        assert!((adam_params[0] - 0.95).abs() < 1e-3);
+        assert!((adamw_params[0] - 0.999f64.powi(5)).abs() < 1e-12);
+        assert!(adamw_params[1] != adam_params[1]);
+
+        // biases are not decayed
+        let mut biases = [1.0f64];
+        adamw.apply(&mut RuleState::new(), &mut biases, &[0.0], false);
+        assert_eq!(biases, [1.0]);
+    }
+
+    #[test]
+    fn weight_decay() {
+        let rule = GradientDescentL2 { rate: 0.1, weight_decay: 0.5 };
+        let mut state = RuleState::new();
+        let mut weights = [2.0f64, -4.0];
+        let mut biases = [2.0f64];
+        for step in 1..4 {
+            rule.apply(&mut state, &mut weights, &[0.0, 0.0], true);
+            rule.apply(&mut state, &mut biases, &[0.0], false);
+            // with no gradient, weights shrink by 1 - rate*decay each step
+            let factor = 0.95f64.powi(step);
+            assert!((weights[0] - 2.0 * factor).abs() < 1e-12);
+            assert!((weights[1] + 4.0 * factor).abs() < 1e-12);
+            assert_eq!(biases[0], 2.0);
+        }
+    }
+
+    #[test]
+    fn l1_regularization() {
+        let rule = GradientDescentL1 { rate: 0.1, lambda: 0.5 };
+        let mut state = RuleState::new();
+        let mut weights = [1.0f64, 0.0, -1.0];
+        rule.apply(&mut state, &mut weights, &[0.0, 0.0, 0.0], true);
+        assert!((weights[0] - 0.95).abs() < 1e-12);
+        assert_eq!(weights[1], 0.0);
+        assert!((weights[2] + 0.95).abs() < 1e-12);
+    }
+
+    #[test]
+    fn norm_clipping() {
+        let mut grads = [3.0f64, 4.0];
+        clip_norm(&mut grads, 1.0);
+        assert!((grads[0] - 0.6).abs() < 1e-12);
+        assert!((grads[1] - 0.8).abs() < 1e-12);
+        // already within the limit
+        clip_norm(&mut grads, 2.0);
+        assert!((grads[0] - 0.6).abs() < 1e-12);
+
+        let rule = ClipNorm { rule: GradientDescent::new(1.0), max_norm: 2.0 };
+        let mut params = [0.0f64, 0.0];
+        rule.apply(&mut RuleState::new(), &mut params, &[30.0, 40.0], true);
+        assert!(((params[0].powi(2) + params[1].powi(2)).sqrt() - 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn schedulers() {
+        let constant = ConstantRate { rate: 0.3f64 };
+        assert_eq!(constant.rate(0), 0.3);
+        assert_eq!(constant.rate(1000), 0.3);
+
+        let step = StepDecay { rate: 0.8f64, factor: 0.5, step: 10 };
+        assert_eq!(step.rate(0), 0.8);
+        assert_eq!(step.rate(9), 0.8);
+        assert_eq!(step.rate(10), 0.4);
+        assert_eq!(step.rate(25), 0.2);
+
+        let exp = ExponentialDecay { rate: 0.8f64, gamma: 0.9 };
+        for epoch in 0..20 {
+            assert!((exp.rate(epoch) - 0.8 * 0.9f64.powi(epoch as i32)).abs() < 1e-12);
+        }
+
+        let mut rule = GradientDescent::new(1.0f64);
+        step.update(&mut rule, 12);
+        assert_eq!(rule.rate, 0.4);
+    }
+
+    #[test]
+    fn cosine_annealing() {
+        let mut cosine = CosineAnnealing { rate_max: 0.1f64, rate_min: 0.01, period: 10, restarts: false };
+        assert_eq!(cosine.rate(0), 0.1);
+        assert!((cosine.rate(5) - 0.055).abs() < 1e-12);
+        assert!((cosine.rate(10) - 0.01).abs() < 1e-12);
+        assert!((cosine.rate(25) - 0.01).abs() < 1e-12);
+        for epoch in 0..10 {
+            assert!(cosine.rate(epoch + 1) < cosine.rate(epoch));
+        }
+
+        cosine.restarts = true;
+        assert!(cosine.rate(9) > 0.01 && cosine.rate(9) < 0.02);
+        assert_eq!(cosine.rate(10), 0.1);
+        assert!((cosine.rate(15) - 0.055).abs() < 1e-12);
+        assert_eq!(cosine.rate(20), 0.1);
+
+        cosine.period = 0;
+        assert_eq!(cosine.rate(3), 0.1);
+        cosine.restarts = false;
+        assert_eq!(cosine.rate(0), 0.1);
+        assert!((cosine.rate(3) - 0.01).abs() < 1e-12);
+    }
+
+    #[test]
+    fn early_stopping() {
+        let errors = [1.0f64, 0.8, 0.6, 0.59, 0.595, 0.6, 0.58, 0.5];
+        let mut stopping = EarlyStopping::new(3, 0.05);
+        let stopped = errors.iter().position(|&e| stopping.update(e));
+        assert_eq!(stopped, Some(5));
+        assert_eq!(stopping.best(), Some(0.6));
+        assert_eq!(stopping.best_epoch(), 2);
+        assert!(stopping.best_weights().is_none());
+
+        let mut stopping = EarlyStopping::new(2, 0.0);
+        let mut stopped = None;
+        for (epoch, &e) in errors.iter().enumerate() {
+            if stopping.update_with_weights(e, || (vec![epoch as f64], vec![])) {
+                stopped = Some(epoch);
+                break;
+            }
+        }
+        assert_eq!(stopped, Some(5));
+        assert_eq!(stopping.best_weights(), Some(&(vec![3.0], vec![])));
+    }
+
+    #[test]
+    fn checkpoint_restores_best() {
+        let mut layer = FeedforwardLayer::new(2, 1, identity());
+        let mut checkpoint = Checkpoint::new(1, 2);
+        let set = |layer: &mut FeedforwardLayer<f64, _, _>, value: f64| {
+            for p in layer.parameters_mut() { *p = value; }
+        };
+        set(&mut layer, 1.0);
+        checkpoint.record(&layer, 0, 1.0);
+        set(&mut layer, 2.0);
+        checkpoint.record(&layer, 1, 0.5);
+        // the training diverges afterwards
+        set(&mut layer, 30.0);
+        checkpoint.record(&layer, 2, 4.0);
+        set(&mut layer, 1e100);
+        checkpoint.record(&layer, 3, f64::NAN);
+
+        // the best snapshot is kept even after leaving the last ones
+        assert_eq!(checkpoint.snapshots().iter().map(|s| s.epoch).collect::<Vec<_>>(), vec![2, 3]);
+        assert_eq!(checkpoint.restore_best(&mut layer), Some(1));
+        assert_eq!(layer.weights(), &[2.0, 2.0]);
+        assert_eq!(layer.biases(), &[2.0]);
+        assert_eq!(checkpoint.restore_last(&mut layer), Some(3));
+        assert_eq!(layer.weights(), &[1e100, 1e100]);
+
+        assert_eq!(Checkpoint::<f64>::new(1, 1).restore_best(&mut layer), None);
+    }
+}
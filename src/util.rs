@@ -1,11 +1,13 @@
 //! A set of utility method to combine networks.
 
-use std::iter::repeat;
-use std::marker::PhantomData;
+use alloc::borrow::ToOwned;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
 
 use num::{Float, zero};
 
-use Compute;
+use {Compute, Trainable, Parametrized, ModeAware, ShapeError, output_error};
 use {Method, UnsupervisedTrain, SupervisedTrain, BackpropTrain};
 
 /*
@@ -14,7 +16,7 @@ use {Method, UnsupervisedTrain, SupervisedTrain, BackpropTrain};
 
 /// An adapter tha chains two networks, linking the first's ouput to
 /// the second's input.
-pub struct Chain<F, A, B> where A: Compute<F>, B: Compute<F> {
+pub struct Chain<F, A, B> where F: Float, A: Compute<F>, B: Compute<F> {
     _marker: PhantomData<F>,
     first: A,
     second: B
@@ -25,7 +27,16 @@ impl<F, A, B> Chain<F, A, B>
 {
     /// Chains the two given adapters
     pub fn new(first: A, second: B) -> Chain<F, A, B> {
-        Chain { _marker: PhantomData, first: first, second: second }
+        Chain { _marker: PhantomData, first, second }
+    }
+
+    /// Chains the two given adapters, checking that the output size of the
+    /// first matches the input size of the second.
+    pub fn try_new(first: A, second: B) -> Result<Chain<F, A, B>, ShapeError> {
+        if first.output_size() != second.input_size() {
+            return Err(ShapeError { expected: first.output_size(), actual: second.input_size() });
+        }
+        Ok(Chain::new(first, second))
     }
 }
 
@@ -48,24 +59,24 @@ impl<F, A, B> Compute<F> for Chain<F, A, B>
 /// The backpropagation training on a chain is computed this way:
 ///
 /// - first compute the output of the first layer
-/// - use the output of this layer to train the second with the target
-/// - use the output of the training of the second layer as a target
-///   to train the first
+/// - use the output of this layer to train the second with the error
+/// - use the gradient returned by the training of the second layer as
+///   the error to train the first
 impl<F, A, B, M> BackpropTrain<F, M> for Chain<F, A, B>
     where F: Float,
           A: BackpropTrain<F, M> + Compute<F>,
           B: BackpropTrain<F, M> + Compute<F>,
           M: Method
 {
-    fn backprop_train(&mut self, rule: &M, input: &[F], target: &[F]) -> Vec<F> {
+    fn backprop_train(&mut self, rule: &M, input: &[F], error: &[F]) -> Vec<F> {
         let mid_input = self.first.compute(input);
-        let mid_target = self.second.backprop_train(rule, &mid_input, target);
-        self.first.backprop_train(rule, input, &mid_target)
+        let mid_error = self.second.backprop_train(rule, &mid_input, error);
+        self.first.backprop_train(rule, input, &mid_error)
     }
 }
 
-/// The supervised training on a chain is computed the same way as the
-/// backprop training, simply discarding its output.
+/// The supervised training on a chain uses the mean squared error: the chain
+/// is trained by backpropagation with the error signal `2*(output - target)/n`.
 impl<F, A, B, M> SupervisedTrain<F, M> for Chain<F, A, B>
     where F: Float,
           A: BackpropTrain<F, M> + Compute<F>,
@@ -73,7 +84,41 @@ impl<F, A, B, M> SupervisedTrain<F, M> for Chain<F, A, B>
           M: Method
 {
     fn supervised_train(&mut self, rule: &M, input: &[F], target: &[F]) {
-        self.backprop_train(rule, input, target);
+        let error = output_error(&self.compute(input), target);
+        self.backprop_train(rule, input, &error);
+    }
+}
+
+impl<F, A, B> ModeAware for Chain<F, A, B>
+    where F: Float, A: Compute<F> + ModeAware, B: Compute<F> + ModeAware
+{
+    fn set_mode(&mut self, training: bool) {
+        self.first.set_mode(training);
+        self.second.set_mode(training);
+    }
+}
+
+impl<F, A, B> Trainable for Chain<F, A, B>
+    where F: Float, A: Compute<F> + Trainable, B: Compute<F> + Trainable
+{
+    fn num_parameters(&self) -> usize {
+        self.first.num_parameters() + self.second.num_parameters()
+    }
+
+    fn has_nan(&self) -> bool {
+        self.first.has_nan() || self.second.has_nan()
+    }
+}
+
+impl<F, A, B> Parametrized<F> for Chain<F, A, B>
+    where F: Float, A: Compute<F> + Parametrized<F>, B: Compute<F> + Parametrized<F>
+{
+    fn parameters_iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a F> + 'a> {
+        Box::new(self.first.parameters_iter().chain(self.second.parameters_iter()))
+    }
+
+    fn parameters_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = &'a mut F> + 'a> {
+        Box::new(self.first.parameters_mut().chain(self.second.parameters_mut()))
     }
 }
 
@@ -94,7 +139,7 @@ impl<F, A, B> Parallel<F, A, B>
 {
     /// Chains the two given adapters
     pub fn new(first: A, second: B) -> Parallel<F, A, B> {
-        Parallel { _marker: PhantomData, first: first, second: second }
+        Parallel { _marker: PhantomData, first, second }
     }
 }
 
@@ -102,13 +147,13 @@ impl<F, A, B> Compute<F> for Parallel<F, A, B>
     where F: Float, A:Compute<F>, B: Compute<F>
 {
     fn compute(&self, input: &[F]) -> Vec<F> {
-        let mut v = self.first.compute(&input);
-        v.extend(self.second.compute(&input));
+        let mut v = self.first.compute(input);
+        v.extend(self.second.compute(input));
         v
     }
 
     fn input_size(&self) -> usize {
-        ::std::cmp::max(self.first.input_size(), self.second.input_size())
+        ::core::cmp::max(self.first.input_size(), self.second.input_size())
     }
 
     fn output_size(&self) -> usize {
@@ -146,6 +191,504 @@ impl<F, A, B, M> SupervisedTrain<F, M> for Parallel<F, A, B>
     }
 }
 
+/// The backpropagation training on a parallel adapter splits the error between
+/// both networks according to their output sizes, trains each of them with
+/// its part, and returns the sum of the gradients they return, as they share
+/// the same input.
+///
+/// More than two branches can be obtained by nesting parallel adapters.
+impl<F, A, B, M> BackpropTrain<F, M> for Parallel<F, A, B>
+    where F: Float,
+          A: BackpropTrain<F, M> + Compute<F>,
+          B: BackpropTrain<F, M> + Compute<F>,
+          M: Method
+{
+    fn backprop_train(&mut self, rule: &M, input: &[F], error: &[F]) -> Vec<F> {
+        let n = ::core::cmp::min(self.first.output_size(), error.len());
+        let mut returned = self.first.backprop_train(rule, input, &error[..n]);
+        let other = self.second.backprop_train(rule, input, &error[n..]);
+        if returned.len() < other.len() {
+            returned.resize(other.len(), zero());
+        }
+        for (g, o) in returned.iter_mut().zip(other) {
+            *g = *g + o;
+        }
+        returned
+    }
+}
+
+impl<F, A, B> ModeAware for Parallel<F, A, B>
+    where F: Float, A: Compute<F> + ModeAware, B: Compute<F> + ModeAware
+{
+    fn set_mode(&mut self, training: bool) {
+        self.first.set_mode(training);
+        self.second.set_mode(training);
+    }
+}
+
+impl<F, A, B> Trainable for Parallel<F, A, B>
+    where F: Float, A: Compute<F> + Trainable, B: Compute<F> + Trainable
+{
+    fn num_parameters(&self) -> usize {
+        self.first.num_parameters() + self.second.num_parameters()
+    }
+
+    fn has_nan(&self) -> bool {
+        self.first.has_nan() || self.second.has_nan()
+    }
+}
+
+impl<F, A, B> Parametrized<F> for Parallel<F, A, B>
+    where F: Float, A: Compute<F> + Parametrized<F>, B: Compute<F> + Parametrized<F>
+{
+    fn parameters_iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a F> + 'a> {
+        Box::new(self.first.parameters_iter().chain(self.second.parameters_iter()))
+    }
+
+    fn parameters_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = &'a mut F> + 'a> {
+        Box::new(self.first.parameters_mut().chain(self.second.parameters_mut()))
+    }
+}
+
+/*
+ * Sequence
+ */
+
+/// A network made of a sequence of layers, each of them feeding its output
+/// into the input of the next one.
+///
+/// The layers are stored as boxed trait objects of type `L`. By default they
+/// only need to implement `Compute`, but a sequence of `BackpropLayer` objects
+/// can be trained as a whole:
+///
+/// ```
+/// # use silinapse::FeedforwardLayer;
+/// # use silinapse::activations::sigmoid;
+/// # use silinapse::training::GradientDescent;
+/// # use silinapse::util::{BackpropLayer, Sequential};
+/// let mut net: Sequential<f32, dyn BackpropLayer<f32, GradientDescent<f32>>> = Sequential::new();
+/// net.push(Box::new(FeedforwardLayer::new(2, 3, sigmoid()))).unwrap();
+/// net.push(Box::new(FeedforwardLayer::new(3, 1, sigmoid()))).unwrap();
+/// ```
+pub struct Sequential<F: Float, L: ?Sized = dyn Compute<F>> {
+    _marker: PhantomData<F>,
+    layers: Vec<Box<L>>
+}
+
+/// A layer that can be trained by backpropagation using the method `M`.
+///
+/// This trait is automatically implemented for all types implementing
+/// `Compute`, `BackpropTrain`, `Trainable`, `Parametrized` and `ModeAware`,
+/// and exists so that such layers can be stored in a `Sequential`.
+///
+/// Layers without parameters can rely on the default methods of these traits:
+///
+/// ```
+/// # use silinapse::{Trainable, Parametrized, ModeAware};
+/// struct MyLayer;
+///
+/// impl Trainable for MyLayer {}
+/// impl Parametrized<f32> for MyLayer {}
+/// impl ModeAware for MyLayer {}
+/// ```
+pub trait BackpropLayer<F: Float, M: Method>:
+    Compute<F> + BackpropTrain<F, M> + Trainable + Parametrized<F> + ModeAware {}
+
+impl<F, M, T> BackpropLayer<F, M> for T
+    where F: Float, M: Method, T: Compute<F> + BackpropTrain<F, M> + Trainable + Parametrized<F> + ModeAware
+{}
+
+impl<F, L> Sequential<F, L>
+    where F: Float, L: ?Sized + Compute<F>
+{
+    /// Creates a new empty sequence of layers.
+    ///
+    /// An empty sequence computes nothing and returns an empty output.
+    pub fn new() -> Sequential<F, L> {
+        Sequential { _marker: PhantomData, layers: Vec::new() }
+    }
+
+    /// Appends a layer at the end of the sequence.
+    ///
+    /// The input size of the new layer must match the output size of the
+    /// current last layer, otherwise it is not inserted and an error is returned.
+    pub fn push(&mut self, layer: Box<L>) -> Result<(), ShapeError> {
+        if let Some(last) = self.layers.last() {
+            if last.output_size() != layer.input_size() {
+                return Err(ShapeError {
+                    expected: last.output_size(),
+                    actual: layer.input_size()
+                });
+            }
+        }
+        self.layers.push(layer);
+        Ok(())
+    }
+
+    /// The number of layers in this sequence.
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Whether this sequence contains no layer at all.
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+}
+
+impl<F, L> Default for Sequential<F, L>
+    where F: Float, L: ?Sized + Compute<F>
+{
+    fn default() -> Sequential<F, L> {
+        Sequential::new()
+    }
+}
+
+impl<F, L> Compute<F> for Sequential<F, L>
+    where F: Float, L: ?Sized + Compute<F>
+{
+    fn compute(&self, input: &[F]) -> Vec<F> {
+        let mut value = input.to_owned();
+        for layer in &self.layers {
+            value = layer.compute(&value);
+        }
+        if self.layers.is_empty() { value.clear(); }
+        value
+    }
+
+    fn input_size(&self) -> usize {
+        self.layers.first().map(|l| l.input_size()).unwrap_or(0)
+    }
+
+    fn output_size(&self) -> usize {
+        self.layers.last().map(|l| l.output_size()).unwrap_or(0)
+    }
+}
+
+/// The backpropagation training on a sequence is computed this way:
+///
+/// - first run the forward pass, keeping the input of every layer
+/// - train the last layer with the error
+/// - then train each layer in reverse order, using as error the gradient
+///   returned by the training of the layer after it
+///
+/// The returned value is the gradient returned by the training of the first
+/// layer.
+impl<F, L, M> BackpropTrain<F, M> for Sequential<F, L>
+    where F: Float, L: ?Sized + Compute<F> + BackpropTrain<F, M>, M: Method
+{
+    fn backprop_train(&mut self, rule: &M, input: &[F], error: &[F]) -> Vec<F> {
+        let mut inputs = Vec::with_capacity(self.layers.len());
+        let mut value = input.to_owned();
+        for layer in &self.layers {
+            let next = layer.compute(&value);
+            inputs.push(value);
+            value = next;
+        }
+        let mut signal = error.to_owned();
+        for (layer, input) in self.layers.iter_mut().zip(inputs.iter()).rev() {
+            signal = layer.backprop_train(rule, input, &signal);
+        }
+        signal
+    }
+}
+
+/// The supervised training on a sequence uses the mean squared error: the
+/// sequence is trained by backpropagation with the error signal
+/// `2*(output - target)/n`.
+impl<F, L, M> SupervisedTrain<F, M> for Sequential<F, L>
+    where F: Float, L: ?Sized + Compute<F> + BackpropTrain<F, M>, M: Method
+{
+    fn supervised_train(&mut self, rule: &M, input: &[F], target: &[F]) {
+        let error = output_error(&self.compute(input), target);
+        self.backprop_train(rule, input, &error);
+    }
+}
+
+/// Switches all the layers of this network at once.
+impl<F, L> ModeAware for Sequential<F, L>
+    where F: Float, L: ?Sized + Compute<F> + ModeAware
+{
+    fn set_mode(&mut self, training: bool) {
+        for layer in &mut self.layers {
+            layer.set_mode(training);
+        }
+    }
+}
+
+impl<F, L> Trainable for Sequential<F, L>
+    where F: Float, L: ?Sized + Compute<F> + Trainable
+{
+    fn num_parameters(&self) -> usize {
+        self.layers.iter().map(|l| l.num_parameters()).sum()
+    }
+
+    fn has_nan(&self) -> bool {
+        self.layers.iter().any(|l| l.has_nan())
+    }
+}
+
+impl<F, L> Parametrized<F> for Sequential<F, L>
+    where F: Float, L: ?Sized + Compute<F> + Parametrized<F>
+{
+    fn parameters_iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a F> + 'a> {
+        Box::new(self.layers.iter().flat_map(|l| l.parameters_iter()))
+    }
+
+    fn parameters_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = &'a mut F> + 'a> {
+        Box::new(self.layers.iter_mut().flat_map(|l| l.parameters_mut()))
+    }
+}
+
+/*
+ * Residual
+ */
+
+/// An adapter adding a skip connection around a network: its output is the
+/// sum of its input and of the output of the inner network.
+///
+/// The inner network must have as many outputs as inputs.
+pub struct Residual<F, C> where F: Float, C: Compute<F> {
+    _marker: PhantomData<F>,
+    inner: C
+}
+
+impl<F, C> Residual<F, C>
+    where F: Float, C: Compute<F>
+{
+    /// Wraps given network with a skip connection.
+    ///
+    /// Fails if the output size of `inner` differs from its input size.
+    pub fn new(inner: C) -> Result<Residual<F, C>, ShapeError> {
+        if inner.output_size() != inner.input_size() {
+            return Err(ShapeError { expected: inner.input_size(), actual: inner.output_size() });
+        }
+        Ok(Residual { _marker: PhantomData, inner })
+    }
+
+    /// Get access to the inner network.
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+}
+
+impl<F, C> Compute<F> for Residual<F, C>
+    where F: Float, C: Compute<F>
+{
+    fn compute(&self, input: &[F]) -> Vec<F> {
+        let mut output = self.inner.compute(input);
+        for (i, y) in output.iter_mut().enumerate() {
+            *y = *y + input.get(i).cloned().unwrap_or(zero());
+        }
+        output
+    }
+
+    fn input_size(&self) -> usize {
+        self.inner.input_size()
+    }
+
+    fn output_size(&self) -> usize {
+        self.inner.output_size()
+    }
+}
+
+/// The gradient returned by a residual adapter is the sum of the one of the
+/// skip connection, which is the error itself, and of the one returned by the
+/// training of the inner network.
+impl<F, C, M> BackpropTrain<F, M> for Residual<F, C>
+    where F: Float,
+          C: BackpropTrain<F, M> + Compute<F>,
+          M: Method
+{
+    fn backprop_train(&mut self, rule: &M, input: &[F], error: &[F]) -> Vec<F> {
+        let mut returned = self.inner.backprop_train(rule, input, error);
+        returned.resize(self.inner.input_size(), zero());
+        for (i, g) in returned.iter_mut().enumerate() {
+            *g = *g + error.get(i).cloned().unwrap_or(zero());
+        }
+        returned
+    }
+}
+
+/// The supervised training on a residual adapter uses the mean squared error:
+/// it is trained by backpropagation with the error signal
+/// `2*(output - target)/n`.
+impl<F, C, M> SupervisedTrain<F, M> for Residual<F, C>
+    where F: Float,
+          C: BackpropTrain<F, M> + Compute<F>,
+          M: Method
+{
+    fn supervised_train(&mut self, rule: &M, input: &[F], target: &[F]) {
+        let error = output_error(&self.compute(input), target);
+        self.backprop_train(rule, input, &error);
+    }
+}
+
+impl<F, C> ModeAware for Residual<F, C>
+    where F: Float, C: Compute<F> + ModeAware
+{
+    fn set_mode(&mut self, training: bool) {
+        self.inner.set_mode(training);
+    }
+}
+
+impl<F, C> Trainable for Residual<F, C>
+    where F: Float, C: Compute<F> + Trainable
+{
+    fn num_parameters(&self) -> usize {
+        self.inner.num_parameters()
+    }
+
+    fn has_nan(&self) -> bool {
+        self.inner.has_nan()
+    }
+}
+
+impl<F, C> Parametrized<F> for Residual<F, C>
+    where F: Float, C: Compute<F> + Parametrized<F>
+{
+    fn parameters_iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a F> + 'a> {
+        self.inner.parameters_iter()
+    }
+
+    fn parameters_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = &'a mut F> + 'a> {
+        self.inner.parameters_mut()
+    }
+}
+
+/*
+ * Activity regularization
+ */
+
+/// A penalty on the activations of a network, see `ActivityReg`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Penalty {
+    /// `sum( |a_i| )`, encouraging sparse activations.
+    L1,
+    /// `sum( a_i^2 )`, encouraging small activations.
+    L2
+}
+
+/// An adapter adding a penalty on the output of the wrapped network to its
+/// training error, scaled by `lambda`.
+///
+/// Its output is the one of the wrapped network, but its training by
+/// backpropagation adds the gradient of `lambda * penalty(output)` to the
+/// incoming error, pushing the activations towards zero. This is for example
+/// used to get sparse codes in autoencoders.
+pub struct ActivityReg<F, C> where F: Float, C: Compute<F> {
+    inner: C,
+    penalty: Penalty,
+    lambda: F
+}
+
+impl<F, C> ActivityReg<F, C>
+    where F: Float, C: Compute<F>
+{
+    /// Wraps given network with given penalty on its output.
+    pub fn new(inner: C, penalty: Penalty, lambda: F) -> ActivityReg<F, C> {
+        ActivityReg { inner, penalty, lambda }
+    }
+
+    /// Get access to the inner network.
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    /// The activations penalized for given input, which are the output of
+    /// the inner network.
+    pub fn activations(&self, input: &[F]) -> Vec<F> {
+        self.inner.compute(input)
+    }
+
+    /// The value of `lambda * penalty` for given activations, as returned by
+    /// `activations()` or `compute()`.
+    pub fn penalty_value(&self, activations: &[F]) -> F {
+        let sum = activations.iter().fold(zero::<F>(), |acc, &a| match self.penalty {
+            Penalty::L1 => acc + a.abs(),
+            Penalty::L2 => acc + a * a
+        });
+        self.lambda * sum
+    }
+}
+
+impl<F, C> Compute<F> for ActivityReg<F, C>
+    where F: Float, C: Compute<F>
+{
+    fn compute(&self, input: &[F]) -> Vec<F> {
+        self.activations(input)
+    }
+
+    fn input_size(&self) -> usize {
+        self.inner.input_size()
+    }
+
+    fn output_size(&self) -> usize {
+        self.inner.output_size()
+    }
+}
+
+/// The error passed to the inner network is the incoming one plus the
+/// gradient of the penalty:
+///
+/// ```text
+/// L1: error_i + lambda * sign(a_i)
+/// L2: error_i + 2 * lambda * a_i
+/// ```
+///
+/// with a zero subgradient for the L1 penalty of a zero activation.
+impl<F, C, M> BackpropTrain<F, M> for ActivityReg<F, C>
+    where F: Float,
+          C: BackpropTrain<F, M> + Compute<F>,
+          M: Method
+{
+    fn backprop_train(&mut self, rule: &M, input: &[F], error: &[F]) -> Vec<F> {
+        let activations = self.compute(input);
+        let two = F::one() + F::one();
+        let error = activations.iter().enumerate().map(|(i, &a)| {
+            let grad = match self.penalty {
+                Penalty::L1 if a != zero() => self.lambda * a.signum(),
+                Penalty::L1 => zero(),
+                Penalty::L2 => two * self.lambda * a
+            };
+            error.get(i).cloned().unwrap_or(zero()) + grad
+        }).collect::<Vec<_>>();
+        self.inner.backprop_train(rule, input, &error)
+    }
+}
+
+impl<F, C> ModeAware for ActivityReg<F, C>
+    where F: Float, C: Compute<F> + ModeAware
+{
+    fn set_mode(&mut self, training: bool) {
+        self.inner.set_mode(training);
+    }
+}
+
+impl<F, C> Trainable for ActivityReg<F, C>
+    where F: Float, C: Compute<F> + Trainable
+{
+    fn num_parameters(&self) -> usize {
+        self.inner.num_parameters()
+    }
+
+    fn has_nan(&self) -> bool {
+        self.inner.has_nan()
+    }
+}
+
+impl<F, C> Parametrized<F> for ActivityReg<F, C>
+    where F: Float, C: Compute<F> + Parametrized<F>
+{
+    fn parameters_iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a F> + 'a> {
+        self.inner.parameters_iter()
+    }
+
+    fn parameters_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = &'a mut F> + 'a> {
+        self.inner.parameters_mut()
+    }
+}
+
 /*
  * Fixed output
  */
@@ -186,7 +729,7 @@ impl Identity {
     /// Creates a new identity network of given size
     pub fn new(size: usize) -> Identity {
         Identity {
-            size: size
+            size
         }
     }
 }
@@ -194,9 +737,7 @@ impl Identity {
 impl<F: Float> Compute<F> for Identity {
     fn compute(&self, input: &[F]) -> Vec<F> {
         let mut out = input.to_owned();
-        out.truncate(self.size);
-        let outsize = out.len();
-        if outsize < self.size { out.extend(repeat(zero::<F>()).take(self.size - outsize)); }
+        out.resize(self.size, zero());
         out
     }
 
@@ -209,11 +750,135 @@ impl<F: Float> Compute<F> for Identity {
     }
 }
 
+impl<F: Float> ModeAware for FixedOutput<F> {}
+
+impl<F: Float> Trainable for FixedOutput<F> {}
+
+impl<F: Float> Parametrized<F> for FixedOutput<F> {}
+
+/// The identity has nothing to train, and returns the error unchanged.
+impl<F: Float, M: Method> BackpropTrain<F, M> for Identity {
+    fn backprop_train(&mut self, _rule: &M, _input: &[F], error: &[F]) -> Vec<F> {
+        let mut returned = error.to_owned();
+        returned.resize(self.size, zero());
+        returned
+    }
+}
+
+impl ModeAware for Identity {}
+
+impl Trainable for Identity {}
+
+impl<F: Float> Parametrized<F> for Identity {}
+
+/*
+ * Flatten
+ */
+
+/// A network turning a multi-dimensional input into a flat vector.
+///
+/// As all the networks of this crate work on flat vectors, this is the
+/// identity on the data: it only records the logical shape of its input,
+/// for example `[channels, length]` for the output of a `Conv1D`, stored in
+/// row-major order (the last dimension varying the fastest). It documents the
+/// junction between such layers and the dense ones in a `Sequential`, and
+/// converts between positions in the shape and in the flat vector.
+pub struct Flatten {
+    shape: Vec<usize>
+}
+
+impl Flatten {
+    /// Creates a new flatten network for inputs of given shape.
+    pub fn new(shape: &[usize]) -> Flatten {
+        Flatten { shape: shape.to_owned() }
+    }
+
+    /// The shape of the input of this network.
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+
+    /// The position in the flat output of the value at given coordinates
+    /// in the input, if they are within its shape.
+    pub fn index(&self, coords: &[usize]) -> Option<usize> {
+        if coords.len() != self.shape.len() {
+            return None;
+        }
+        let mut index = 0;
+        for (&c, &dim) in coords.iter().zip(&self.shape) {
+            if c >= dim {
+                return None;
+            }
+            index = index * dim + c;
+        }
+        Some(index)
+    }
+
+    /// The coordinates in the input of the value at given position in the
+    /// flat output, if it is within its size.
+    pub fn coords(&self, mut index: usize) -> Option<Vec<usize>> {
+        if index >= self.shape.iter().product() {
+            return None;
+        }
+        let mut coords = vec![0; self.shape.len()];
+        for (c, &dim) in coords.iter_mut().zip(&self.shape).rev() {
+            *c = index % dim;
+            index /= dim;
+        }
+        Some(coords)
+    }
+}
+
+impl<F: Float> Compute<F> for Flatten {
+    fn compute(&self, input: &[F]) -> Vec<F> {
+        let mut out = input.to_owned();
+        out.resize(self.shape.iter().product(), zero());
+        out
+    }
+
+    fn input_size(&self) -> usize {
+        self.shape.iter().product()
+    }
+
+    fn output_size(&self) -> usize {
+        self.shape.iter().product()
+    }
+}
+
+/// Like the identity, flattening has nothing to train, and returns the error
+/// unchanged.
+impl<F: Float, M: Method> BackpropTrain<F, M> for Flatten {
+    fn backprop_train(&mut self, _rule: &M, _input: &[F], error: &[F]) -> Vec<F> {
+        let mut returned = error.to_owned();
+        returned.resize(self.shape.iter().product(), zero());
+        returned
+    }
+}
+
+impl ModeAware for Flatten {}
+
+impl Trainable for Flatten {}
+
+impl<F: Float> Parametrized<F> for Flatten {}
+
 #[cfg(test)]
 mod tests {
-    use super::{Identity, Chain, Parallel};
+    use super::{Identity, Flatten, Chain, Parallel, Sequential, Residual, BackpropLayer};
+    use super::{ActivityReg, Penalty};
 
-    use Compute;
+    use {Compute, ShapeError, SupervisedTrain, BackpropTrain, Trainable, Parametrized};
+    #[cfg(feature = "std")]
+    use ModeAware;
+    use activations::{self, sigmoid};
+    use feedforward::FeedforwardLayer;
+    #[cfg(feature = "std")]
+    use dropout::Dropout;
+    use conv::Conv1D;
+    use loss::{CrossEntropy, train_step};
+    use prelu::PReLULayer;
+    use softmax::SoftmaxLayer;
+    use testing::random;
+    use training::{Adam, GradientDescent};
 
     #[test]
     fn identity() {
@@ -228,9 +893,250 @@ mod tests {
         assert_eq!(ch.compute(&[1.0f32, 2.0, 3.0]), [1.0f32, 2.0, 3.0, 0.0, 0.0, 0.0])
     }
 
+    #[test]
+    fn chain_feedforward() {
+        let first = FeedforwardLayer::from_weights(2, vec![1.0f64, 2.0, -1.0, 0.5], vec![0.0, 1.0],
+                                                   activations::identity()).unwrap();
+        let second = FeedforwardLayer::from_weights(2, vec![2.0, -1.0], vec![0.5], activations::identity()).unwrap();
+        let mut ch = Chain::try_new(first, second).unwrap();
+        // first: [1 + 2*2, -1 + 0.5*2 + 1] = [5, 1], second: 2*5 - 1 + 0.5
+        assert_eq!(ch.compute(&[1.0, 2.0]), vec![9.5]);
+
+        // the gradient goes through the weights of both layers
+        let returned = ch.backprop_train(&GradientDescent::new(0.1), &[1.0, 2.0], &[1.0]);
+        assert_eq!(returned, vec![3.0, 3.5]);
+        assert_eq!(ch.compute(&[0.0, 0.0]), vec![(2.0 - 0.1 * 5.0) * -0.2 + (-1.0 - 0.1) * 1.1 + 0.4]);
+
+        let first = FeedforwardLayer::new(2, 3, activations::identity::<f64>());
+        let second = FeedforwardLayer::new(2, 1, activations::identity::<f64>());
+        assert_eq!(Chain::try_new(first, second).err(), Some(ShapeError { expected: 3, actual: 2 }));
+    }
+
     #[test]
     fn parallel() {
         let ch = Parallel::new(Identity::new(4), Identity::new(2));
         assert_eq!(ch.compute(&[1.0f32, 2.0, 3.0]), [1.0f32, 2.0, 3.0, 0.0, 1.0, 2.0])
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn parallel_backprop() {
+        let mut ch = Parallel::new(Identity::new(3), Identity::new(3));
+        assert_eq!(ch.compute(&[1.0f32, 2.0, 3.0]), [1.0f32, 2.0, 3.0, 1.0, 2.0, 3.0]);
+        let returned = ch.backprop_train(&GradientDescent::new(0.1), &[1.0, 2.0, 3.0],
+                                         &[1.0, 0.5, -1.0, 2.0, 0.5, 1.0]);
+        assert_eq!(returned, vec![3.0, 1.0, 0.0]);
+
+        // a parallel adapter can be nested for more branches
+        let mut ch = Parallel::new(Identity::new(2), Parallel::new(Identity::new(2), Identity::new(1)));
+        assert_eq!(ch.output_size(), 5);
+        let returned = ch.backprop_train(&GradientDescent::new(0.1), &[1.0f32, 2.0],
+                                         &[1.0, 1.0, 2.0, 2.0, 4.0]);
+        assert_eq!(returned, vec![7.0, 3.0]);
+    }
+
+    #[test]
+    fn sequential() {
+        let mut seq = Sequential::<f32>::new();
+        assert_eq!(seq.push(Box::new(Identity::new(3))), Ok(()));
+        assert_eq!(seq.push(Box::new(Identity::new(3))), Ok(()));
+        assert_eq!(seq.input_size(), 3);
+        assert_eq!(seq.output_size(), 3);
+        assert_eq!(seq.compute(&[1.0f32, 2.0, 3.0]), [1.0f32, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn sequential_mismatch() {
+        let mut seq = Sequential::<f32>::new();
+        seq.push(Box::new(Identity::new(3))).unwrap();
+        assert_eq!(seq.push(Box::new(Identity::new(2))),
+                   Err(ShapeError { expected: 3, actual: 2 }));
+        assert_eq!(seq.len(), 1);
+    }
+
+    #[test]
+    fn sequential_xor() {
+        // a deterministic pseudo-random initialization.
+        let mut random = random();
+        let mut net: Sequential<f32, dyn BackpropLayer<f32, GradientDescent<f32>>> = Sequential::new();
+        net.push(Box::new(FeedforwardLayer::new_from(2, 4, sigmoid(), &mut random))).unwrap();
+        net.push(Box::new(FeedforwardLayer::new_from(4, 1, sigmoid(), &mut random))).unwrap();
+        assert_eq!(net.num_parameters(), (2*4 + 4) + (4 + 1));
+        let samples = [([0.0f32, 0.0], [0.0f32]), ([0.0, 1.0], [1.0]),
+                       ([1.0, 0.0], [1.0]), ([1.0, 1.0], [0.0])];
+        let loss = |net: &Sequential<f32, dyn BackpropLayer<f32, GradientDescent<f32>>>| {
+            samples.iter().fold(0.0, |acc, (i, t)| {
+                acc + (net.compute(i)[0] - t[0]).powi(2)
+            })
+        };
+        let rule = GradientDescent::new(0.5f32);
+        let before = loss(&net);
+        for _ in 0..2000 {
+            for (i, t) in &samples {
+                net.supervised_train(&rule, i, t);
+            }
+        }
+        let after = loss(&net);
+        assert!(after < before);
+    }
+
+    #[test]
+    fn sequential_frozen() {
+        let mut random = random();
+        let mut first = FeedforwardLayer::new_from(2, 3, sigmoid(), &mut random);
+        let mut second = FeedforwardLayer::new_from(3, 1, sigmoid(), &mut random);
+        let mut reference = Chain::new(FeedforwardLayer::from_weights(2, first.weights().to_vec(),
+                                                                      first.biases().to_vec(), sigmoid()).unwrap(),
+                                       FeedforwardLayer::from_weights(3, second.weights().to_vec(),
+                                                                      second.biases().to_vec(), sigmoid()).unwrap());
+        first.set_frozen(true);
+        second.set_frozen(true);
+        let mut net: Sequential<f32, dyn BackpropLayer<f32, GradientDescent<f32>>> = Sequential::new();
+        net.push(Box::new(first)).unwrap();
+        net.push(Box::new(second)).unwrap();
+
+        // the gradient still flows through the frozen layers, which stay fixed
+        let rule = GradientDescent::new(0.5f32);
+        let input = [1.0f32, -0.5];
+        let before = net.compute(&input);
+        let returned = net.backprop_train(&rule, &input, &[1.0]);
+        assert_eq!(returned, reference.backprop_train(&rule, &input, &[1.0]));
+        assert!(returned.iter().any(|&g| g != 0.0));
+        assert_eq!(net.compute(&input), before);
+        assert!(reference.compute(&input) != before);
+    }
+
+    #[test]
+    fn residual() {
+        let mut residual = Residual::new(FeedforwardLayer::new(3, 3, activations::identity())).unwrap();
+        assert_eq!(residual.compute(&[1.0f64, -2.0, 0.5]), vec![1.0, -2.0, 0.5]);
+
+        // the identity path passes the error through, while the inner layer
+        // is trained and returns a zero gradient as its weights are zero
+        let error = [1.0f64, 0.5, -1.0];
+        let returned = residual.backprop_train(&GradientDescent::new(1.0), &[1.0, -2.0, 0.5], &error);
+        assert_eq!(returned, error.to_vec());
+        assert_eq!(residual.inner().biases(), &[-1.0, -0.5, 1.0]);
+        assert_eq!(residual.compute(&[0.0, 0.0, 0.0]), vec![-1.0, -0.5, 1.0]);
+
+        // the inner gradient is added to the identity one
+        let returned = residual.backprop_train(&GradientDescent::new(1.0), &[1.0, -2.0, 0.5], &error);
+        assert!(returned != error.to_vec());
+    }
+
+    #[test]
+    fn residual_mismatch() {
+        match Residual::new(FeedforwardLayer::new(3, 2, activations::identity::<f64>())) {
+            Err(e) => assert_eq!(e, ShapeError { expected: 3, actual: 2 }),
+            Ok(_) => panic!("A residual adapter around a non-square layer must fail.")
+        }
+    }
+
+    #[test]
+    fn flatten() {
+        let flatten = Flatten::new(&[2, 3]);
+        assert_eq!(flatten.shape(), &[2, 3]);
+        let input = [1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0];
+        assert_eq!(Compute::<f32>::input_size(&flatten), 6);
+        assert_eq!(flatten.compute(&input), input.to_vec());
+        let mut flatten = flatten;
+        let error = [0.5f32, -1.0, 0.0, 2.0, 1.0, -0.5];
+        assert_eq!(flatten.backprop_train(&GradientDescent::new(1.0), &input, &error), error.to_vec());
+
+        assert_eq!(flatten.index(&[1, 0]), Some(3));
+        assert_eq!(flatten.index(&[0, 3]), None);
+        assert_eq!(flatten.coords(5), Some(vec![1, 2]));
+        assert_eq!(flatten.coords(6), None);
+        for i in 0..6 {
+            assert_eq!(flatten.index(&flatten.coords(i).unwrap()), Some(i));
+        }
+
+        // between a convolution and a dense layer
+        let mut net: Sequential<f32, dyn BackpropLayer<f32, GradientDescent<f32>>> = Sequential::new();
+        net.push(Box::new(Conv1D::new(1, 5, 2, 3, 1, activations::identity()))).unwrap();
+        net.push(Box::new(Flatten::new(&[2, 3]))).unwrap();
+        net.push(Box::new(FeedforwardLayer::new(6, 1, sigmoid()))).unwrap();
+        assert_eq!(net.compute(&[1.0, 2.0, 3.0, 4.0, 5.0]), vec![0.5]);
+    }
+
+    #[test]
+    fn sequential_parameters() {
+        let mut net: Sequential<f64, dyn BackpropLayer<f64, GradientDescent<f64>>> = Sequential::new();
+        net.push(Box::new(FeedforwardLayer::from_weights(1, vec![1.0, 2.0], vec![3.0, 4.0], activations::identity()).unwrap())).unwrap();
+        net.push(Box::new(FeedforwardLayer::from_weights(2, vec![5.0, 6.0], vec![7.0], activations::identity()).unwrap())).unwrap();
+        assert_eq!(net.parameters_iter().cloned().collect::<Vec<_>>(), vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0]);
+        assert_eq!(net.parameters_iter().count(), net.num_parameters());
+        for p in net.parameters_mut() {
+            *p = 0.0;
+        }
+        assert_eq!(net.compute(&[1.0]), vec![0.0]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn sequential_mode() {
+        let mut net: Sequential<f64, dyn BackpropLayer<f64, GradientDescent<f64>>> = Sequential::new();
+        net.push(Box::new(Identity::new(32))).unwrap();
+        net.push(Box::new(Dropout::with_seed(32, 0.5, [1, 2, 3, 4]).unwrap())).unwrap();
+        let input = [1.0; 32];
+        // in training mode some inputs are dropped and the others rescaled
+        let output = net.compute(&input);
+        assert!(output.contains(&0.0) && output.contains(&2.0));
+
+        net.set_mode(false);
+        assert_eq!(net.compute(&input), input.to_vec());
+        net.set_mode(true);
+        assert!(net.compute(&input).contains(&0.0));
+    }
+
+    #[test]
+    fn sequential_adam() {
+        let mut net: Sequential<f64, dyn BackpropLayer<f64, Adam<f64>>> = Sequential::new();
+        net.push(Box::new(FeedforwardLayer::new_from(2, 3, activations::identity(), random()))).unwrap();
+        net.push(Box::new(PReLULayer::new(3, 0.25))).unwrap();
+        net.push(Box::new(FeedforwardLayer::new_from(3, 2, activations::identity(), random()))).unwrap();
+        net.push(Box::new(SoftmaxLayer::new(2))).unwrap();
+        let alphas = |net: &Sequential<f64, dyn BackpropLayer<f64, Adam<f64>>>| {
+            net.parameters_iter().skip(9).take(3).cloned().collect::<Vec<_>>()
+        };
+        assert_eq!(alphas(&net), vec![0.25; 3]);
+        let rule = Adam::new(0.01);
+        let samples = [([1.0, -1.0], [1.0, 0.0]), ([-1.0, 1.0], [0.0, 1.0])];
+        let losses = (0..50).map(|_| {
+            samples.iter().map(|(x, t)| train_step(&mut net, &rule, &CrossEntropy, x, t)).sum::<f64>()
+        }).collect::<Vec<_>>();
+        assert!(losses[49] < losses[0]);
+        assert!(alphas(&net) != vec![0.25; 3]);
+    }
+
+    #[test]
+    fn activity_regularization() {
+        let layer = FeedforwardLayer::from_weights(2, vec![1.0f64, 0.5, -0.5, 2.0, 0.0, 0.0], vec![0.0, 0.0, 0.0],
+                                                   activations::identity()).unwrap();
+        let mut reg = ActivityReg::new(layer, Penalty::L1, 0.1);
+        let input = [1.0, 1.0];
+        let rule = GradientDescent::new(0.1);
+        let before = reg.compute(&input);
+        assert_eq!(before, vec![1.5, 1.5, 0.0]);
+        assert_eq!(reg.activations(&input), before);
+        assert!((reg.penalty_value(&before) - 0.3).abs() < 1e-12);
+        fn is_sync<T: Sync>(_: &T) {}
+        is_sync(&reg);
+
+        // without any upstream error, the penalty alone shrinks the activations
+        let returned = reg.backprop_train(&rule, &input, &[0.0, 0.0, 0.0]);
+        // the error given to the layer was lambda * sign(a) = [0.1, 0.1, 0.0]
+        assert!((returned[0] - 0.1 * (1.0 - 0.5)).abs() < 1e-12);
+        assert!((returned[1] - 0.1 * (0.5 + 2.0)).abs() < 1e-12);
+        let after = reg.compute(&input);
+        assert!(after[0].abs() < before[0].abs() && after[1].abs() < before[1].abs());
+        assert_eq!(after[2], 0.0);
+        for _ in 0..10 {
+            reg.backprop_train(&rule, &input, &[0.0, 0.0, 0.0]);
+        }
+        assert!(reg.compute(&input)[0].abs() < after[0].abs());
+
+        let layer = FeedforwardLayer::from_weights(1, vec![2.0f64], vec![0.0], activations::identity()).unwrap();
+        let mut reg = ActivityReg::new(layer, Penalty::L2, 0.5);
+        assert_eq!(reg.backprop_train(&rule, &[1.0], &[0.0]), vec![2.0 * 0.5 * 2.0 * 2.0]);
+    }
+}
@@ -0,0 +1,51 @@
+//! Checks that `compute_into` does not allocate, using an allocator counting
+//! the allocations made by the current thread.
+
+extern crate silinapse;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use silinapse::{Compute, FeedforwardLayer};
+use silinapse::activations::sigmoid;
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|c| c.set(c.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(|c| c.get())
+}
+
+#[test]
+fn compute_into_does_not_allocate() {
+    let layer = FeedforwardLayer::new_from(50, 20, sigmoid(), || 0.1f32);
+    let input = vec![1.0f32; 50];
+    let mut out = vec![0.0f32; 20];
+
+    let before = allocations();
+    for _ in 0..100 {
+        layer.compute_into(&input, &mut out);
+    }
+    assert_eq!(allocations(), before);
+
+    // whereas compute allocates its output
+    let _ = layer.compute(&input);
+    assert!(allocations() > before);
+}
@@ -0,0 +1,35 @@
+//! Checks that the core of the library can be used without the standard
+//! library, run it with:
+//!
+//! ```text
+//! cargo test --no-default-features --features libm --test no_std
+//! ```
+
+#![no_std]
+
+#[macro_use]
+extern crate alloc;
+extern crate silinapse;
+
+use silinapse::{Compute, FeedforwardLayer};
+use silinapse::activations::sigmoid;
+use silinapse::loss::{MeanSquaredError, EpochOptions, train_epochs};
+use silinapse::training::GradientDescent;
+
+#[test]
+fn compute() {
+    let layer = FeedforwardLayer::new_from(2, 1, sigmoid(), || 0.5f64);
+    let output = layer.compute(&[1.0, -3.0]);
+    assert_eq!(output.len(), 1);
+    // sigmoid(0.5 - 1.5 + 0.5) = 0.37754...
+    assert!(output[0] > 0.3775 && output[0] < 0.3776);
+}
+
+#[test]
+fn train() {
+    let mut layer = FeedforwardLayer::new(1, 1, sigmoid());
+    let samples = [(vec![1.0f64], vec![1.0]), (vec![-1.0], vec![0.0])];
+    let rule = GradientDescent::new(0.5);
+    let losses = train_epochs(&mut layer, &rule, &MeanSquaredError, &samples, 20, EpochOptions::new());
+    assert!(losses[19] < losses[0]);
+}